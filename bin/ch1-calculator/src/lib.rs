@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+use eventsourcing::Aggregate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Number of operations that can be undone.
+pub const HISTORY_LIMIT: usize = 16;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Calculator {
+    pub value: i64,
+    /// Operations that can be undone, oldest first.
+    history: VecDeque<Applied>,
+    /// Undone operations that can be redone, most recently undone last.
+    undone: Vec<Applied>,
+}
+
+/// An operation and the value it was applied to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Applied {
+    event: CalculatorEvent,
+    value_before: i64,
+}
+
+#[derive(Debug, Clone)]
+pub enum CalculatorCommand {
+    Add { value: i64 },
+    Sub { value: i64 },
+    Mul { value: i64 },
+    Div { value: i64 },
+    Undo,
+    Redo,
+}
+
+/// `Undone` carries the operation compensating the last one, e.g. `DidSub`
+/// for a `DidAdd`. History is never rewritten. `Redone` applies an undone
+/// operation again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CalculatorEvent {
+    DidAdd { value: i64 },
+    DidSub { value: i64 },
+    DidMul { value: i64 },
+    DidDiv { value: i64 },
+    Undone { compensation: Box<CalculatorEvent> },
+    Redone { event: Box<CalculatorEvent> },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CalculatorError {
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    #[error("Nothing to redo")]
+    NothingToRedo,
+}
+
+impl Aggregate for Calculator {
+    type Command = CalculatorCommand;
+    type Event = CalculatorEvent;
+    type Error = CalculatorError;
+
+    fn handle_command(
+        &self,
+        command: CalculatorCommand,
+    ) -> Result<Vec<CalculatorEvent>, CalculatorError> {
+        let event = match command {
+            CalculatorCommand::Add { value } => CalculatorEvent::DidAdd { value },
+            CalculatorCommand::Sub { value } => CalculatorEvent::DidSub { value },
+            CalculatorCommand::Mul { value } => CalculatorEvent::DidMul { value },
+            CalculatorCommand::Div { value: 0 } => return Err(CalculatorError::DivisionByZero),
+            CalculatorCommand::Div { value } => CalculatorEvent::DidDiv { value },
+            CalculatorCommand::Undo => {
+                let last = self.history.back().ok_or(CalculatorError::NothingToUndo)?;
+                CalculatorEvent::Undone {
+                    compensation: Box::new(self.compensation(last)),
+                }
+            }
+            CalculatorCommand::Redo => {
+                let last = self.undone.last().ok_or(CalculatorError::NothingToRedo)?;
+                CalculatorEvent::Redone {
+                    event: Box::new(last.event.clone()),
+                }
+            }
+        };
+        Ok(vec![event])
+    }
+
+    fn apply_event(&mut self, event: CalculatorEvent) {
+        match event {
+            CalculatorEvent::Undone { compensation } => {
+                self.compute(&compensation);
+                if let Some(applied) = self.history.pop_back() {
+                    self.undone.push(applied);
+                }
+            }
+            CalculatorEvent::Redone { event } => {
+                self.undone.pop();
+                self.record(*event);
+            }
+            event => {
+                self.undone.clear();
+                self.record(event);
+            }
+        }
+    }
+}
+
+impl Calculator {
+    fn record(&mut self, event: CalculatorEvent) {
+        let value_before = self.value;
+        self.compute(&event);
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(Applied {
+            event,
+            value_before,
+        });
+    }
+
+    fn compute(&mut self, event: &CalculatorEvent) {
+        match *event {
+            CalculatorEvent::DidAdd { value } => self.value += value,
+            CalculatorEvent::DidSub { value } => self.value -= value,
+            CalculatorEvent::DidMul { value } => self.value *= value,
+            CalculatorEvent::DidDiv { value } => self.value /= value,
+            CalculatorEvent::Undone { .. } | CalculatorEvent::Redone { .. } => {}
+        }
+    }
+
+    /// The inverse operation when it restores the previous value exactly,
+    /// otherwise an addition of the difference, e.g. after multiplying by 0
+    /// or a division with a remainder.
+    fn compensation(&self, applied: &Applied) -> CalculatorEvent {
+        let inverse = match applied.event {
+            CalculatorEvent::DidAdd { value } => Some(CalculatorEvent::DidSub { value }),
+            CalculatorEvent::DidSub { value } => Some(CalculatorEvent::DidAdd { value }),
+            CalculatorEvent::DidMul { value } if value != 0 => {
+                Some(CalculatorEvent::DidDiv { value })
+            }
+            CalculatorEvent::DidDiv { value } if applied.value_before % value == 0 => {
+                Some(CalculatorEvent::DidMul { value })
+            }
+            _ => None,
+        };
+        inverse.unwrap_or_else(|| CalculatorEvent::DidAdd {
+            value: applied.value_before - self.value,
+        })
+    }
+}
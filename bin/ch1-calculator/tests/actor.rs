@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use ch1_calculator::{Calculator, CalculatorCommand, CalculatorError, CalculatorEvent};
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+use eventsourcing::{AggregateActor, AggregateMessage, AggregateRef};
+use ractor::{call_t, Actor};
+
+const RPC_TIMEOUT_MS: u64 = 1000;
+const STREAM_ID: &str = "calculator/1";
+
+async fn spawn(store: &Arc<dyn EventStore>) -> AggregateRef<Calculator> {
+    let (actor, _) = Actor::spawn(
+        None,
+        AggregateActor::<Calculator>::new(Arc::clone(store)),
+        STREAM_ID.to_string(),
+    )
+    .await
+    .unwrap();
+    actor
+}
+
+async fn execute(
+    actor: &AggregateRef<Calculator>,
+    command: CalculatorCommand,
+) -> Result<Vec<CalculatorEvent>, CalculatorError> {
+    call_t!(
+        actor,
+        AggregateMessage::ExecuteWithReply,
+        RPC_TIMEOUT_MS,
+        command
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn calculator_is_persisted_and_rehydrated() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let actor = spawn(&store).await;
+
+    assert_eq!(
+        execute(&actor, CalculatorCommand::Add { value: 8 }).await,
+        Ok(vec![CalculatorEvent::DidAdd { value: 8 }])
+    );
+    execute(&actor, CalculatorCommand::Div { value: 2 })
+        .await
+        .unwrap();
+    assert_eq!(
+        execute(&actor, CalculatorCommand::Div { value: 0 }).await,
+        Err(CalculatorError::DivisionByZero)
+    );
+    actor.stop(None);
+
+    // Rejected commands are not persisted.
+    assert_eq!(store.read_stream(STREAM_ID, 0).await.unwrap().len(), 2);
+
+    // The undo history is rebuilt along with the value.
+    let actor = spawn(&store).await;
+    let calculator = call_t!(actor, AggregateMessage::GetState, RPC_TIMEOUT_MS).unwrap();
+    assert_eq!(calculator.value, 4);
+    execute(&actor, CalculatorCommand::Undo).await.unwrap();
+    let calculator = call_t!(actor, AggregateMessage::GetState, RPC_TIMEOUT_MS).unwrap();
+    assert_eq!(calculator.value, 8);
+}
@@ -0,0 +1,92 @@
+use ch1_calculator::{
+    Calculator, CalculatorCommand, CalculatorError, CalculatorEvent, HISTORY_LIMIT,
+};
+use eventsourcing::testing::AggregateTestFixture;
+
+#[test]
+fn commands_become_events() {
+    let outcome = AggregateTestFixture::<Calculator>::new()
+        .given([CalculatorEvent::DidAdd { value: 8 }])
+        .when(CalculatorCommand::Div { value: 2 })
+        .then_events([CalculatorEvent::DidDiv { value: 2 }]);
+    assert_eq!(outcome.aggregate().value, 4);
+}
+
+#[test]
+fn division_by_zero_is_rejected() {
+    let outcome = AggregateTestFixture::<Calculator>::new()
+        .given([
+            CalculatorEvent::DidAdd { value: 8 },
+            CalculatorEvent::DidMul { value: 3 },
+        ])
+        .when(CalculatorCommand::Div { value: 0 })
+        .then_error(CalculatorError::DivisionByZero);
+    assert_eq!(outcome.aggregate().value, 24);
+}
+
+#[test]
+fn undo_emits_the_inverse_operation() {
+    let outcome = AggregateTestFixture::<Calculator>::new()
+        .given([
+            CalculatorEvent::DidAdd { value: 8 },
+            CalculatorEvent::DidMul { value: 3 },
+        ])
+        .when(CalculatorCommand::Undo)
+        .then_events([CalculatorEvent::Undone {
+            compensation: Box::new(CalculatorEvent::DidDiv { value: 3 }),
+        }]);
+    assert_eq!(outcome.aggregate().value, 8);
+}
+
+#[test]
+fn lossy_operations_are_undone_by_the_difference() {
+    AggregateTestFixture::<Calculator>::new()
+        .given([
+            CalculatorEvent::DidAdd { value: 7 },
+            CalculatorEvent::DidDiv { value: 2 },
+        ])
+        .when(CalculatorCommand::Undo)
+        .then_events([CalculatorEvent::Undone {
+            compensation: Box::new(CalculatorEvent::DidAdd { value: 4 }),
+        }]);
+}
+
+#[test]
+fn redo_reapplies_the_undone_operation() {
+    let outcome = AggregateTestFixture::<Calculator>::new()
+        .given([
+            CalculatorEvent::DidAdd { value: 8 },
+            CalculatorEvent::DidSub { value: 3 },
+            CalculatorEvent::Undone {
+                compensation: Box::new(CalculatorEvent::DidAdd { value: 3 }),
+            },
+        ])
+        .when(CalculatorCommand::Redo)
+        .then_events([CalculatorEvent::Redone {
+            event: Box::new(CalculatorEvent::DidSub { value: 3 }),
+        }]);
+    assert_eq!(outcome.aggregate().value, 5);
+
+    // A new operation discards what could be redone.
+    AggregateTestFixture::<Calculator>::new()
+        .given([
+            CalculatorEvent::DidAdd { value: 8 },
+            CalculatorEvent::Undone {
+                compensation: Box::new(CalculatorEvent::DidSub { value: 8 }),
+            },
+            CalculatorEvent::DidAdd { value: 1 },
+        ])
+        .when(CalculatorCommand::Redo)
+        .then_error(CalculatorError::NothingToRedo);
+}
+
+#[test]
+fn history_is_bounded() {
+    AggregateTestFixture::<Calculator>::new()
+        .given((0..HISTORY_LIMIT + 1).map(|_| CalculatorEvent::DidAdd { value: 1 }))
+        .given((0..HISTORY_LIMIT).map(|_| CalculatorEvent::Undone {
+            compensation: Box::new(CalculatorEvent::DidSub { value: 1 }),
+        }))
+        .when(CalculatorCommand::Undo)
+        .then_error(CalculatorError::NothingToUndo);
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use ch1_calculator::{Calculator, CalculatorCommand, CalculatorEvent};
+use eventsourcing::envelope::Upcasters;
+use eventsourcing::testing::{decode_fixture, AggregateTestFixture};
+
+#[test]
+fn golden_calculator_log_still_replays() {
+    let events: Vec<CalculatorEvent> = decode_fixture(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/calculator-events-v1.jsonl"),
+        &Upcasters::new(),
+    );
+    assert_eq!(
+        events[3],
+        CalculatorEvent::Undone {
+            compensation: Box::new(CalculatorEvent::DidDiv { value: 3 })
+        }
+    );
+    assert_eq!(
+        events[4],
+        CalculatorEvent::Redone {
+            event: Box::new(CalculatorEvent::DidMul { value: 3 })
+        }
+    );
+
+    // The undo history is rebuilt from the log too.
+    let outcome = AggregateTestFixture::<Calculator>::new()
+        .given(events)
+        .when(CalculatorCommand::Undo)
+        .then_events([CalculatorEvent::Undone {
+            compensation: Box::new(CalculatorEvent::DidAdd { value: 2 }),
+        }]);
+    assert_eq!(outcome.aggregate().value, 12);
+}
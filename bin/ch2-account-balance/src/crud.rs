@@ -0,0 +1,107 @@
+//! CRUD baseline of the account service, to measure what event sourcing
+//! costs and buys.
+//!
+//! [`CrudAccounts`] has the same API as [`AccountBalance`](crate::AccountBalance)
+//! and the same decision logic, the [`Account`] aggregate, but keeps only the
+//! current balance of each account in a SQLite row, updated in place. There
+//! is no history to audit, replay or project.
+//!
+//! Enabled with the `crud-baseline` feature.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use eventsourcing::Aggregate;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use thiserror::Error;
+
+use crate::{Account, AccountBalanceCommand, AccountBalanceRejection, AccountLimits};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS accounts (
+    account_number TEXT PRIMARY KEY,
+    balance INTEGER NOT NULL
+);
+"#;
+
+#[derive(Error, Debug)]
+pub enum CrudError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+pub struct CrudAccounts {
+    conn: Mutex<Connection>,
+    limits: AccountLimits,
+}
+
+impl CrudAccounts {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CrudError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, CrudError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, CrudError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            limits: AccountLimits::default(),
+        })
+    }
+
+    pub fn with_limits(mut self, limits: AccountLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Runs `command` against the account and returns its new balance, or
+    /// why the command was rejected.
+    ///
+    /// The balance is read and written in one immediate transaction, so
+    /// concurrent commands on the same account are serialized by SQLite
+    /// rather than by an actor.
+    pub async fn execute(
+        &self,
+        account_number: &str,
+        command: AccountBalanceCommand,
+    ) -> Result<Result<i64, AccountBalanceRejection>, CrudError> {
+        let mut conn = self.conn.lock().expect("poisoned sqlite connection");
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut account = Account::with_limits(self.limits);
+        account.balance = read_balance(&tx, account_number)?;
+        let events = match account.handle_command(command) {
+            Ok(events) => events,
+            Err(rejection) => return Ok(Err(rejection)),
+        };
+        for event in events {
+            account.apply_event(event);
+        }
+        tx.execute(
+            "INSERT INTO accounts (account_number, balance) VALUES (?1, ?2) \
+             ON CONFLICT (account_number) DO UPDATE SET balance = excluded.balance",
+            params![account_number, account.balance],
+        )?;
+        tx.commit()?;
+        Ok(Ok(account.balance))
+    }
+
+    /// Balance of an account, zero if it has never been used.
+    pub async fn balance(&self, account_number: &str) -> Result<i64, CrudError> {
+        let conn = self.conn.lock().expect("poisoned sqlite connection");
+        Ok(read_balance(&conn, account_number)?)
+    }
+}
+
+fn read_balance(conn: &Connection, account_number: &str) -> rusqlite::Result<i64> {
+    Ok(conn
+        .query_row(
+            "SELECT balance FROM accounts WHERE account_number = ?1",
+            params![account_number],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or_default())
+}
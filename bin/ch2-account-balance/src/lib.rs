@@ -0,0 +1,879 @@
+#[cfg(feature = "crud-baseline")]
+pub mod crud;
+
+use eventsourcing::budget::YieldBudget;
+use eventsourcing::bus::{EventBus, EventHandler};
+use eventsourcing::envelope::{EventEnvelope, Upcasters};
+use eventsourcing::projection::Projector;
+#[cfg(feature = "scheduler")]
+use eventsourcing::scheduler::{CommandDispatcher, DispatchError};
+use eventsourcing::snapshot::{Snapshot, SnapshotStore};
+use eventsourcing::store::{EventStore, ExpectedVersion, RecordedEvent, DEDUPE_WINDOW};
+use eventsourcing::Aggregate;
+use ractor::{
+    async_trait, concurrency::tokio_primitives::JoinHandle, errors::{MessagingErr, RactorErr, SpawnErr}, Actor,
+    ActorProcessingErr, ActorRef, RpcReplyPort, rpc::CallResult,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Hosts the [`Account`] aggregate of every account, one actor per account.
+///
+/// Decisions and state changes are left to [`Account`]. Unlike
+/// [`AggregateActor`](eventsourcing::AggregateActor), the actor also takes
+/// events from outside with caller-assigned ids, passivates idle accounts and
+/// bounds the events waiting to be handled.
+#[derive(Clone)]
+pub struct AccountBalance {
+    store: Arc<dyn EventStore>,
+    bus: EventBus,
+    limits: AccountLimits,
+    snapshots: Option<Arc<dyn SnapshotStore>>,
+    idle_timeout: Option<Duration>,
+    rpc_timeout: Duration,
+    /// Permits for events sent by [`AccountBalance::apply_event`] and not
+    /// handled yet, shared by all the accounts.
+    ingestion: Arc<Semaphore>,
+    max_pending_events: usize,
+}
+
+/// Rules enforced on [`AccountBalanceCommand`]s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccountLimits {
+    /// How far below zero a withdrawal may take the balance.
+    pub overdraft_limit: i64,
+    /// Largest amount of a single deposit or withdrawal, if any.
+    pub max_transaction: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct AccountBalanceArgs {
+    initial_balance: i64,
+    account_number: String,
+}
+
+impl AccountBalanceArgs {
+    pub fn new(account_number: String) -> Self {
+        Self {
+            initial_balance: 0,
+            account_number,
+        }
+    }
+}
+
+/// `WithdrawalRejected` records a withdrawal refused by the account rules,
+/// for audit. It does not change the balance.
+///
+/// Persisted as version [`EVENT_VERSION`]; older versions are read with
+/// [`upcasters`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccountBalanceEventPayload {
+    AmountWithdrawn { value: i64 },
+    AmountDeposited { value: i64 },
+    FeeApplied { value: i64 },
+    WithdrawalRejected { requested: i64, reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AccountBalanceCommand {
+    Deposit { value: i64 },
+    Withdraw { value: i64 },
+    /// Charged regardless of the transaction and overdraft limits, e.g. by
+    /// a [`Scheduler`](eventsourcing::scheduler::Scheduler).
+    ApplyFee { value: i64 },
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountBalanceRejection {
+    #[error("amount must be positive")]
+    NonPositiveAmount,
+    #[error("amount {value} exceeds the per-transaction maximum of {max}")]
+    TransactionLimitExceeded { value: i64, max: i64 },
+    #[error("withdrawing {value} from {balance} exceeds the overdraft limit of {overdraft_limit}")]
+    OverdraftLimitExceeded {
+        value: i64,
+        balance: i64,
+        overdraft_limit: i64,
+    },
+    #[error("amount {value} takes the balance of {balance} out of range")]
+    BalanceOutOfRange { value: i64, balance: i64 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountBalanceEvent {
+    pub account_number: String,
+    /// Caller-assigned id. Resending an event with the same id after a
+    /// transient failure does not apply it twice.
+    pub event_id: Option<String>,
+    pub payload: AccountBalanceEventPayload,
+}
+
+#[derive(Debug)]
+pub enum AccountBalanceMessage {
+    /// Held to the same rules as the matching command, a withdrawal beyond
+    /// the limits is recorded as `WithdrawalRejected`. The permit is released
+    /// once the event is handled.
+    ApplyEvent(AccountBalanceEvent, OwnedSemaphorePermit),
+    /// Validates a command against the limits. Replies the new balance or
+    /// the rejection. Events are recorded under the event id, if any, and a
+    /// command resent with the id of applied events only replies the balance,
+    /// or the rejection it got the first time.
+    Execute(
+        AccountBalanceCommand,
+        Option<String>,
+        RpcReplyPort<Result<i64, AccountBalanceRejection>>,
+    ),
+    GetBalance(RpcReplyPort<i64>),
+    /// Sent periodically when passivation is enabled.
+    CheckIdle,
+}
+
+pub struct AccountBalanceState {
+    account_number: String,
+    account: Account,
+    version: u64,
+    /// Ids of the last [`DEDUPE_WINDOW`] events applied.
+    recent_event_ids: VecDeque<String>,
+    /// Rejections recorded under the ids in `recent_event_ids`.
+    recent_rejections: HashMap<String, AccountBalanceRejection>,
+    last_active: Instant,
+    passivating: bool,
+}
+
+/// Part of the state saved when an idle account is passivated.
+#[derive(Serialize, Deserialize)]
+struct AccountSnapshot {
+    balance: i64,
+    recent_event_ids: VecDeque<String>,
+    /// Missing from snapshots saved before rejections were kept.
+    #[serde(default)]
+    recent_rejections: HashMap<String, AccountBalanceRejection>,
+}
+
+#[derive(Error, Debug)]
+pub enum AccountBalanceError {
+    #[error("query to {actor} timed out after {timeout:?}")]
+    QueryTimeout { actor: String, timeout: Duration },
+    #[error("command to {actor} timed out after {timeout:?}")]
+    CommandTimeout { actor: String, timeout: Duration },
+    #[error(transparent)]
+    Ractor(#[from] RactorErr<AccountBalanceMessage>),
+}
+
+#[async_trait]
+impl Actor for AccountBalance {
+    type Msg = AccountBalanceMessage;
+    type State = AccountBalanceState;
+    type Arguments = AccountBalanceArgs;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let mut state = Self::State {
+            account_number: args.account_number,
+            account: Account {
+                balance: args.initial_balance,
+                limits: self.limits,
+            },
+            version: 0,
+            recent_event_ids: VecDeque::new(),
+            recent_rejections: HashMap::new(),
+            last_active: Instant::now(),
+            passivating: false,
+        };
+        if let Some(snapshots) = &self.snapshots {
+            if let Some(snapshot) = snapshots.load(&stream_id(&state.account_number)).await? {
+                let saved: AccountSnapshot = serde_json::from_value(snapshot.state)?;
+                state.account.balance = saved.balance;
+                state.recent_event_ids = saved.recent_event_ids;
+                state.recent_rejections = saved.recent_rejections;
+                state.version = snapshot.version;
+            }
+        }
+        let snapshot_version = state.version;
+        let mut budget = YieldBudget::default();
+        for recorded in self
+            .store
+            .read_stream(&stream_id(&state.account_number), state.version)
+            .await?
+        {
+            let envelope = EventEnvelope::from_recorded::<AccountBalanceEventPayload>(&recorded)?;
+            let payload = envelope.decode()?;
+            state.apply(envelope.event_id, payload);
+            state.version = recorded.version;
+            budget.tick().await;
+        }
+        tracing::info!(
+            "initial balance: {}, replayed {} events after version {}",
+            state.account.balance,
+            state.version - snapshot_version,
+            snapshot_version
+        );
+
+        Ok(state)
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(idle_timeout) = self.idle_timeout {
+            myself.send_interval(idle_timeout, || AccountBalanceMessage::CheckIdle);
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        use tracing::{field, Instrument};
+
+        if let AccountBalanceMessage::CheckIdle = message {
+            return self.passivate_if_idle(myself, state).await;
+        }
+        state.last_active = Instant::now();
+        let tracing_span = tracing::info_span!("handle", ?message, event = field::Empty);
+
+        self.handle_message(message, state)
+            .instrument(tracing_span)
+            .await
+    }
+}
+
+impl AccountBalance {
+    async fn handle_message(
+        &self,
+        message: AccountBalanceMessage,
+        state: &mut AccountBalanceState,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            AccountBalanceMessage::ApplyEvent(event, _permit) => {
+                tracing::Span::current().record("event", tracing::field::debug(&event));
+                if let Some(event_id) = &event.event_id {
+                    if state.recent_event_ids.contains(event_id) {
+                        tracing::info!("skipping duplicate event {}", event_id);
+                        return Ok(());
+                    }
+                }
+                let command = match event.payload {
+                    AccountBalanceEventPayload::AmountDeposited { value } => {
+                        AccountBalanceCommand::Deposit { value }
+                    }
+                    AccountBalanceEventPayload::AmountWithdrawn { value } => {
+                        AccountBalanceCommand::Withdraw { value }
+                    }
+                    AccountBalanceEventPayload::FeeApplied { value } => {
+                        AccountBalanceCommand::ApplyFee { value }
+                    }
+                    payload @ AccountBalanceEventPayload::WithdrawalRejected { .. } => {
+                        return self.persist(state, event.event_id, vec![payload]).await;
+                    }
+                };
+                let result = self.execute_command(state, command, event.event_id).await?;
+                if let Err(rejection) = result {
+                    tracing::warn!("event rejected: {}", rejection);
+                }
+            }
+            AccountBalanceMessage::Execute(command, event_id, reply_port) => {
+                if let Some(event_id) = &event_id {
+                    if state.recent_event_ids.contains(event_id) {
+                        tracing::info!("skipping duplicate command {}", event_id);
+                        let outcome = match state.recent_rejections.get(event_id) {
+                            Some(rejection) => Err(rejection.clone()),
+                            None => Ok(state.account.balance),
+                        };
+                        let _ = reply_port.send(outcome);
+                        return Ok(());
+                    }
+                }
+                let result = self.execute_command(state, command, event_id).await?;
+                let _ = reply_port.send(result);
+            }
+            AccountBalanceMessage::GetBalance(reply_port) => {
+                tracing::info!("sending balance: {}", state.account.balance);
+                let _ = reply_port.send(state.account.balance);
+            }
+            // Handled before the message reaches here.
+            AccountBalanceMessage::CheckIdle => {}
+        }
+
+        Ok(())
+    }
+
+    /// Persists the events of `command`, or the rejection of a withdrawal,
+    /// under `event_id`.
+    async fn execute_command(
+        &self,
+        state: &mut AccountBalanceState,
+        command: AccountBalanceCommand,
+        event_id: Option<String>,
+    ) -> Result<Result<i64, AccountBalanceRejection>, ActorProcessingErr> {
+        match state.account.handle_command(command.clone()) {
+            Ok(payloads) => {
+                self.persist(state, event_id, payloads).await?;
+                Ok(Ok(state.account.balance))
+            }
+            Err(rejection) => {
+                if let AccountBalanceCommand::Withdraw { value } = command {
+                    let payload = AccountBalanceEventPayload::WithdrawalRejected {
+                        requested: value,
+                        reason: rejection.to_string(),
+                    };
+                    self.persist(state, event_id, vec![payload]).await?;
+                }
+                Ok(Err(rejection))
+            }
+        }
+    }
+
+    /// Saves a snapshot and drains the actor once it has been idle for the
+    /// configured timeout. Messages sent meanwhile are still handled, later
+    /// ones are rejected by the closed mailbox and resent to a new actor.
+    async fn passivate_if_idle(
+        &self,
+        myself: AccountBalanceActorRef,
+        state: &mut AccountBalanceState,
+    ) -> Result<(), ActorProcessingErr> {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return Ok(());
+        };
+        if state.passivating || state.last_active.elapsed() < idle_timeout {
+            return Ok(());
+        }
+
+        if let Some(snapshots) = &self.snapshots {
+            snapshots
+                .save(Snapshot {
+                    stream_id: stream_id(&state.account_number),
+                    version: state.version,
+                    state: serde_json::to_value(AccountSnapshot {
+                        balance: state.account.balance,
+                        recent_event_ids: state.recent_event_ids.clone(),
+                        recent_rejections: state.recent_rejections.clone(),
+                    })?,
+                })
+                .await?;
+        }
+        tracing::info!(
+            account_number = %state.account_number,
+            version = state.version,
+            "passivating idle account"
+        );
+        myself.drain()?;
+        state.passivating = true;
+        Ok(())
+    }
+
+    /// Appends `payloads` in one batch, so a retried append is recognized
+    /// as a whole although they share `event_id`.
+    async fn persist(
+        &self,
+        state: &mut AccountBalanceState,
+        event_id: Option<String>,
+        payloads: Vec<AccountBalanceEventPayload>,
+    ) -> Result<(), ActorProcessingErr> {
+        let stream_id = stream_id(&state.account_number);
+        let mut envelopes = Vec::with_capacity(payloads.len());
+        for payload in &payloads {
+            let mut envelope = EventEnvelope::new(&stream_id, payload, EVENT_VERSION)?;
+            envelope.event_id = event_id.clone();
+            envelopes.push(serde_json::to_value(&envelope)?);
+        }
+        let previous_version = state.version;
+        state.version = self
+            .store
+            .append(
+                &stream_id,
+                ExpectedVersion::Exact(previous_version),
+                envelopes,
+            )
+            .await?;
+        self.bus
+            .publish(&self.store.read_stream(&stream_id, previous_version).await?);
+        for payload in payloads {
+            state.apply(event_id.clone(), payload);
+        }
+        tracing::debug!("balance after: {}", state.account.balance);
+        Ok(())
+    }
+}
+
+impl AccountBalanceState {
+    fn apply(&mut self, event_id: Option<String>, payload: AccountBalanceEventPayload) {
+        if let Some(event_id) = event_id {
+            if let AccountBalanceEventPayload::WithdrawalRejected { requested, .. } = &payload {
+                // The event only keeps the reason, decide again against the
+                // state it was recorded on to get the rejection.
+                let command = AccountBalanceCommand::Withdraw { value: *requested };
+                if let Err(rejection) = self.account.handle_command(command) {
+                    self.recent_rejections.insert(event_id.clone(), rejection);
+                }
+            }
+            if self.recent_event_ids.back() != Some(&event_id) {
+                if self.recent_event_ids.len() == DEDUPE_WINDOW {
+                    if let Some(expired) = self.recent_event_ids.pop_front() {
+                        self.recent_rejections.remove(&expired);
+                    }
+                }
+                self.recent_event_ids.push_back(event_id);
+            }
+        }
+        self.account.apply_event(payload);
+    }
+}
+
+/// Decision logic of an account, free of actors and storage.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Account {
+    pub balance: i64,
+    /// Set by the hosting [`AccountBalance`], not rebuilt from events.
+    #[serde(skip)]
+    pub limits: AccountLimits,
+}
+
+impl Account {
+    pub fn with_limits(limits: AccountLimits) -> Self {
+        Self { balance: 0, limits }
+    }
+}
+
+impl Aggregate for Account {
+    type Command = AccountBalanceCommand;
+    type Event = AccountBalanceEventPayload;
+    type Error = AccountBalanceRejection;
+
+    fn handle_command(
+        &self,
+        command: AccountBalanceCommand,
+    ) -> Result<Vec<AccountBalanceEventPayload>, AccountBalanceRejection> {
+        let value = match command {
+            AccountBalanceCommand::Deposit { value }
+            | AccountBalanceCommand::Withdraw { value }
+            | AccountBalanceCommand::ApplyFee { value } => value,
+        };
+        if value <= 0 {
+            return Err(AccountBalanceRejection::NonPositiveAmount);
+        }
+        if let Some(max) = self.limits.max_transaction {
+            if value > max && !matches!(command, AccountBalanceCommand::ApplyFee { .. }) {
+                return Err(AccountBalanceRejection::TransactionLimitExceeded { value, max });
+            }
+        }
+        let balance = match command {
+            AccountBalanceCommand::Deposit { .. } => self.balance.checked_add(value),
+            AccountBalanceCommand::Withdraw { .. } | AccountBalanceCommand::ApplyFee { .. } => {
+                self.balance.checked_sub(value)
+            }
+        };
+        let Some(balance) = balance else {
+            return Err(AccountBalanceRejection::BalanceOutOfRange {
+                value,
+                balance: self.balance,
+            });
+        };
+        let event = match command {
+            AccountBalanceCommand::Deposit { .. } => {
+                AccountBalanceEventPayload::AmountDeposited { value }
+            }
+            AccountBalanceCommand::Withdraw { .. } if balance < -self.limits.overdraft_limit => {
+                return Err(AccountBalanceRejection::OverdraftLimitExceeded {
+                    value,
+                    balance: self.balance,
+                    overdraft_limit: self.limits.overdraft_limit,
+                });
+            }
+            AccountBalanceCommand::Withdraw { .. } => {
+                AccountBalanceEventPayload::AmountWithdrawn { value }
+            }
+            AccountBalanceCommand::ApplyFee { .. } => AccountBalanceEventPayload::FeeApplied { value },
+        };
+        Ok(vec![event])
+    }
+
+    /// Commands keep the balance in range, so saturating only matters for
+    /// events recorded before they did.
+    fn apply_event(&mut self, event: AccountBalanceEventPayload) {
+        match event {
+            AccountBalanceEventPayload::AmountDeposited { value } => {
+                self.balance = self.balance.saturating_add(value);
+            }
+            AccountBalanceEventPayload::AmountWithdrawn { value } => {
+                self.balance = self.balance.saturating_sub(value);
+            }
+            AccountBalanceEventPayload::FeeApplied { value } => {
+                self.balance = self.balance.saturating_sub(value);
+            }
+            AccountBalanceEventPayload::WithdrawalRejected { .. } => {}
+        }
+    }
+}
+
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Events sent with [`AccountBalance::apply_event`] that can wait in the
+/// account mailboxes, see [`AccountBalance::with_max_pending_events`].
+pub const DEFAULT_MAX_PENDING_EVENTS: usize = 1024;
+const RESPAWN_BACKOFF: Duration = Duration::from_millis(5);
+
+pub type AccountBalanceActorRef = ActorRef<AccountBalanceMessage>;
+
+pub const STREAM_TYPE: &str = "account";
+
+/// Schema version of the [`AccountBalanceEventPayload`]s this release
+/// persists.
+pub const EVENT_VERSION: u32 = 2;
+
+/// Upcasters reading every earlier version of [`AccountBalanceEventPayload`],
+/// installed at startup by the binaries reading account streams.
+pub fn upcasters() -> Upcasters {
+    Upcasters::new().register("AccountBalanceEventPayload", 1, rename_rejected_value)
+}
+
+/// v1 -> v2: `WithdrawalRejected { value }` became `{ requested }`, since
+/// unlike the other `value`s it is not an amount that moved.
+fn rename_rejected_value(mut payload: serde_json::Value) -> serde_json::Value {
+    if let Some(rejected) = payload
+        .get_mut("WithdrawalRejected")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        if let Some(value) = rejected.remove("value") {
+            rejected.insert("requested".to_string(), value);
+        }
+    }
+    payload
+}
+
+fn stream_id(account_number: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, account_number)
+}
+
+impl AccountBalance {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self {
+            bus: EventBus::for_store(&store),
+            store,
+            limits: AccountLimits::default(),
+            snapshots: None,
+            idle_timeout: None,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            ingestion: Arc::new(Semaphore::new(DEFAULT_MAX_PENDING_EVENTS)),
+            max_pending_events: DEFAULT_MAX_PENDING_EVENTS,
+        }
+    }
+
+    /// Bounds the events sent with [`AccountBalance::apply_event`] that are
+    /// not handled yet. Once reached, `apply_event` waits for capacity
+    /// instead of growing the unbounded actor mailboxes.
+    ///
+    /// # Panics
+    ///
+    /// If `max_pending_events` is 0, which would make every `apply_event`
+    /// wait forever.
+    pub fn with_max_pending_events(mut self, max_pending_events: usize) -> Self {
+        assert!(max_pending_events > 0, "max_pending_events must be positive");
+        self.ingestion = Arc::new(Semaphore::new(max_pending_events));
+        self.max_pending_events = max_pending_events;
+        self
+    }
+
+    /// Events sent with [`AccountBalance::apply_event`] and not handled yet,
+    /// for producers to throttle on.
+    pub fn pending_events(&self) -> usize {
+        self.max_pending_events - self.ingestion.available_permits()
+    }
+
+    /// Stops account actors after `idle_timeout` without messages. They are
+    /// respawned on the next command or event.
+    pub fn with_passivation(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Saves a snapshot of passivated accounts so respawning them does not
+    /// replay the whole stream.
+    pub fn with_snapshots(mut self, snapshots: Arc<dyn SnapshotStore>) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: AccountLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// How long commands and queries wait for the account actor, instead of
+    /// [`DEFAULT_RPC_TIMEOUT`].
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        self.rpc_timeout
+    }
+
+    pub async fn spawn(&self, args: AccountBalanceArgs) -> Result<(AccountBalanceActorRef, JoinHandle<()>), SpawnErr> {
+        let name = Some(Self::via(&args.account_number));
+        Actor::spawn(name, self.clone(), args).await
+    }
+
+    /// Sends `event` to its account without waiting for it to be handled.
+    ///
+    /// Waits while [`AccountBalance::with_max_pending_events`] events are
+    /// already pending.
+    pub async fn apply_event(&self, event: AccountBalanceEvent) -> Result<(), RactorErr<AccountBalanceMessage>> {
+        let account_number = event.account_number.clone();
+        let permit = Arc::clone(&self.ingestion)
+            .acquire_owned()
+            .await
+            .expect("ingestion semaphore is never closed");
+        let mut message = AccountBalanceMessage::ApplyEvent(event, permit);
+        loop {
+            let actor = self.get_or_spawn(&account_number).await?;
+            match actor.send_message(message) {
+                // The actor is passivating, resend once it has stopped.
+                Err(MessagingErr::SendErr(returned)) => {
+                    message = returned;
+                    tokio::time::sleep(RESPAWN_BACKOFF).await;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// Runs `command` against the account and returns its new balance, or
+    /// why the command was rejected.
+    pub async fn execute(
+        &self,
+        account_number: &str,
+        command: AccountBalanceCommand,
+    ) -> Result<Result<i64, AccountBalanceRejection>, AccountBalanceError> {
+        self.execute_once(account_number, command, None).await
+    }
+
+    /// Like [`AccountBalance::execute`], recording the events under
+    /// `event_id`, so that resending the command after a transient failure
+    /// does not apply it twice.
+    pub async fn execute_once(
+        &self,
+        account_number: &str,
+        command: AccountBalanceCommand,
+        event_id: Option<String>,
+    ) -> Result<Result<i64, AccountBalanceRejection>, AccountBalanceError> {
+        match self
+            .call(account_number, |reply_port| {
+                AccountBalanceMessage::Execute(command.clone(), event_id.clone(), reply_port)
+            })
+            .await?
+        {
+            CallResult::Success(result) => Ok(result),
+            CallResult::Timeout => Err(AccountBalanceError::CommandTimeout {
+                actor: Self::via(account_number),
+                timeout: self.rpc_timeout,
+            }),
+            CallResult::SenderError => Err(RactorErr::from(MessagingErr::ChannelClosed).into()),
+        }
+    }
+
+    /// Balance of an account, respawning its actor if it was passivated.
+    pub async fn balance(&self, account_number: &str) -> Result<i64, AccountBalanceError> {
+        match self
+            .call(account_number, AccountBalanceMessage::GetBalance)
+            .await?
+        {
+            CallResult::Success(balance) => Ok(balance),
+            CallResult::Timeout => Err(AccountBalanceError::QueryTimeout {
+                actor: Self::via(account_number),
+                timeout: self.rpc_timeout,
+            }),
+            CallResult::SenderError => Err(RactorErr::from(MessagingErr::ChannelClosed).into()),
+        }
+    }
+
+    async fn call<T: Send + 'static>(
+        &self,
+        account_number: &str,
+        build: impl Fn(RpcReplyPort<T>) -> AccountBalanceMessage,
+    ) -> Result<CallResult<T>, RactorErr<AccountBalanceMessage>> {
+        loop {
+            let actor = self.get_or_spawn(account_number).await?;
+            match actor.call(&build, Some(self.rpc_timeout)).await {
+                // The actor is passivating, resend once it has stopped.
+                Err(MessagingErr::SendErr(_)) => tokio::time::sleep(RESPAWN_BACKOFF).await,
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    async fn get_or_spawn(&self, account_number: &str) -> Result<AccountBalanceActorRef, SpawnErr> {
+        loop {
+            if let Some(actor) = Self::where_is(account_number) {
+                return Ok(actor);
+            }
+            match self
+                .spawn(AccountBalanceArgs::new(account_number.to_string()))
+                .await
+            {
+                Ok((actor, _)) => return Ok(actor),
+                // A concurrent spawn, or a passivated actor not stopped yet.
+                Err(SpawnErr::ActorAlreadyRegistered(_)) => {
+                    tokio::time::sleep(RESPAWN_BACKOFF).await
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Balance held by a running account actor, `None` if the account is not
+    /// loaded, e.g. after passivation. See [`AccountBalance::balance`].
+    pub async fn get_balance(account_number: &str) -> Result<Option<i64>, AccountBalanceError> {
+        Self::get_balance_with_timeout(account_number, DEFAULT_RPC_TIMEOUT).await
+    }
+
+    pub async fn get_balance_with_timeout(
+        account_number: &str,
+        timeout: Duration,
+    ) -> Result<Option<i64>, AccountBalanceError> {
+        let Some(actor) = Self::where_is(account_number) else {
+            return Ok(None);
+        };
+        match actor
+            .call(AccountBalanceMessage::GetBalance, Some(timeout))
+            .await
+            .map_err(RactorErr::from)?
+        {
+            CallResult::Success(balance) => Ok(Some(balance)),
+            CallResult::Timeout => Err(AccountBalanceError::QueryTimeout {
+                actor: Self::via(account_number),
+                timeout,
+            }),
+            CallResult::SenderError => Err(RactorErr::from(MessagingErr::ChannelClosed).into()),
+        }
+    }
+
+    fn via(account_number: &str) -> String {
+        format!("{}/{}", std::any::type_name::<Self>(), account_number)
+    }
+
+    pub fn where_is(account_number: &str) -> Option<AccountBalanceActorRef> {
+        AccountBalanceActorRef::where_is(Self::via(account_number))
+    }
+}
+
+/// Executes commands scheduled on account streams under their occurrence
+/// id. Rejected commands are logged and not retried.
+#[cfg(feature = "scheduler")]
+#[async_trait]
+impl CommandDispatcher for AccountBalance {
+    async fn dispatch(
+        &self,
+        stream_id: &str,
+        command: serde_json::Value,
+        occurrence_id: &str,
+    ) -> Result<(), DispatchError> {
+        let Some((STREAM_TYPE, account_number)) = stream_id.split_once('/') else {
+            return Err(DispatchError::Failed(
+                format!("{} is not an account stream", stream_id).into(),
+            ));
+        };
+        let command = serde_json::from_value(command)?;
+        if let Err(rejection) = self
+            .execute_once(account_number, command, Some(occurrence_id.to_string()))
+            .await
+            .map_err(|err| DispatchError::Failed(err.into()))?
+        {
+            tracing::warn!("scheduled command on {} rejected: {}", stream_id, rejection);
+        }
+        Ok(())
+    }
+}
+
+/// Demo projection printing every account movement with the running balance.
+#[derive(Default)]
+pub struct Ledger {
+    balances: HashMap<String, i64>,
+}
+
+impl Ledger {
+    pub fn balance(&self, stream_id: &str) -> i64 {
+        self.balances.get(stream_id).copied().unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl EventHandler for Ledger {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        let Some(payload) = event.decode_known()? else {
+            return Ok(());
+        };
+        let balance = self.balances.entry(event.stream_id.clone()).or_default();
+        let (label, delta) = match payload {
+            AccountBalanceEventPayload::AmountDeposited { value } => ("deposit", value),
+            AccountBalanceEventPayload::AmountWithdrawn { value } => ("withdrawal", -value),
+            AccountBalanceEventPayload::FeeApplied { value } => ("fee", -value),
+            AccountBalanceEventPayload::WithdrawalRejected { .. } => ("rejected", 0),
+        };
+        *balance += delta;
+        println!(
+            "#{:<4} {:<20} {:<10} {:>8} {:>8}",
+            event.position, event.stream_id, label, delta, balance
+        );
+        Ok(())
+    }
+}
+
+/// Read model with the balance and movement totals of every account.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountSummaryProjection {
+    pub accounts: BTreeMap<String, AccountSummary>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSummary {
+    pub balance: i64,
+    pub total_deposits: i64,
+    pub total_withdrawals: i64,
+    pub total_fees: i64,
+}
+
+impl Projector for AccountSummaryProjection {
+    const NAME: &'static str = "account-summary";
+}
+
+#[async_trait]
+impl EventHandler for AccountSummaryProjection {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        let Some((STREAM_TYPE, account_number)) = event.stream_id.split_once('/') else {
+            return Ok(());
+        };
+        let Some(payload) = event.decode_known()? else {
+            return Ok(());
+        };
+        let summary = self.accounts.entry(account_number.to_string()).or_default();
+        match payload {
+            AccountBalanceEventPayload::AmountDeposited { value } => {
+                summary.balance = summary.balance.saturating_add(value);
+                summary.total_deposits = summary.total_deposits.saturating_add(value);
+            }
+            AccountBalanceEventPayload::AmountWithdrawn { value } => {
+                summary.balance = summary.balance.saturating_sub(value);
+                summary.total_withdrawals = summary.total_withdrawals.saturating_add(value);
+            }
+            AccountBalanceEventPayload::FeeApplied { value } => {
+                summary.balance = summary.balance.saturating_sub(value);
+                summary.total_fees = summary.total_fees.saturating_add(value);
+            }
+            AccountBalanceEventPayload::WithdrawalRejected { .. } => {}
+        }
+        Ok(())
+    }
+}
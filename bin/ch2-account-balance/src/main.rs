@@ -1,138 +1,24 @@
-use ractor::{
-    async_trait, concurrency::tokio_primitives::JoinHandle, errors::{RactorErr, SpawnErr}, Actor,
-    ActorProcessingErr, ActorRef, RpcReplyPort, call_t,
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceCommand, AccountBalanceEvent, AccountBalanceEventPayload,
+    AccountLimits, AccountSummaryProjection, Ledger, STREAM_TYPE,
 };
+use eventsourcing::app::{App, RecurringCommand};
+use eventsourcing::branch::Branch;
+use eventsourcing::bus::{stream_type, EventBus, EventSubscriber};
+use eventsourcing::export::{export, Anonymizer};
+use eventsourcing::projection::{ProjectionActor, ProjectionMessage};
+use eventsourcing::selftest;
+use eventsourcing::snapshot::{InMemorySnapshotStore, JsonFileSnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, InMemoryEventStore, JsonLinesEventStore, RecordedEvent};
+use eventstore_sqlite::SqliteEventStore;
+use ractor::{call_t, Actor};
+use std::fs::File;
+use std::io::BufWriter;
 use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct AccountBalance;
-
-#[derive(Default)]
-pub struct AccountBalanceArgs {
-    initial_balance: i64,
-    account_number: String,
-}
-
-impl AccountBalanceArgs {
-    pub fn new(account_number: String) -> Self {
-        Self {
-            initial_balance: 0,
-            account_number,
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum AccountBalanceEventPayload {
-    AmountWithdrawn { value: i64 },
-    AmountDeposited { value: i64 },
-    FeeApplied { value: i64 },
-}
-
-#[derive(Debug)]
-pub struct AccountBalanceEvent {
-    account_number: String,
-    payload: AccountBalanceEventPayload,
-}
-
-#[derive(Debug)]
-pub enum AccountBalanceMessage {
-    ApplyEvent(AccountBalanceEvent),
-    GetBalance(RpcReplyPort<i64>),
-}
-
-pub struct AccountBalanceState {
-    balance: i64,
-}
-
-#[async_trait]
-impl Actor for AccountBalance {
-    type Msg = AccountBalanceMessage;
-    type State = AccountBalanceState;
-    type Arguments = AccountBalanceArgs;
-
-    async fn pre_start(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        args: Self::Arguments,
-    ) -> Result<Self::State, ActorProcessingErr> {
-        tracing::info!("initial balance: {}", args.initial_balance);
-
-        Ok(Self::State { balance: args.initial_balance })
-    }
-
-    async fn handle(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        message: Self::Msg,
-        state: &mut Self::State,
-    ) -> Result<(), ActorProcessingErr> {
-        use tracing::field;
-        let tracing_span = tracing::info_span!("handle", ?message, event = field::Empty);
-        let _tracing_guard = tracing_span.enter();
-
-        match message {
-            AccountBalanceMessage::ApplyEvent(event) => {
-                tracing_span.record("event", field::debug(&event));
-                match event.payload {
-                    AccountBalanceEventPayload::AmountDeposited { value } => {
-                        state.balance += value;
-                    }
-                    AccountBalanceEventPayload::AmountWithdrawn { value } => {
-                        state.balance -= value;
-                    }
-                    AccountBalanceEventPayload::FeeApplied { value } => {
-                        state.balance -= value;
-                    }
-                }
-                tracing::debug!("balance after: {}", state.balance);
-            }
-            AccountBalanceMessage::GetBalance(reply_port) => {
-                tracing::info!("sending balance: {}", state.balance);
-                let _ = reply_port.send(state.balance);
-            }
-        }
-
-        Ok(())
-    }
-}
-
-const RPC_TIMEOUT_MS: u64 = 1000;
-
-type AccountBalanceActorRef = ActorRef<AccountBalanceMessage>;
-
-impl AccountBalance {
-    pub async fn spawn(args: AccountBalanceArgs) -> Result<(AccountBalanceActorRef, JoinHandle<()>), SpawnErr> {
-        let name = Some(Self::via(&args.account_number));
-        Actor::spawn(name, Self, args).await
-    }
-
-    pub async fn apply_event(event: AccountBalanceEvent) -> Result<(), RactorErr<AccountBalanceMessage>> {
-        let actor = match Self::where_is(&event.account_number) {
-            Some(actor) => actor,
-            None => {
-                Self::spawn(AccountBalanceArgs::new(event.account_number.clone())).await?.0
-            }
-        };
-        actor.send_message(AccountBalanceMessage::ApplyEvent(event))?;
-        Ok(())
-    }
-
-    pub async fn get_balance(account_number: &str) -> Result<Option<i64>, RactorErr<AccountBalanceMessage>> {
-        if let Some(actor) = Self::where_is(account_number) {
-            call_t!(actor, AccountBalanceMessage::GetBalance, RPC_TIMEOUT_MS).map(Some)
-        } else {
-            Ok(None)
-        }
-    }
-
-    fn via(account_number: &str) -> String {
-        format!("{}/{}", std::any::type_name::<Self>(), account_number)
-    }
-
-    fn where_is(account_number: &str) -> Option<AccountBalanceActorRef> {
-        AccountBalanceActorRef::where_is(Self::via(account_number))
-    }
-}
+const FEE_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
 
 #[tokio::main]
 async fn main() -> ExitCode {
@@ -146,21 +32,152 @@ async fn main() -> ExitCode {
 }
 
 async fn inner() -> anyhow::Result<()> {
-    local_logging::init()?;
+    let _logging = local_logging::init()?;
+    ch2_account_balance::upcasters().install();
+
+    // Pass a file path to keep the event log across runs: a SQLite database
+    // for `.db`/`.sqlite` files, JSON lines otherwise. Projection checkpoints
+    // are saved next to it.
+    // `--self-test` checks the store and the actor runtime, then exits.
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != selftest::FLAG)
+        .collect();
+    let path = args.first().cloned();
+    let store: Arc<dyn EventStore> = match &path {
+        Some(path) if path.ends_with(".db") || path.ends_with(".sqlite") => {
+            Arc::new(SqliteEventStore::open(path)?)
+        }
+        Some(path) => Arc::new(JsonLinesEventStore::open(path)?),
+        None => Arc::new(InMemoryEventStore::new()),
+    };
+    if selftest::requested() {
+        return Ok(selftest::run_and_print(&store).await?);
+    }
 
-    AccountBalance::apply_event(AccountBalanceEvent {
-        account_number: "ACCOUNT1".to_string(),
-        payload: AccountBalanceEventPayload::AmountDeposited { value: 100 },
-    }).await?;
-    AccountBalance::apply_event(AccountBalanceEvent {
+    // `EVENTS --export OUT` writes an anonymized copy of the account streams
+    // to OUT, e.g. to attach to a bug report, instead of running the demo.
+    if let [_, flag, out] = args.as_slice() {
+        if flag == "--export" {
+            let exported = export(
+                store.as_ref(),
+                |stream_id| stream_type(stream_id) == STREAM_TYPE,
+                &mut Anonymizer::new(),
+                &mut BufWriter::new(File::create(out)?),
+            )
+            .await?;
+            println!("exported {} events to {}", exported, out);
+            return Ok(());
+        }
+    }
+
+    let checkpoints: Arc<dyn SnapshotStore> = match &path {
+        Some(path) => Arc::new(JsonFileSnapshotStore::open(format!("{}.checkpoints.json", path))?),
+        None => Arc::new(InMemorySnapshotStore::new()),
+    };
+
+    let (summary, _) = Actor::spawn(
+        None,
+        ProjectionActor::<AccountSummaryProjection>::new(Arc::clone(&store), checkpoints),
+        (),
+    )
+    .await?;
+    let (ledger, ledger_handle) = Actor::spawn(
+        None,
+        EventSubscriber::<Ledger>::new(
+            Arc::clone(&store),
+            EventBus::for_store(&store),
+            &[STREAM_TYPE],
+        ),
+        Ledger::default(),
+    )
+    .await?;
+    let accounts = AccountBalance::new(Arc::clone(&store)).with_limits(AccountLimits {
+        overdraft_limit: 50,
+        max_transaction: Some(500),
+    });
+    let rpc_timeout_ms = accounts.rpc_timeout().as_millis() as u64;
+
+    for command in [
+        AccountBalanceCommand::Deposit { value: 100 },
+        AccountBalanceCommand::Withdraw { value: 120 },
+        AccountBalanceCommand::Withdraw { value: 40 },
+        AccountBalanceCommand::Deposit { value: 1000 },
+    ] {
+        match accounts.execute("ACCOUNT1", command.clone()).await? {
+            Ok(balance) => println!("{:?} => balance {}", command, balance),
+            Err(rejection) => println!("{:?} rejected: {}", command, rejection),
+        }
+    }
+    accounts.apply_event(AccountBalanceEvent {
         account_number: "ACCOUNT1".to_string(),
+        event_id: None,
         payload: AccountBalanceEventPayload::FeeApplied { value: 5 },
     }).await?;
 
+    // Charges a monthly fee from now on. The schedule is kept in the event
+    // log, so with a persistent log it is only set up by the first run.
+    let app = App::builder(Arc::clone(&store))
+        .with_schedule(
+            "fees",
+            Arc::new(accounts.clone()),
+            vec![RecurringCommand {
+                schedule_id: "monthly-fee/ACCOUNT1".to_string(),
+                stream_id: format!("{}/ACCOUNT1", STREAM_TYPE),
+                command: serde_json::to_value(AccountBalanceCommand::ApplyFee { value: 2 })?,
+                period: FEE_PERIOD,
+            }],
+        )
+        .build()
+        .await?;
+    for (schedule_id, due_at_ms) in app.scheduled().await? {
+        println!("scheduled {} at {} ms", schedule_id, due_at_ms);
+    }
+    app.shutdown().await?;
+
     for account in &["ACCOUNT1", "ACCOUNT2"] {
-        let balance = AccountBalance::get_balance(account).await?;
+        let balance =
+            AccountBalance::get_balance_with_timeout(account, accounts.rpc_timeout()).await?;
         println!("balance of {}: {:?}", account, balance);
     }
 
+    let summary = call_t!(summary, ProjectionMessage::Query, rpc_timeout_ms)?;
+    for (account, account_summary) in &summary.accounts {
+        println!("summary of {}: {:?}", account, account_summary);
+    }
+
+    // What if the fee had never been charged? Fork the account without it
+    // and build the summary of the branch; the real history is untouched.
+    let branch = Branch::new();
+    branch
+        .fork_where(
+            store.as_ref(),
+            &format!("{}/ACCOUNT1", STREAM_TYPE),
+            |event: &RecordedEvent| {
+                !matches!(
+                    event.decode::<AccountBalanceEventPayload>(),
+                    Ok(AccountBalanceEventPayload::FeeApplied { .. })
+                )
+            },
+        )
+        .await?;
+    let (what_if, _) = Actor::spawn(
+        None,
+        ProjectionActor::<AccountSummaryProjection>::new(
+            branch.store(),
+            Arc::new(InMemorySnapshotStore::new()),
+        ),
+        (),
+    )
+    .await?;
+    let what_if_summary = call_t!(what_if, ProjectionMessage::Query, rpc_timeout_ms)?;
+    for (account, account_summary) in &what_if_summary.accounts {
+        println!("summary of {} without fees: {:?}", account, account_summary);
+    }
+    what_if.stop(None);
+
+    ledger.drain()?;
+    ledger_handle.await?;
+
     Ok(())
 }
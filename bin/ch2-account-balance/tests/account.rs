@@ -0,0 +1,112 @@
+use ch2_account_balance::{
+    Account, AccountBalanceCommand, AccountBalanceEventPayload, AccountBalanceRejection,
+    AccountLimits,
+};
+use eventsourcing::testing::AggregateTestFixture;
+
+fn fixture() -> AggregateTestFixture<Account> {
+    AggregateTestFixture::with_aggregate(Account::with_limits(AccountLimits {
+        overdraft_limit: 50,
+        max_transaction: Some(200),
+    }))
+}
+
+#[test]
+fn withdrawals_may_overdraw_up_to_the_limit() {
+    let outcome = fixture()
+        .given([AccountBalanceEventPayload::AmountDeposited { value: 100 }])
+        .when(AccountBalanceCommand::Withdraw { value: 150 })
+        .then_events([AccountBalanceEventPayload::AmountWithdrawn { value: 150 }]);
+    assert_eq!(outcome.aggregate().balance, -50);
+
+    fixture()
+        .given([
+            AccountBalanceEventPayload::AmountDeposited { value: 100 },
+            AccountBalanceEventPayload::AmountWithdrawn { value: 150 },
+        ])
+        .when(AccountBalanceCommand::Withdraw { value: 1 })
+        .then_error(AccountBalanceRejection::OverdraftLimitExceeded {
+            value: 1,
+            balance: -50,
+            overdraft_limit: 50,
+        });
+}
+
+#[test]
+fn fees_count_against_the_overdraft_limit() {
+    fixture()
+        .given([
+            AccountBalanceEventPayload::AmountDeposited { value: 10 },
+            AccountBalanceEventPayload::FeeApplied { value: 30 },
+        ])
+        .when(AccountBalanceCommand::Withdraw { value: 31 })
+        .then_error(AccountBalanceRejection::OverdraftLimitExceeded {
+            value: 31,
+            balance: -20,
+            overdraft_limit: 50,
+        });
+}
+
+#[test]
+fn amounts_must_be_positive_and_within_the_transaction_limit() {
+    fixture()
+        .when(AccountBalanceCommand::Deposit { value: 300 })
+        .then_error(AccountBalanceRejection::TransactionLimitExceeded {
+            value: 300,
+            max: 200,
+        });
+    fixture()
+        .when(AccountBalanceCommand::Withdraw { value: 0 })
+        .then_error(AccountBalanceRejection::NonPositiveAmount);
+}
+
+#[test]
+fn rejected_withdrawals_do_not_change_the_balance() {
+    let outcome = fixture()
+        .given([
+            AccountBalanceEventPayload::AmountDeposited { value: 20 },
+            AccountBalanceEventPayload::WithdrawalRejected {
+                requested: 500,
+                reason: "too much".to_string(),
+            },
+        ])
+        .when(AccountBalanceCommand::Deposit { value: 5 })
+        .then_events([AccountBalanceEventPayload::AmountDeposited { value: 5 }]);
+    assert_eq!(outcome.aggregate().balance, 25);
+}
+
+#[test]
+fn fees_ignore_the_transaction_and_overdraft_limits() {
+    let outcome = fixture()
+        .given([AccountBalanceEventPayload::AmountDeposited { value: 10 }])
+        .when(AccountBalanceCommand::ApplyFee { value: 300 })
+        .then_events([AccountBalanceEventPayload::FeeApplied { value: 300 }]);
+    assert_eq!(outcome.aggregate().balance, -290);
+    fixture()
+        .when(AccountBalanceCommand::ApplyFee { value: 0 })
+        .then_error(AccountBalanceRejection::NonPositiveAmount);
+}
+
+#[test]
+fn amounts_taking_the_balance_out_of_range_are_rejected() {
+    let unlimited = || {
+        AggregateTestFixture::with_aggregate(Account::with_limits(AccountLimits {
+            overdraft_limit: i64::MAX,
+            max_transaction: None,
+        }))
+    };
+    unlimited()
+        .given([AccountBalanceEventPayload::AmountWithdrawn { value: 2 }])
+        .when(AccountBalanceCommand::Withdraw { value: i64::MAX })
+        .then_error(AccountBalanceRejection::BalanceOutOfRange {
+            value: i64::MAX,
+            balance: -2,
+        });
+    unlimited()
+        .given([AccountBalanceEventPayload::AmountDeposited { value: i64::MAX }])
+        .when(AccountBalanceCommand::Deposit { value: 1 })
+        .then_error(AccountBalanceRejection::BalanceOutOfRange {
+            value: 1,
+            balance: i64::MAX,
+        });
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use ch2_account_balance::{AccountBalanceEventPayload, AccountSummary, AccountSummaryProjection};
+use eventsourcing::projection::{ProjectionActor, ProjectionMessage};
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::testing::ProjectionTester;
+use ractor::{call_t, Actor};
+
+fn payload(payload: AccountBalanceEventPayload) -> serde_json::Value {
+    serde_json::to_value(payload).unwrap()
+}
+
+#[tokio::test]
+async fn summary_tracks_totals_per_account() {
+    let mut tester = ProjectionTester::new(AccountSummaryProjection::default());
+
+    tester
+        .feed("account/A", AccountBalanceEventPayload::AmountDeposited { value: 100 })
+        .await
+        .unwrap();
+    tester
+        .feed("account/A", AccountBalanceEventPayload::AmountWithdrawn { value: 30 })
+        .await
+        .unwrap();
+    tester
+        .feed("account/A", AccountBalanceEventPayload::FeeApplied { value: 5 })
+        .await
+        .unwrap();
+    tester
+        .feed("calculator/1", serde_json::json!({ "DidAdd": { "value": 1 } }))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        tester.read_model().accounts.get("A"),
+        Some(&AccountSummary {
+            balance: 65,
+            total_deposits: 100,
+            total_withdrawals: 30,
+            total_fees: 5,
+        })
+    );
+    assert_eq!(tester.read_model().accounts.len(), 1);
+}
+
+#[tokio::test]
+async fn projection_resumes_from_checkpoint() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let checkpoints: Arc<dyn SnapshotStore> = Arc::new(InMemorySnapshotStore::new());
+    let spawn = || {
+        Actor::spawn(
+            None,
+            ProjectionActor::<AccountSummaryProjection>::new(
+                Arc::clone(&store),
+                Arc::clone(&checkpoints),
+            ),
+            (),
+        )
+    };
+
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::Any,
+            vec![payload(AccountBalanceEventPayload::AmountDeposited { value: 100 })],
+        )
+        .await
+        .unwrap();
+    let (projection, handle) = spawn().await.unwrap();
+    let summary = call_t!(projection, ProjectionMessage::Query, 1000).unwrap();
+    assert_eq!(summary.accounts["A"].balance, 100);
+    projection.stop(None);
+    handle.await.unwrap();
+
+    // Differs from the replayed model, to tell which one the actor resumed
+    // from.
+    let checkpoint_id = ProjectionActor::<AccountSummaryProjection>::checkpoint_id();
+    let mut checkpoint = checkpoints.load(&checkpoint_id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.version, 1);
+    checkpoint.state["accounts"]["A"]["balance"] = 1000.into();
+    checkpoints.save(checkpoint).await.unwrap();
+
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::Any,
+            vec![payload(AccountBalanceEventPayload::FeeApplied { value: 5 })],
+        )
+        .await
+        .unwrap();
+    let (projection, _) = spawn().await.unwrap();
+    let summary = call_t!(projection, ProjectionMessage::Query, 1000).unwrap();
+    assert_eq!(summary.accounts["A"].balance, 995);
+    assert_eq!(summary.accounts["A"].total_fees, 5);
+    let checkpoint = checkpoints.load(&checkpoint_id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.version, 2);
+}
+
+#[tokio::test]
+async fn unknown_events_are_skipped() {
+    let mut tester = ProjectionTester::new(AccountSummaryProjection::default());
+
+    // A variant added by a newer release.
+    tester
+        .feed("account/A", serde_json::json!({ "InterestPaid": { "value": 7 } }))
+        .await
+        .unwrap();
+    tester
+        .feed("account/A", AccountBalanceEventPayload::AmountDeposited { value: 100 })
+        .await
+        .unwrap();
+
+    assert_eq!(tester.read_model().accounts["A"].balance, 100);
+}
+
+#[tokio::test]
+async fn totals_saturate_instead_of_overflowing() {
+    let mut tester = ProjectionTester::new(AccountSummaryProjection::default());
+
+    for _ in 0..2 {
+        tester
+            .feed(
+                "account/A",
+                AccountBalanceEventPayload::AmountDeposited { value: i64::MAX },
+            )
+            .await
+            .unwrap();
+    }
+    tester
+        .feed("account/A", AccountBalanceEventPayload::FeeApplied { value: 5 })
+        .await
+        .unwrap();
+
+    let summary = &tester.read_model().accounts["A"];
+    assert_eq!(summary.total_deposits, i64::MAX);
+    assert_eq!(summary.balance, i64::MAX - 5);
+}
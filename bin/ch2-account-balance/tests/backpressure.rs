@@ -0,0 +1,114 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceError, AccountBalanceEvent, AccountBalanceEventPayload,
+};
+use eventsourcing::store::{
+    EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent, StoreError,
+};
+use ractor::async_trait;
+use serde_json::Value;
+use tokio::sync::Semaphore;
+
+/// Store whose appends wait until the test opens the gate.
+struct GatedStore {
+    inner: InMemoryEventStore,
+    gate: Semaphore,
+}
+
+#[async_trait]
+impl EventStore for GatedStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<Value>,
+    ) -> Result<u64, StoreError> {
+        self.gate.acquire().await.unwrap().forget();
+        self.inner
+            .append(stream_id, expected_version, payloads)
+            .await
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.inner.read_stream(stream_id, after_version).await
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.inner.read_all(after_position, limit).await
+    }
+}
+
+fn deposit(value: i64) -> AccountBalanceEvent {
+    AccountBalanceEvent {
+        account_number: "BUSY1".to_string(),
+        event_id: None,
+        payload: AccountBalanceEventPayload::AmountDeposited { value },
+    }
+}
+
+#[tokio::test]
+async fn apply_event_waits_for_capacity() {
+    let store = Arc::new(GatedStore {
+        inner: InMemoryEventStore::new(),
+        gate: Semaphore::new(0),
+    });
+    let accounts = AccountBalance::new(store.clone()).with_max_pending_events(2);
+
+    accounts.apply_event(deposit(1)).await.unwrap();
+    accounts.apply_event(deposit(2)).await.unwrap();
+    assert_eq!(accounts.pending_events(), 2);
+    let third = tokio::time::timeout(Duration::from_millis(50), accounts.apply_event(deposit(3)));
+    assert!(third.await.is_err(), "third event must wait for capacity");
+
+    store.gate.add_permits(3);
+    accounts.apply_event(deposit(3)).await.unwrap();
+    assert_eq!(accounts.balance("BUSY1").await.unwrap(), 6);
+    assert_eq!(accounts.pending_events(), 0);
+}
+
+#[test]
+#[should_panic(expected = "max_pending_events must be positive")]
+fn at_least_one_event_must_be_allowed_to_wait() {
+    AccountBalance::new(Arc::new(InMemoryEventStore::new())).with_max_pending_events(0);
+}
+
+#[tokio::test]
+async fn queries_to_a_busy_account_time_out() {
+    let store = Arc::new(GatedStore {
+        inner: InMemoryEventStore::new(),
+        gate: Semaphore::new(0),
+    });
+    let accounts = AccountBalance::new(store.clone());
+
+    // The account is stuck appending this event until the gate opens.
+    accounts
+        .apply_event(AccountBalanceEvent {
+            account_number: "SLOW1".to_string(),
+            event_id: None,
+            payload: AccountBalanceEventPayload::AmountDeposited { value: 1 },
+        })
+        .await
+        .unwrap();
+    let timeout = Duration::from_millis(50);
+    let err = AccountBalance::get_balance_with_timeout("SLOW1", timeout)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, AccountBalanceError::QueryTimeout { timeout: reported, .. } if reported == timeout),
+        "unexpected error: {:?}",
+        err
+    );
+
+    store.gate.add_permits(1);
+    assert_eq!(accounts.balance("SLOW1").await.unwrap(), 1);
+}
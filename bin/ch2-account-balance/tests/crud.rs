@@ -0,0 +1,37 @@
+#![cfg(feature = "crud-baseline")]
+
+use std::sync::Arc;
+
+use ch2_account_balance::crud::CrudAccounts;
+use ch2_account_balance::{AccountBalance, AccountBalanceCommand, AccountLimits};
+use eventsourcing::store::InMemoryEventStore;
+
+#[tokio::test]
+async fn crud_baseline_decides_like_the_event_sourced_service() {
+    let limits = AccountLimits {
+        overdraft_limit: 50,
+        max_transaction: Some(200),
+    };
+    let accounts = AccountBalance::new(Arc::new(InMemoryEventStore::new())).with_limits(limits);
+    let crud = CrudAccounts::open_in_memory().unwrap().with_limits(limits);
+
+    for command in [
+        AccountBalanceCommand::Deposit { value: 100 },
+        AccountBalanceCommand::Withdraw { value: 150 },
+        AccountBalanceCommand::Withdraw { value: 1 },
+        AccountBalanceCommand::Deposit { value: 201 },
+        AccountBalanceCommand::Deposit { value: 0 },
+        AccountBalanceCommand::ApplyFee { value: 300 },
+    ] {
+        assert_eq!(
+            crud.execute("CRUD1", command.clone()).await.unwrap(),
+            accounts.execute("CRUD1", command).await.unwrap()
+        );
+    }
+    assert_eq!(crud.balance("CRUD1").await.unwrap(), -350);
+    assert_eq!(
+        crud.balance("CRUD1").await.unwrap(),
+        accounts.balance("CRUD1").await.unwrap()
+    );
+    assert_eq!(crud.balance("CRUD2").await.unwrap(), 0);
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ch2_account_balance::{
+    upcasters, AccountBalance, AccountBalanceEvent, AccountBalanceEventPayload,
+};
+use eventsourcing::envelope::{EventEnvelope, Upcasters};
+use eventsourcing::snapshot::JsonFileSnapshotStore;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::testing::{decode_fixture, read_fixture};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Copy of a fixture the test may write to, leaving the checked-in one as is.
+fn fixture_copy(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("golden-{}-{}", std::process::id(), name));
+    std::fs::copy(fixture(name), &path).unwrap();
+    path
+}
+
+#[test]
+fn account_events_of_every_revision_still_decode() {
+    use AccountBalanceEventPayload::*;
+
+    // Written before events were wrapped in envelopes.
+    assert_eq!(
+        decode_fixture::<AccountBalanceEventPayload>(
+            fixture("account-events-bare.jsonl"),
+            &upcasters()
+        ),
+        vec![
+            AmountDeposited { value: 1000 },
+            AmountWithdrawn { value: 50 },
+            FeeApplied { value: 5 },
+        ]
+    );
+    // Version 1 envelopes, with and without event id and signature, from
+    // before `WithdrawalRejected { value }` was renamed.
+    assert_eq!(
+        decode_fixture::<AccountBalanceEventPayload>(
+            fixture("account-events-v1.jsonl"),
+            &upcasters()
+        ),
+        vec![
+            AmountDeposited { value: 500 },
+            AmountWithdrawn { value: 100 },
+            WithdrawalRejected {
+                requested: 1000,
+                reason: "withdrawing 1000 from 400 exceeds the overdraft limit of 50".to_string(),
+            },
+            FeeApplied { value: 2 },
+        ]
+    );
+    assert_eq!(
+        decode_fixture::<AccountBalanceEventPayload>(
+            fixture("account-events-v2.jsonl"),
+            &upcasters()
+        ),
+        vec![
+            AmountDeposited { value: 300 },
+            WithdrawalRejected {
+                requested: 900,
+                reason: "withdrawing 900 from 300 exceeds the overdraft limit of 50".to_string(),
+            },
+            AmountWithdrawn { value: 50 },
+        ]
+    );
+}
+
+#[test]
+fn v1_rejections_need_the_upcaster() {
+    let events = read_fixture(fixture("account-events-v1.jsonl"));
+    // Deserialized as stored, since another test installs the upcasters.
+    let v1 = EventEnvelope::from_recorded::<AccountBalanceEventPayload>(&events[2]).unwrap();
+    assert!(serde_json::from_value::<AccountBalanceEventPayload>(v1.payload).is_err());
+}
+
+#[cfg(feature = "scheduler")]
+#[test]
+fn scheduled_fee_commands_still_decode() {
+    use ch2_account_balance::AccountBalanceCommand;
+    use eventsourcing::scheduler::ScheduleEvent;
+
+    let events: Vec<ScheduleEvent> =
+        decode_fixture(fixture("fee-schedule.jsonl"), &Upcasters::new());
+    let ScheduleEvent::Scheduled(scheduled) = &events[0] else {
+        panic!("expected a scheduled command, got {:?}", events[0]);
+    };
+    let command: AccountBalanceCommand = serde_json::from_value(scheduled.command.clone()).unwrap();
+    assert!(matches!(
+        command,
+        AccountBalanceCommand::ApplyFee { value: 2 }
+    ));
+}
+
+#[tokio::test]
+async fn accounts_rehydrate_from_golden_logs_and_snapshots() {
+    upcasters().install();
+    let store = Arc::new(InMemoryEventStore::new());
+    for name in [
+        "account-events-bare.jsonl",
+        "account-events-v1.jsonl",
+        "account-events-v2.jsonl",
+    ] {
+        for event in read_fixture(fixture(name)) {
+            store
+                .append(
+                    &event.stream_id,
+                    ExpectedVersion::Exact(event.version - 1),
+                    vec![event.payload],
+                )
+                .await
+                .unwrap();
+        }
+    }
+    let snapshots = JsonFileSnapshotStore::open(fixture_copy("account-snapshots.json")).unwrap();
+    let accounts = AccountBalance::new(store).with_snapshots(Arc::new(snapshots));
+
+    assert_eq!(accounts.balance("ACCOUNT1").await.unwrap(), 945);
+    assert_eq!(accounts.balance("ACCOUNT3").await.unwrap(), 250);
+    // The snapshot keeps the ids of the events it covers, so a redelivered
+    // event is still recognized.
+    accounts
+        .apply_event(AccountBalanceEvent {
+            account_number: "ACCOUNT2".to_string(),
+            event_id: Some("atm-0001".to_string()),
+            payload: AccountBalanceEventPayload::AmountWithdrawn { value: 100 },
+        })
+        .await
+        .unwrap();
+    assert_eq!(accounts.balance("ACCOUNT2").await.unwrap(), 398);
+}
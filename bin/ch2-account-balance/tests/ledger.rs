@@ -0,0 +1,27 @@
+use ch2_account_balance::{AccountBalanceEventPayload, Ledger};
+use eventsourcing::testing::ProjectionTester;
+
+#[tokio::test]
+async fn ledger_tracks_running_balance_per_account() {
+    let mut tester = ProjectionTester::new(Ledger::default());
+
+    tester
+        .feed("account/A", AccountBalanceEventPayload::AmountDeposited { value: 100 })
+        .await
+        .unwrap();
+    tester
+        .feed("account/B", AccountBalanceEventPayload::AmountDeposited { value: 50 })
+        .await
+        .unwrap();
+    tester
+        .feed("account/A", AccountBalanceEventPayload::FeeApplied { value: 5 })
+        .await
+        .unwrap();
+    tester
+        .feed("account/B", AccountBalanceEventPayload::AmountWithdrawn { value: 20 })
+        .await
+        .unwrap();
+
+    assert_eq!(tester.read_model().balance("account/A"), 95);
+    assert_eq!(tester.read_model().balance("account/B"), 30);
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceCommand, AccountBalanceEvent, AccountBalanceEventPayload,
+    AccountBalanceRejection, AccountLimits,
+};
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+
+#[tokio::test]
+async fn withdrawals_respect_overdraft_and_transaction_limits() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let accounts = AccountBalance::new(store.clone()).with_limits(AccountLimits {
+        overdraft_limit: 50,
+        max_transaction: Some(200),
+    });
+    let execute = |command| accounts.execute("LIMITS1", command);
+
+    assert_eq!(
+        execute(AccountBalanceCommand::Deposit { value: 100 })
+            .await
+            .unwrap(),
+        Ok(100)
+    );
+    assert_eq!(
+        execute(AccountBalanceCommand::Withdraw { value: 150 })
+            .await
+            .unwrap(),
+        Ok(-50)
+    );
+    assert_eq!(
+        execute(AccountBalanceCommand::Withdraw { value: 1 })
+            .await
+            .unwrap(),
+        Err(AccountBalanceRejection::OverdraftLimitExceeded {
+            value: 1,
+            balance: -50,
+            overdraft_limit: 50,
+        })
+    );
+    assert_eq!(
+        execute(AccountBalanceCommand::Deposit { value: 300 })
+            .await
+            .unwrap(),
+        Err(AccountBalanceRejection::TransactionLimitExceeded {
+            value: 300,
+            max: 200
+        })
+    );
+    assert_eq!(
+        execute(AccountBalanceCommand::Withdraw { value: 0 })
+            .await
+            .unwrap(),
+        Err(AccountBalanceRejection::NonPositiveAmount)
+    );
+    assert_eq!(
+        AccountBalance::get_balance("LIMITS1").await.unwrap(),
+        Some(-50)
+    );
+
+    // Rejected withdrawals are recorded, rejected deposits are not.
+    let events = store.read_stream("account/LIMITS1", 0).await.unwrap();
+    let payloads = events
+        .iter()
+        .map(|event| event.decode::<AccountBalanceEventPayload>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(payloads.len(), 4);
+    assert!(matches!(
+        payloads[2],
+        AccountBalanceEventPayload::WithdrawalRejected { requested: 1, .. }
+    ));
+    assert!(matches!(
+        payloads[3],
+        AccountBalanceEventPayload::WithdrawalRejected { requested: 0, .. }
+    ));
+}
+
+#[tokio::test]
+async fn applied_events_respect_the_limits() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let accounts = AccountBalance::new(store.clone()).with_limits(AccountLimits {
+        overdraft_limit: 0,
+        max_transaction: None,
+    });
+    let apply = |event_id: &str, payload| {
+        accounts.apply_event(AccountBalanceEvent {
+            account_number: "LIMITS2".to_string(),
+            event_id: Some(event_id.to_string()),
+            payload,
+        })
+    };
+
+    apply(
+        "d1",
+        AccountBalanceEventPayload::AmountDeposited { value: 100 },
+    )
+    .await
+    .unwrap();
+    apply(
+        "w1",
+        AccountBalanceEventPayload::AmountWithdrawn { value: i64::MAX },
+    )
+    .await
+    .unwrap();
+    // A redelivered rejection is not recorded twice.
+    apply(
+        "w1",
+        AccountBalanceEventPayload::AmountWithdrawn { value: i64::MAX },
+    )
+    .await
+    .unwrap();
+    assert_eq!(accounts.balance("LIMITS2").await.unwrap(), 100);
+
+    let events = store.read_stream("account/LIMITS2", 0).await.unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        events[1].decode().unwrap(),
+        AccountBalanceEventPayload::WithdrawalRejected {
+            requested: i64::MAX,
+            ..
+        }
+    ));
+}
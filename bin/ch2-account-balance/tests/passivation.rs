@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceCommand, AccountBalanceEvent, AccountBalanceEventPayload,
+};
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::InMemoryEventStore;
+
+const IDLE_TIMEOUT: Duration = Duration::from_millis(50);
+
+async fn wait_until_passivated(account_number: &str) {
+    for _ in 0..100 {
+        if AccountBalance::where_is(account_number).is_none() {
+            return;
+        }
+        tokio::time::sleep(IDLE_TIMEOUT).await;
+    }
+    panic!("{} was not passivated", account_number);
+}
+
+#[tokio::test]
+async fn idle_accounts_are_passivated_and_respawned() {
+    let snapshots = Arc::new(InMemorySnapshotStore::new());
+    let accounts = AccountBalance::new(Arc::new(InMemoryEventStore::new()))
+        .with_passivation(IDLE_TIMEOUT)
+        .with_snapshots(snapshots.clone());
+
+    accounts
+        .execute("IDLE1", AccountBalanceCommand::Deposit { value: 100 })
+        .await
+        .unwrap()
+        .unwrap();
+    wait_until_passivated("IDLE1").await;
+    let snapshot = snapshots.load("account/IDLE1").await.unwrap().unwrap();
+    assert_eq!(snapshot.version, 1);
+
+    // Respawned from the snapshot by the next command.
+    assert_eq!(
+        accounts
+            .execute("IDLE1", AccountBalanceCommand::Withdraw { value: 30 })
+            .await
+            .unwrap(),
+        Ok(70)
+    );
+    wait_until_passivated("IDLE1").await;
+
+    // And by the next event.
+    accounts
+        .apply_event(AccountBalanceEvent {
+            account_number: "IDLE1".to_string(),
+            event_id: None,
+            payload: AccountBalanceEventPayload::FeeApplied { value: 5 },
+        })
+        .await
+        .unwrap();
+    assert_eq!(accounts.balance("IDLE1").await.unwrap(), 65);
+}
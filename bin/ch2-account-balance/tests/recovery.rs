@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceArgs, AccountBalanceEvent, AccountBalanceEventPayload,
+};
+use eventsourcing::store::InMemoryEventStore;
+
+fn event(payload: AccountBalanceEventPayload) -> AccountBalanceEvent {
+    AccountBalanceEvent {
+        account_number: "RECOVERY1".to_string(),
+        event_id: None,
+        payload,
+    }
+}
+
+#[tokio::test]
+async fn balance_survives_actor_restart() {
+    let accounts = AccountBalance::new(Arc::new(InMemoryEventStore::new()));
+    let (actor, handle) = accounts
+        .spawn(AccountBalanceArgs::new("RECOVERY1".to_string()))
+        .await
+        .unwrap();
+
+    accounts
+        .apply_event(event(AccountBalanceEventPayload::AmountDeposited { value: 100 }))
+        .await
+        .unwrap();
+    accounts
+        .apply_event(event(AccountBalanceEventPayload::FeeApplied { value: 5 }))
+        .await
+        .unwrap();
+    assert_eq!(
+        AccountBalance::get_balance("RECOVERY1").await.unwrap(),
+        Some(95)
+    );
+
+    actor.kill();
+    handle.await.unwrap();
+    assert!(AccountBalance::where_is("RECOVERY1").is_none());
+
+    accounts
+        .apply_event(event(AccountBalanceEventPayload::AmountWithdrawn { value: 15 }))
+        .await
+        .unwrap();
+    assert_eq!(
+        AccountBalance::get_balance("RECOVERY1").await.unwrap(),
+        Some(80)
+    );
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceArgs, AccountBalanceCommand, AccountBalanceEvent,
+    AccountBalanceEventPayload, AccountBalanceRejection,
+};
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+
+fn deposit(event_id: &str, value: i64) -> AccountBalanceEvent {
+    AccountBalanceEvent {
+        account_number: "RETRY1".to_string(),
+        event_id: Some(event_id.to_string()),
+        payload: AccountBalanceEventPayload::AmountDeposited { value },
+    }
+}
+
+#[tokio::test]
+async fn retried_events_are_applied_once() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let accounts = AccountBalance::new(store.clone());
+    let (actor, handle) = accounts
+        .spawn(AccountBalanceArgs::new("RETRY1".to_string()))
+        .await
+        .unwrap();
+
+    accounts.apply_event(deposit("d1", 100)).await.unwrap();
+    accounts.apply_event(deposit("d1", 100)).await.unwrap();
+    accounts.apply_event(deposit("d2", 10)).await.unwrap();
+    assert_eq!(
+        AccountBalance::get_balance("RETRY1").await.unwrap(),
+        Some(110)
+    );
+
+    // A retry reaching a restarted actor is recognized from the replayed log.
+    actor.kill();
+    handle.await.unwrap();
+    accounts.apply_event(deposit("d1", 100)).await.unwrap();
+    assert_eq!(
+        AccountBalance::get_balance("RETRY1").await.unwrap(),
+        Some(110)
+    );
+    assert_eq!(
+        store.read_stream("account/RETRY1", 0).await.unwrap().len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn retried_rejections_are_replied_again() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let accounts = AccountBalance::new(store.clone());
+    let (actor, handle) = accounts
+        .spawn(AccountBalanceArgs::new("RETRY3".to_string()))
+        .await
+        .unwrap();
+    let withdraw = AccountBalanceCommand::Withdraw { value: 50 };
+    let rejection = AccountBalanceRejection::OverdraftLimitExceeded {
+        value: 50,
+        balance: 0,
+        overdraft_limit: 0,
+    };
+
+    let first = accounts
+        .execute_once("RETRY3", withdraw.clone(), Some("w1".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(first, Err(rejection.clone()));
+    accounts
+        .execute("RETRY3", AccountBalanceCommand::Deposit { value: 100 })
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The retry is not withdrawn now that the balance covers it.
+    let retried = accounts
+        .execute_once("RETRY3", withdraw.clone(), Some("w1".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(retried, Err(rejection.clone()));
+
+    // Nor by a restarted actor, which rebuilds the rejection from the log.
+    actor.kill();
+    handle.await.unwrap();
+    let retried = accounts
+        .execute_once("RETRY3", withdraw, Some("w1".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(retried, Err(rejection));
+    assert_eq!(accounts.balance("RETRY3").await.unwrap(), 100);
+    assert_eq!(
+        store.read_stream("account/RETRY3", 0).await.unwrap().len(),
+        2
+    );
+}
+
+#[cfg(feature = "scheduler")]
+#[tokio::test]
+async fn redispatched_occurrences_are_applied_once() {
+    use eventsourcing::scheduler::{occurrence_id, CommandDispatcher};
+
+    let store = Arc::new(InMemoryEventStore::new());
+    let accounts = AccountBalance::new(store.clone());
+    let fee = serde_json::json!({ "ApplyFee": { "value": 5 } });
+
+    // The scheduler retries an occurrence whose `Fired` it failed to record.
+    let first = occurrence_id("monthly-fee/RETRY2", 1000);
+    accounts
+        .dispatch("account/RETRY2", fee.clone(), &first)
+        .await
+        .unwrap();
+    accounts
+        .dispatch("account/RETRY2", fee.clone(), &first)
+        .await
+        .unwrap();
+    assert_eq!(accounts.balance("RETRY2").await.unwrap(), -5);
+
+    let next = occurrence_id("monthly-fee/RETRY2", 2000);
+    accounts
+        .dispatch("account/RETRY2", fee, &next)
+        .await
+        .unwrap();
+    assert_eq!(accounts.balance("RETRY2").await.unwrap(), -10);
+    assert_eq!(
+        store.read_stream("account/RETRY2", 0).await.unwrap().len(),
+        2
+    );
+}
@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use eventsourcing::Aggregate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const STREAM_TYPE: &str = "account";
+
+pub fn stream_id(account_number: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, account_number)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Account {
+    balance: i64,
+    frozen: bool,
+    /// Transfer steps already handled, so redelivered saga commands are no-ops.
+    transfer_steps: BTreeSet<(String, TransferStep)>,
+    /// Amounts debited by transfers and not refunded.
+    #[serde(default)]
+    refundable: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TransferStep {
+    Debit,
+    Credit,
+    Refund,
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountCommand {
+    Deposit {
+        amount: i64,
+    },
+    Freeze,
+    /// Withdraws the transfer amount from the source account.
+    Debit {
+        transfer_id: String,
+        amount: i64,
+    },
+    /// Deposits the transfer amount into the target account.
+    Credit {
+        transfer_id: String,
+        amount: i64,
+    },
+    /// Gives a debited amount back to the source when the credit failed.
+    /// Only the amount this account recorded as debited by `transfer_id` can
+    /// be refunded, once.
+    Refund {
+        transfer_id: String,
+        amount: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccountEvent {
+    Deposited { amount: i64 },
+    Frozen,
+    Debited { transfer_id: String, amount: i64 },
+    DebitRejected { transfer_id: String, reason: String },
+    Credited { transfer_id: String, amount: i64 },
+    CreditRejected { transfer_id: String, reason: String },
+    Refunded { transfer_id: String, amount: i64 },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AccountError {
+    #[error("amount must be positive")]
+    NonPositiveAmount,
+    #[error("account is frozen")]
+    Frozen,
+    #[error("insufficient funds: balance {balance}, requested {requested}")]
+    InsufficientFunds { balance: i64, requested: i64 },
+    #[error("transfer {transfer_id} debited nothing to refund")]
+    NothingToRefund { transfer_id: String },
+    #[error("transfer {transfer_id} debited {debited}, cannot refund {requested}")]
+    RefundMismatch {
+        transfer_id: String,
+        debited: i64,
+        requested: i64,
+    },
+    #[error("amount {amount} takes the balance of {balance} out of range")]
+    BalanceOutOfRange { amount: i64, balance: i64 },
+}
+
+impl Account {
+    pub fn balance(&self) -> i64 {
+        self.balance
+    }
+
+    /// Amount debited by `transfer_id` and not refunded yet.
+    pub fn refundable(&self, transfer_id: &str) -> Option<i64> {
+        self.refundable.get(transfer_id).copied()
+    }
+
+    fn has_handled(&self, transfer_id: &str, step: TransferStep) -> bool {
+        self.transfer_steps
+            .contains(&(transfer_id.to_string(), step))
+    }
+
+    fn check_debit(&self, amount: i64) -> Result<(), AccountError> {
+        if amount <= 0 {
+            Err(AccountError::NonPositiveAmount)
+        } else if self.frozen {
+            Err(AccountError::Frozen)
+        } else if amount > self.balance {
+            Err(AccountError::InsufficientFunds {
+                balance: self.balance,
+                requested: amount,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_credit(&self, amount: i64) -> Result<(), AccountError> {
+        if amount <= 0 {
+            Err(AccountError::NonPositiveAmount)
+        } else if self.frozen {
+            Err(AccountError::Frozen)
+        } else {
+            self.check_in_range(amount)
+        }
+    }
+
+    fn check_in_range(&self, amount: i64) -> Result<(), AccountError> {
+        match self.balance.checked_add(amount) {
+            Some(_) => Ok(()),
+            None => Err(AccountError::BalanceOutOfRange {
+                amount,
+                balance: self.balance,
+            }),
+        }
+    }
+}
+
+impl Aggregate for Account {
+    type Command = AccountCommand;
+    type Event = AccountEvent;
+    type Error = AccountError;
+
+    /// Transfer steps never fail: rejections are recorded as events so the
+    /// saga driving the transfer can react to them.
+    fn handle_command(&self, command: AccountCommand) -> Result<Vec<AccountEvent>, AccountError> {
+        let event = match command {
+            AccountCommand::Deposit { amount } => {
+                self.check_credit(amount)?;
+                AccountEvent::Deposited { amount }
+            }
+            AccountCommand::Freeze => AccountEvent::Frozen,
+            AccountCommand::Debit { transfer_id, .. }
+                if self.has_handled(&transfer_id, TransferStep::Debit) =>
+            {
+                return Ok(vec![]);
+            }
+            AccountCommand::Debit {
+                transfer_id,
+                amount,
+            } => match self.check_debit(amount) {
+                Ok(()) => AccountEvent::Debited {
+                    transfer_id,
+                    amount,
+                },
+                Err(err) => AccountEvent::DebitRejected {
+                    transfer_id,
+                    reason: err.to_string(),
+                },
+            },
+            AccountCommand::Credit { transfer_id, .. }
+                if self.has_handled(&transfer_id, TransferStep::Credit) =>
+            {
+                return Ok(vec![]);
+            }
+            AccountCommand::Credit {
+                transfer_id,
+                amount,
+            } => match self.check_credit(amount) {
+                Ok(()) => AccountEvent::Credited {
+                    transfer_id,
+                    amount,
+                },
+                Err(err) => AccountEvent::CreditRejected {
+                    transfer_id,
+                    reason: err.to_string(),
+                },
+            },
+            AccountCommand::Refund { transfer_id, .. }
+                if self.has_handled(&transfer_id, TransferStep::Refund) =>
+            {
+                return Ok(vec![]);
+            }
+            // Refunds return money that was ours, so they bypass the freeze.
+            AccountCommand::Refund {
+                transfer_id,
+                amount,
+            } => match self.refundable(&transfer_id) {
+                Some(debited) if debited == amount => {
+                    self.check_in_range(amount)?;
+                    AccountEvent::Refunded {
+                        transfer_id,
+                        amount,
+                    }
+                }
+                Some(debited) => {
+                    return Err(AccountError::RefundMismatch {
+                        transfer_id,
+                        debited,
+                        requested: amount,
+                    })
+                }
+                None => return Err(AccountError::NothingToRefund { transfer_id }),
+            },
+        };
+        Ok(vec![event])
+    }
+
+    /// Commands keep the balance in range, so saturating only matters for
+    /// events recorded before they did.
+    fn apply_event(&mut self, event: AccountEvent) {
+        match event {
+            AccountEvent::Deposited { amount } => {
+                self.balance = self.balance.saturating_add(amount)
+            }
+            AccountEvent::Frozen => self.frozen = true,
+            AccountEvent::Debited {
+                transfer_id,
+                amount,
+            } => {
+                self.balance = self.balance.saturating_sub(amount);
+                self.refundable.insert(transfer_id.clone(), amount);
+                self.transfer_steps
+                    .insert((transfer_id, TransferStep::Debit));
+            }
+            AccountEvent::DebitRejected { transfer_id, .. } => {
+                self.transfer_steps
+                    .insert((transfer_id, TransferStep::Debit));
+            }
+            AccountEvent::Credited {
+                transfer_id,
+                amount,
+            } => {
+                self.balance = self.balance.saturating_add(amount);
+                self.transfer_steps
+                    .insert((transfer_id, TransferStep::Credit));
+            }
+            AccountEvent::CreditRejected { transfer_id, .. } => {
+                self.transfer_steps
+                    .insert((transfer_id, TransferStep::Credit));
+            }
+            AccountEvent::Refunded {
+                transfer_id,
+                amount,
+            } => {
+                self.balance = self.balance.saturating_add(amount);
+                self.refundable.remove(&transfer_id);
+                self.transfer_steps
+                    .insert((transfer_id, TransferStep::Refund));
+            }
+        }
+    }
+}
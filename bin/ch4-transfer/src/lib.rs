@@ -0,0 +1,3 @@
+pub mod account;
+pub mod saga;
+pub mod transfer;
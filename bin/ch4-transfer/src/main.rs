@@ -0,0 +1,135 @@
+use anyhow::Context;
+use ch4_transfer::account::{self, Account, AccountCommand};
+use ch4_transfer::saga::TransferSaga;
+use ch4_transfer::transfer::{self, Transfer, TransferCommand};
+use eventsourcing::app::App;
+use eventsourcing::audit::AuditLog;
+use eventsourcing::ops::OpsLog;
+use eventsourcing::selftest;
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+use eventsourcing::AggregateActor;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+async fn inner() -> anyhow::Result<()> {
+    let _logging = local_logging::init()?;
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    // `--self-test` checks the store and the actor runtime, then exits.
+    if selftest::requested() {
+        return Ok(selftest::run_and_print(&store).await?);
+    }
+    let audit = AuditLog::new(Arc::clone(&store));
+    let builder = App::builder(Arc::clone(&store))
+        .with_aggregate_actor(
+            AggregateActor::<Account>::new(Arc::clone(&store))
+                .with_audit_log(audit.clone())
+                .with_ops_log(OpsLog::new(Arc::clone(&store), account::STREAM_TYPE)),
+        )
+        .with_aggregate_actor(
+            AggregateActor::<Transfer>::new(Arc::clone(&store))
+                .with_ops_log(OpsLog::new(Arc::clone(&store), transfer::STREAM_TYPE)),
+        );
+    let accounts = builder
+        .repository::<Account>()
+        .context("accounts are not registered")?;
+    let transfers = builder
+        .repository::<Transfer>()
+        .context("transfers are not registered")?;
+    let saga = TransferSaga::new(
+        accounts.clone().with_source("transfer-saga"),
+        transfers.clone(),
+    );
+    let app = builder
+        .with_subscriber(
+            "transfer-saga",
+            &[account::STREAM_TYPE, transfer::STREAM_TYPE],
+            saga,
+        )
+        .build()
+        .await?;
+    let accounts = accounts.with_source("teller");
+
+    accounts
+        .execute_with_reply(
+            &account::stream_id("alice"),
+            AccountCommand::Deposit { amount: 100 },
+            RPC_TIMEOUT,
+        )
+        .await??;
+    accounts
+        .execute_with_reply(
+            &account::stream_id("carol"),
+            AccountCommand::Freeze,
+            RPC_TIMEOUT,
+        )
+        .await??;
+    if let Err(err) = accounts
+        .execute_with_reply(
+            &account::stream_id("carol"),
+            AccountCommand::Deposit { amount: 10 },
+            RPC_TIMEOUT,
+        )
+        .await?
+    {
+        println!("deposit to carol rejected: {}", err);
+    }
+
+    // Completes, fails on the debit, and is compensated after the credit
+    // to the frozen account is rejected.
+    for (transfer_id, to, amount) in [("t1", "bob", 30), ("t2", "bob", 500), ("t3", "carol", 20)] {
+        transfers
+            .execute_with_reply(
+                &transfer::stream_id(transfer_id),
+                TransferCommand::Start {
+                    from: "alice".to_string(),
+                    to: to.to_string(),
+                    amount,
+                },
+                RPC_TIMEOUT,
+            )
+            .await??;
+    }
+
+    for transfer_id in ["t1", "t2", "t3"] {
+        let transfer = loop {
+            let transfer = transfers
+                .get_state(&transfer::stream_id(transfer_id), RPC_TIMEOUT)
+                .await?;
+            if transfer.status.is_terminal() {
+                break transfer;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        };
+        println!("transfer {}: {:?}", transfer_id, transfer);
+    }
+    for account_number in ["alice", "bob", "carol"] {
+        let account = accounts
+            .get_state(&account::stream_id(account_number), RPC_TIMEOUT)
+            .await?;
+        println!("balance of {}: {}", account_number, account.balance());
+    }
+    for record in audit.query(&account::stream_id("carol"), ..).await? {
+        println!(
+            "audit of carol: {:?} from {:?}: {:?}",
+            record.command, record.source, record.outcome
+        );
+    }
+
+    app.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use eventsourcing::bus::EventHandler;
+use eventsourcing::envelope::Correlation;
+use eventsourcing::store::RecordedEvent;
+use eventsourcing::{AggregateMessage, AggregateRepository};
+use ractor::{async_trait, ActorProcessingErr};
+
+use crate::account::{self, Account, AccountCommand, AccountEvent};
+use crate::transfer::{self, Transfer, TransferCommand, TransferEvent};
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(1);
+const REFUND_ATTEMPTS: u32 = 3;
+const REFUND_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Drives transfers by turning committed events into the next command.
+///
+/// ```text
+/// Started              -> Debit source         -> Debited / DebitRejected
+/// Debited              -> Credit target        -> Credited / CreditRejected
+/// CompensationStarted  -> Refund source        -> Refunded / RefundRetried /
+///                                                 CompensationFailed
+/// RefundRetried        -> Refund source        -> same as above
+/// ```
+///
+/// Account outcomes are recorded back on the transfer, which decides the next
+/// step. Commands carry the [`Correlation`] of the event they follow, so all
+/// the events of a transfer share the correlation id of the command starting
+/// it. The saga keeps no state of its own: it runs in an
+/// [`EventSubscriber`](eventsourcing::bus::EventSubscriber) subscribed to both
+/// stream types, and since every command it sends is idempotent per transfer,
+/// replaying the log after a restart simply resumes unfinished transfers.
+///
+/// A refund is the only step with no rejection event to record, so the saga
+/// waits for its outcome. When the account does not answer, it records
+/// `RefundRetried` on the transfer after a delay, with a ractor timer rather
+/// than holding up the subscriber, and refunds again on that event. It
+/// records `CompensationFailed` once the refund is rejected or the attempts
+/// run out, leaving it to an operator.
+///
+/// Events of variants unknown to this release are skipped.
+#[derive(Clone)]
+pub struct TransferSaga {
+    accounts: AggregateRepository<Account>,
+    transfers: AggregateRepository<Transfer>,
+}
+
+impl TransferSaga {
+    pub fn new(
+        accounts: AggregateRepository<Account>,
+        transfers: AggregateRepository<Transfer>,
+    ) -> Self {
+        Self {
+            accounts,
+            transfers,
+        }
+    }
+
+    async fn on_account_event(
+        &self,
+        event: AccountEvent,
+        correlation: Correlation,
+    ) -> Result<(), ActorProcessingErr> {
+        let (transfer_id, command) = match event {
+            AccountEvent::Debited { transfer_id, .. } => {
+                (transfer_id, TransferCommand::RecordDebit)
+            }
+            AccountEvent::DebitRejected {
+                transfer_id,
+                reason,
+            } => (transfer_id, TransferCommand::RecordDebitRejected { reason }),
+            AccountEvent::Credited { transfer_id, .. } => {
+                (transfer_id, TransferCommand::RecordCredit)
+            }
+            AccountEvent::CreditRejected {
+                transfer_id,
+                reason,
+            } => (
+                transfer_id,
+                TransferCommand::RecordCreditRejected { reason },
+            ),
+            AccountEvent::Refunded { transfer_id, .. } => {
+                (transfer_id, TransferCommand::RecordRefund)
+            }
+            AccountEvent::Deposited { .. } | AccountEvent::Frozen => return Ok(()),
+        };
+        self.transfers
+            .execute_correlated(&transfer::stream_id(&transfer_id), command, correlation)
+            .await?;
+        Ok(())
+    }
+
+    async fn on_transfer_event(
+        &self,
+        transfer_id: &str,
+        event: TransferEvent,
+        correlation: Correlation,
+    ) -> Result<(), ActorProcessingErr> {
+        let (account_number, command) = match event {
+            TransferEvent::Started { from, amount, .. } => (
+                from,
+                AccountCommand::Debit {
+                    transfer_id: transfer_id.to_string(),
+                    amount,
+                },
+            ),
+            TransferEvent::Debited => {
+                let transfer = self.transfer(transfer_id).await?;
+                (
+                    transfer.to,
+                    AccountCommand::Credit {
+                        transfer_id: transfer_id.to_string(),
+                        amount: transfer.amount,
+                    },
+                )
+            }
+            TransferEvent::CompensationStarted { .. } => {
+                let transfer = self.transfer(transfer_id).await?;
+                return self.refund(transfer_id, &transfer, 1, correlation).await;
+            }
+            TransferEvent::RefundRetried { attempt } => {
+                let transfer = self.transfer(transfer_id).await?;
+                return self
+                    .refund(transfer_id, &transfer, attempt, correlation)
+                    .await;
+            }
+            TransferEvent::Completed
+            | TransferEvent::Failed { .. }
+            | TransferEvent::Compensated
+            | TransferEvent::CompensationFailed { .. } => return Ok(()),
+        };
+        self.accounts
+            .execute_correlated(&account::stream_id(&account_number), command, correlation)
+            .await?;
+        Ok(())
+    }
+
+    async fn refund(
+        &self,
+        transfer_id: &str,
+        transfer: &Transfer,
+        attempt: u32,
+        correlation: Correlation,
+    ) -> Result<(), ActorProcessingErr> {
+        let command = AccountCommand::Refund {
+            transfer_id: transfer_id.to_string(),
+            amount: transfer.amount,
+        };
+        let error = match self
+            .accounts
+            .execute_correlated_with_reply(
+                &account::stream_id(&transfer.from),
+                command,
+                correlation.clone(),
+                RPC_TIMEOUT,
+            )
+            .await
+        {
+            // The Refunded event completes the compensation.
+            Ok(Ok(_)) => return Ok(()),
+            Ok(Err(rejection)) => rejection.to_string(),
+            Err(err) if attempt < REFUND_ATTEMPTS => {
+                tracing::warn!(transfer_id, attempt, "refund failed, retrying: {}", err);
+                let transfer = self
+                    .transfers
+                    .get(&transfer::stream_id(transfer_id))
+                    .await?;
+                transfer.send_after(REFUND_RETRY_DELAY * attempt, move || {
+                    AggregateMessage::ExecuteFrom {
+                        source: None,
+                        correlation: Some(correlation),
+                        command_id: None,
+                        command: TransferCommand::RetryRefund {
+                            attempt: attempt + 1,
+                        },
+                        reply_port: None,
+                    }
+                });
+                return Ok(());
+            }
+            Err(err) => err.to_string(),
+        };
+        tracing::error!(
+            transfer_id,
+            "refund failed, compensation needs an operator: {}",
+            error
+        );
+        self.transfers
+            .execute_correlated(
+                &transfer::stream_id(transfer_id),
+                TransferCommand::RecordRefundFailed { error },
+                correlation,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn transfer(&self, transfer_id: &str) -> Result<Transfer, ActorProcessingErr> {
+        Ok(self
+            .transfers
+            .get_state(&transfer::stream_id(transfer_id), RPC_TIMEOUT)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl EventHandler for TransferSaga {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        let correlation = Correlation::caused_by(event);
+        match event.stream_id.split_once('/') {
+            Some((account::STREAM_TYPE, _)) => match event.decode_known()? {
+                Some(account_event) => self.on_account_event(account_event, correlation).await,
+                None => Ok(()),
+            },
+            Some((transfer::STREAM_TYPE, transfer_id)) => match event.decode_known()? {
+                Some(transfer_event) => {
+                    self.on_transfer_event(transfer_id, transfer_event, correlation)
+                        .await
+                }
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
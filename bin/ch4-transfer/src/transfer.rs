@@ -0,0 +1,202 @@
+use eventsourcing::Aggregate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const STREAM_TYPE: &str = "transfer";
+
+pub fn stream_id(transfer_id: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, transfer_id)
+}
+
+/// Process state of a money transfer between two accounts.
+///
+/// The aggregate only records what the saga observed. It never touches the
+/// accounts itself, so a record command arriving in an unexpected status is
+/// ignored rather than rejected, which keeps redelivered events harmless.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: i64,
+    pub status: TransferStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferStatus {
+    #[default]
+    New,
+    Started,
+    Debited,
+    Completed,
+    Failed {
+        reason: String,
+    },
+    Compensating {
+        reason: String,
+    },
+    Compensated {
+        reason: String,
+    },
+    /// The saga gave up refunding the source account, see `TransferSaga`.
+    /// Needs an operator; a refund recorded later still compensates it.
+    CompensationFailed {
+        reason: String,
+        error: String,
+    },
+}
+
+impl TransferStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TransferStatus::Completed
+                | TransferStatus::Failed { .. }
+                | TransferStatus::Compensated { .. }
+                | TransferStatus::CompensationFailed { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TransferCommand {
+    Start {
+        from: String,
+        to: String,
+        amount: i64,
+    },
+    RecordDebit,
+    RecordDebitRejected {
+        reason: String,
+    },
+    RecordCredit,
+    RecordCreditRejected {
+        reason: String,
+    },
+    RecordRefund,
+    /// The refund got no answer and is tried again, see `TransferSaga`.
+    RetryRefund {
+        attempt: u32,
+    },
+    RecordRefundFailed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TransferEvent {
+    Started {
+        from: String,
+        to: String,
+        amount: i64,
+    },
+    Debited,
+    Completed,
+    Failed {
+        reason: String,
+    },
+    CompensationStarted {
+        reason: String,
+    },
+    Compensated,
+    RefundRetried {
+        attempt: u32,
+    },
+    CompensationFailed {
+        error: String,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum TransferError {
+    #[error("transfer already started")]
+    AlreadyStarted,
+    #[error("amount must be positive")]
+    NonPositiveAmount,
+    #[error("cannot transfer to the same account")]
+    SameAccount,
+}
+
+impl Aggregate for Transfer {
+    type Command = TransferCommand;
+    type Event = TransferEvent;
+    type Error = TransferError;
+
+    fn handle_command(
+        &self,
+        command: TransferCommand,
+    ) -> Result<Vec<TransferEvent>, TransferError> {
+        let events = match (&self.status, command) {
+            (TransferStatus::New, TransferCommand::Start { from, to, amount }) => {
+                if amount <= 0 {
+                    return Err(TransferError::NonPositiveAmount);
+                }
+                if from == to {
+                    return Err(TransferError::SameAccount);
+                }
+                vec![TransferEvent::Started { from, to, amount }]
+            }
+            (_, TransferCommand::Start { .. }) => return Err(TransferError::AlreadyStarted),
+            (TransferStatus::Started, TransferCommand::RecordDebit) => {
+                vec![TransferEvent::Debited]
+            }
+            (TransferStatus::Started, TransferCommand::RecordDebitRejected { reason }) => {
+                vec![TransferEvent::Failed { reason }]
+            }
+            (TransferStatus::Debited, TransferCommand::RecordCredit) => {
+                vec![TransferEvent::Completed]
+            }
+            (TransferStatus::Debited, TransferCommand::RecordCreditRejected { reason }) => {
+                vec![TransferEvent::CompensationStarted { reason }]
+            }
+            (
+                TransferStatus::Compensating { .. } | TransferStatus::CompensationFailed { .. },
+                TransferCommand::RecordRefund,
+            ) => vec![TransferEvent::Compensated],
+            (TransferStatus::Compensating { .. }, TransferCommand::RetryRefund { attempt }) => {
+                vec![TransferEvent::RefundRetried { attempt }]
+            }
+            (
+                TransferStatus::Compensating { .. },
+                TransferCommand::RecordRefundFailed { error },
+            ) => {
+                vec![TransferEvent::CompensationFailed { error }]
+            }
+            (status, command) => {
+                tracing::debug!("ignoring {:?} in status {:?}", command, status);
+                vec![]
+            }
+        };
+        Ok(events)
+    }
+
+    fn apply_event(&mut self, event: TransferEvent) {
+        self.status = match event {
+            TransferEvent::Started { from, to, amount } => {
+                self.from = from;
+                self.to = to;
+                self.amount = amount;
+                TransferStatus::Started
+            }
+            TransferEvent::Debited => TransferStatus::Debited,
+            TransferEvent::Completed => TransferStatus::Completed,
+            TransferEvent::Failed { reason } => TransferStatus::Failed { reason },
+            TransferEvent::CompensationStarted { reason } => {
+                TransferStatus::Compensating { reason }
+            }
+            TransferEvent::Compensated => match std::mem::take(&mut self.status) {
+                TransferStatus::Compensating { reason }
+                | TransferStatus::CompensationFailed { reason, .. } => {
+                    TransferStatus::Compensated { reason }
+                }
+                status => status,
+            },
+            TransferEvent::RefundRetried { .. } => std::mem::take(&mut self.status),
+            TransferEvent::CompensationFailed { error } => match std::mem::take(&mut self.status) {
+                TransferStatus::Compensating { reason } => {
+                    TransferStatus::CompensationFailed { reason, error }
+                }
+                status => status,
+            },
+        };
+    }
+}
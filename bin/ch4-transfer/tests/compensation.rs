@@ -0,0 +1,158 @@
+use ch4_transfer::account::{Account, AccountCommand, AccountError, AccountEvent};
+use ch4_transfer::transfer::{Transfer, TransferCommand, TransferEvent, TransferStatus};
+use eventsourcing::testing::AggregateTestFixture;
+
+fn debited(transfer_id: &str, amount: i64) -> [AccountEvent; 2] {
+    [
+        AccountEvent::Deposited { amount: 100 },
+        AccountEvent::Debited {
+            transfer_id: transfer_id.to_string(),
+            amount,
+        },
+    ]
+}
+
+fn refund(transfer_id: &str, amount: i64) -> AccountCommand {
+    AccountCommand::Refund {
+        transfer_id: transfer_id.to_string(),
+        amount,
+    }
+}
+
+#[test]
+fn retried_refunds_are_applied_once() {
+    let outcome = AggregateTestFixture::<Account>::new()
+        .given(debited("t1", 30))
+        .when(refund("t1", 30))
+        .then_events([AccountEvent::Refunded {
+            transfer_id: "t1".to_string(),
+            amount: 30,
+        }]);
+    assert_eq!(outcome.aggregate().balance(), 100);
+    assert_eq!(outcome.aggregate().refundable("t1"), None);
+
+    AggregateTestFixture::<Account>::new()
+        .given(debited("t1", 30))
+        .given([AccountEvent::Refunded {
+            transfer_id: "t1".to_string(),
+            amount: 30,
+        }])
+        .when(refund("t1", 30))
+        .then_events([]);
+}
+
+#[test]
+fn only_recorded_debits_are_refunded() {
+    AggregateTestFixture::<Account>::new()
+        .given([
+            AccountEvent::Deposited { amount: 100 },
+            AccountEvent::DebitRejected {
+                transfer_id: "t1".to_string(),
+                reason: "frozen".to_string(),
+            },
+        ])
+        .when(refund("t1", 30))
+        .then_error(AccountError::NothingToRefund {
+            transfer_id: "t1".to_string(),
+        });
+    AggregateTestFixture::<Account>::new()
+        .given(debited("t1", 30))
+        .when(refund("t1", 50))
+        .then_error(AccountError::RefundMismatch {
+            transfer_id: "t1".to_string(),
+            debited: 30,
+            requested: 50,
+        });
+}
+
+#[test]
+fn compensated_is_recorded_once() {
+    let compensated = [
+        TransferEvent::Started {
+            from: "A".to_string(),
+            to: "B".to_string(),
+            amount: 30,
+        },
+        TransferEvent::Debited,
+        TransferEvent::CompensationStarted {
+            reason: "account is frozen".to_string(),
+        },
+        TransferEvent::Compensated,
+    ];
+    let outcome = AggregateTestFixture::<Transfer>::new()
+        .given(compensated)
+        .when(TransferCommand::RecordRefund)
+        .then_events([]);
+    assert_eq!(
+        outcome.aggregate().status,
+        TransferStatus::Compensated {
+            reason: "account is frozen".to_string()
+        }
+    );
+}
+
+#[test]
+fn failed_compensations_can_still_be_compensated() {
+    let compensating = [
+        TransferEvent::Started {
+            from: "A".to_string(),
+            to: "B".to_string(),
+            amount: 30,
+        },
+        TransferEvent::Debited,
+        TransferEvent::CompensationStarted {
+            reason: "account is frozen".to_string(),
+        },
+    ];
+    let outcome = AggregateTestFixture::<Transfer>::new()
+        .given(compensating)
+        .when(TransferCommand::RecordRefundFailed {
+            error: "timeout".to_string(),
+        })
+        .then_events([TransferEvent::CompensationFailed {
+            error: "timeout".to_string(),
+        }]);
+    assert_eq!(
+        outcome.aggregate().status,
+        TransferStatus::CompensationFailed {
+            reason: "account is frozen".to_string(),
+            error: "timeout".to_string(),
+        }
+    );
+    assert!(outcome.aggregate().status.is_terminal());
+
+    AggregateTestFixture::<Transfer>::with_aggregate(outcome.aggregate().clone())
+        .when(TransferCommand::RecordRefund)
+        .then_events([TransferEvent::Compensated]);
+}
+
+#[test]
+fn refund_retries_are_recorded_while_compensating() {
+    let compensating = [
+        TransferEvent::Started {
+            from: "A".to_string(),
+            to: "B".to_string(),
+            amount: 30,
+        },
+        TransferEvent::Debited,
+        TransferEvent::CompensationStarted {
+            reason: "account is frozen".to_string(),
+        },
+    ];
+    let outcome = AggregateTestFixture::<Transfer>::new()
+        .given(compensating)
+        .when(TransferCommand::RetryRefund { attempt: 2 })
+        .then_events([TransferEvent::RefundRetried { attempt: 2 }]);
+    assert_eq!(
+        outcome.aggregate().status,
+        TransferStatus::Compensating {
+            reason: "account is frozen".to_string()
+        }
+    );
+
+    // A retry scheduled before the refund went through is ignored.
+    AggregateTestFixture::<Transfer>::with_aggregate(outcome.aggregate().clone())
+        .given([TransferEvent::Compensated])
+        .when(TransferCommand::RetryRefund { attempt: 3 })
+        .then_events([]);
+}
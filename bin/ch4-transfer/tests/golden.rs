@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use ch4_transfer::account::{Account, AccountCommand, AccountEvent};
+use ch4_transfer::transfer::{Transfer, TransferCommand, TransferEvent, TransferStatus};
+use eventsourcing::envelope::Upcasters;
+use eventsourcing::testing::{decode_fixture, AggregateTestFixture};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn account_events(name: &str) -> Vec<AccountEvent> {
+    decode_fixture(fixture(name), &Upcasters::new())
+}
+
+fn transfer_events(name: &str) -> Vec<TransferEvent> {
+    decode_fixture(fixture(name), &Upcasters::new())
+}
+
+#[test]
+fn events_of_every_revision_still_decode() {
+    // Written before events were wrapped in envelopes.
+    assert_eq!(
+        account_events("account-events-bare.jsonl"),
+        vec![
+            AccountEvent::Deposited { amount: 100 },
+            AccountEvent::Debited {
+                transfer_id: "T1".to_string(),
+                amount: 30,
+            },
+        ]
+    );
+    assert_eq!(
+        transfer_events("transfer-events-bare.jsonl"),
+        vec![
+            TransferEvent::Started {
+                from: "A".to_string(),
+                to: "B".to_string(),
+                amount: 30,
+            },
+            TransferEvent::Debited,
+        ]
+    );
+    // Version 1 envelopes with correlation ids.
+    assert_eq!(
+        account_events("account-events-v1.jsonl"),
+        vec![
+            AccountEvent::Frozen,
+            AccountEvent::CreditRejected {
+                transfer_id: "T1".to_string(),
+                reason: "account is frozen".to_string(),
+            },
+            AccountEvent::Refunded {
+                transfer_id: "T1".to_string(),
+                amount: 30,
+            },
+        ]
+    );
+    assert_eq!(
+        transfer_events("transfer-events-v1.jsonl"),
+        vec![
+            TransferEvent::CompensationStarted {
+                reason: "account is frozen".to_string(),
+            },
+            TransferEvent::Compensated,
+        ]
+    );
+}
+
+#[test]
+fn debits_logged_before_refund_tracking_can_be_refunded() {
+    AggregateTestFixture::<Account>::new()
+        .given(account_events("account-events-bare.jsonl"))
+        .when(AccountCommand::Refund {
+            transfer_id: "T1".to_string(),
+            amount: 30,
+        })
+        .then_events([AccountEvent::Refunded {
+            transfer_id: "T1".to_string(),
+            amount: 30,
+        }]);
+}
+
+#[test]
+fn golden_transfer_replays_to_compensated() {
+    // A refund redelivered to the replayed, finished transfer is ignored.
+    let outcome = AggregateTestFixture::<Transfer>::new()
+        .given(transfer_events("transfer-events-bare.jsonl"))
+        .given(transfer_events("transfer-events-v1.jsonl"))
+        .when(TransferCommand::RecordRefund)
+        .then_events([]);
+    assert_eq!(
+        outcome.aggregate().status,
+        TransferStatus::Compensated {
+            reason: "account is frozen".to_string()
+        }
+    );
+}
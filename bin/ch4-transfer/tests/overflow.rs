@@ -0,0 +1,41 @@
+use ch4_transfer::account::{Account, AccountCommand, AccountError, AccountEvent};
+use eventsourcing::testing::AggregateTestFixture;
+
+const NEAR_MAX: i64 = i64::MAX - 10;
+
+#[test]
+fn deposits_out_of_range_are_rejected() {
+    AggregateTestFixture::<Account>::new()
+        .given([AccountEvent::Deposited { amount: NEAR_MAX }])
+        .when(AccountCommand::Deposit { amount: 20 })
+        .then_error(AccountError::BalanceOutOfRange {
+            amount: 20,
+            balance: NEAR_MAX,
+        });
+}
+
+#[test]
+fn credits_out_of_range_are_recorded_as_rejected() {
+    AggregateTestFixture::<Account>::new()
+        .given([AccountEvent::Deposited { amount: NEAR_MAX }])
+        .when(AccountCommand::Credit {
+            transfer_id: "t1".to_string(),
+            amount: 20,
+        })
+        .then_events([AccountEvent::CreditRejected {
+            transfer_id: "t1".to_string(),
+            reason: "amount 20 takes the balance of 9223372036854775797 out of range".to_string(),
+        }]);
+}
+
+#[test]
+fn events_recorded_out_of_range_saturate() {
+    let outcome = AggregateTestFixture::<Account>::new()
+        .given([
+            AccountEvent::Deposited { amount: NEAR_MAX },
+            AccountEvent::Deposited { amount: 20 },
+        ])
+        .when(AccountCommand::Freeze)
+        .then_events([AccountEvent::Frozen]);
+    assert_eq!(outcome.aggregate().balance(), i64::MAX);
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ch4_transfer::account::{self, Account, AccountCommand, AccountEvent};
+use ch4_transfer::saga::TransferSaga;
+use ch4_transfer::transfer::{self, Transfer, TransferCommand, TransferEvent, TransferStatus};
+use eventsourcing::bus::{stream_type, EventBus, EventHandler, EventSubscriber};
+use eventsourcing::envelope;
+use eventsourcing::snapshot::InMemorySnapshotStore;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::{AggregateActor, AggregateRepository};
+use ractor::Actor;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn wait_for_terminal(
+    transfers: &AggregateRepository<Transfer>,
+    transfer_id: &str,
+) -> Transfer {
+    for _ in 0..100 {
+        let transfer = transfers
+            .get_state(&transfer::stream_id(transfer_id), TIMEOUT)
+            .await
+            .unwrap();
+        if transfer.status.is_terminal() {
+            return transfer;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("transfer {} did not finish", transfer_id);
+}
+
+#[tokio::test]
+async fn saga_completes_fails_and_compensates_transfers() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let accounts = AggregateRepository::new(
+        AggregateActor::<Account>::new(Arc::clone(&store))
+            .with_event_bus(EventBus::for_store(&store)),
+    );
+    let transfers = AggregateRepository::new(
+        AggregateActor::<Transfer>::new(Arc::clone(&store))
+            .with_event_bus(EventBus::for_store(&store)),
+    );
+    Actor::spawn(
+        None,
+        EventSubscriber::<TransferSaga>::new(
+            Arc::clone(&store),
+            EventBus::for_store(&store),
+            &[account::STREAM_TYPE, transfer::STREAM_TYPE],
+        )
+        .with_checkpoints(Arc::new(InMemorySnapshotStore::new()), "transfer-saga"),
+        TransferSaga::new(accounts.clone(), transfers.clone()),
+    )
+    .await
+    .unwrap();
+
+    accounts
+        .execute_with_reply(
+            &account::stream_id("saga-source"),
+            AccountCommand::Deposit { amount: 100 },
+            TIMEOUT,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    accounts
+        .execute_with_reply(
+            &account::stream_id("saga-frozen"),
+            AccountCommand::Freeze,
+            TIMEOUT,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    for (transfer_id, to, amount) in [
+        ("saga-ok", "saga-target", 30),
+        ("saga-overdraft", "saga-target", 500),
+        ("saga-refund", "saga-frozen", 20),
+    ] {
+        transfers
+            .execute_with_reply(
+                &transfer::stream_id(transfer_id),
+                TransferCommand::Start {
+                    from: "saga-source".to_string(),
+                    to: to.to_string(),
+                    amount,
+                },
+                TIMEOUT,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    assert_eq!(
+        wait_for_terminal(&transfers, "saga-ok").await.status,
+        TransferStatus::Completed
+    );
+    assert!(matches!(
+        wait_for_terminal(&transfers, "saga-overdraft").await.status,
+        TransferStatus::Failed { .. }
+    ));
+    assert!(matches!(
+        wait_for_terminal(&transfers, "saga-refund").await.status,
+        TransferStatus::Compensated { .. }
+    ));
+
+    let balance = |account_number: &'static str| {
+        let accounts = accounts.clone();
+        async move {
+            accounts
+                .get_state(&account::stream_id(account_number), TIMEOUT)
+                .await
+                .unwrap()
+                .balance()
+        }
+    };
+    assert_eq!(balance("saga-source").await, 70);
+    assert_eq!(balance("saga-target").await, 30);
+    assert_eq!(balance("saga-frozen").await, 0);
+
+    // Every event of a transfer, on either aggregate, shares the correlation
+    // id of the command starting it.
+    let refund_events: Vec<_> = store
+        .read_all(0, 1000)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|event| {
+            if event.stream_id == transfer::stream_id("saga-refund") {
+                return true;
+            }
+            if stream_type(&event.stream_id) == account::STREAM_TYPE {
+                return match event.decode::<AccountEvent>().unwrap() {
+                    AccountEvent::Debited { transfer_id, .. }
+                    | AccountEvent::DebitRejected { transfer_id, .. }
+                    | AccountEvent::Credited { transfer_id, .. }
+                    | AccountEvent::CreditRejected { transfer_id, .. }
+                    | AccountEvent::Refunded { transfer_id, .. } => transfer_id == "saga-refund",
+                    AccountEvent::Deposited { .. } | AccountEvent::Frozen => false,
+                };
+            }
+            false
+        })
+        .collect();
+    assert!(refund_events.len() > 4);
+    let correlation_id = envelope::correlation_id(&refund_events[0].payload);
+    assert!(correlation_id.is_some());
+    for event in &refund_events[1..] {
+        assert_eq!(envelope::correlation_id(&event.payload), correlation_id);
+    }
+}
+
+#[tokio::test]
+async fn rejected_refunds_fail_the_compensation() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let accounts = AggregateRepository::new(AggregateActor::<Account>::new(Arc::clone(&store)));
+    let transfers = AggregateRepository::new(AggregateActor::<Transfer>::new(Arc::clone(&store)));
+    // The source account never recorded the debit, so it rejects the refund.
+    let events = [
+        TransferEvent::Started {
+            from: "parked-source".to_string(),
+            to: "parked-target".to_string(),
+            amount: 20,
+        },
+        TransferEvent::Debited,
+        TransferEvent::CompensationStarted {
+            reason: "account is frozen".to_string(),
+        },
+    ];
+    store
+        .append(
+            &transfer::stream_id("parked"),
+            ExpectedVersion::NoStream,
+            events
+                .iter()
+                .map(|event| serde_json::to_value(event).unwrap())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let mut saga = TransferSaga::new(accounts, transfers.clone());
+    let compensation_started = store
+        .read_stream(&transfer::stream_id("parked"), 2)
+        .await
+        .unwrap();
+    saga.handle_event(&compensation_started[0]).await.unwrap();
+
+    let transfer = wait_for_terminal(&transfers, "parked").await;
+    assert!(matches!(
+        transfer.status,
+        TransferStatus::CompensationFailed { error, .. } if error.contains("debited nothing")
+    ));
+}
@@ -0,0 +1,139 @@
+//! Event feed for consumers without a broker.
+//!
+//! `GET /events?after=POSITION&limit=N&wait_secs=S` replies up to `N` events
+//! of the committed log after `POSITION`, in order, with a `next` link (also
+//! in the `Link` header) to fetch the following page. When there are no newer
+//! events, the request is held for up to `S` seconds until some are
+//! committed. A consumer that stores the position of the last event it
+//! processed and resumes from it gets at-least-once, ordered delivery.
+//!
+//! `stream_type=account,transfer` restricts the feed to streams of those
+//! types. Without it, the feed has every stream but the internal ones, see
+//! [`INTERNAL_STREAM_TYPES`], which are only served when asked for by type.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Query, State};
+use axum::http::header::LINK;
+use axum::response::{AppendHeaders, IntoResponse};
+use axum::routing::get;
+use axum::{Json, Router};
+use eventsourcing::bus::{stream_type, INTERNAL_STREAM_TYPES};
+use eventsourcing::store::{EventStore, RecordedEvent};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::ApiError;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+pub const MAX_WAIT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn router(store: Arc<dyn EventStore>) -> Router {
+    Router::new()
+        .route("/events", get(events))
+        .with_state(store)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FeedQuery {
+    #[serde(default)]
+    pub after: u64,
+    pub limit: Option<usize>,
+    pub wait_secs: Option<u64>,
+    /// Comma-separated stream types to serve.
+    pub stream_type: Option<String>,
+}
+
+impl FeedQuery {
+    fn serves(&self, event: &RecordedEvent) -> bool {
+        let event_type = stream_type(&event.stream_id);
+        match &self.stream_type {
+            Some(stream_types) => stream_types.split(',').any(|t| t == event_type),
+            None => !INTERNAL_STREAM_TYPES.contains(&event_type),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedPage {
+    pub events: Vec<RecordedEvent>,
+    /// `self` and `next` links.
+    pub links: Vec<FeedLink>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedLink {
+    pub rel: String,
+    pub href: String,
+}
+
+impl FeedPage {
+    pub fn next(&self) -> Option<&str> {
+        self.links
+            .iter()
+            .find(|link| link.rel == "next")
+            .map(|link| link.href.as_str())
+    }
+}
+
+async fn events(
+    State(store): State<Arc<dyn EventStore>>,
+    Query(query): Query<FeedQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let wait = Duration::from_secs(query.wait_secs.unwrap_or_default()).min(MAX_WAIT);
+    let deadline = Instant::now() + wait;
+    // Position of the last event read, served or not.
+    let mut next_after = query.after;
+    let events = loop {
+        let read = store.read_all(next_after, limit).await?;
+        let Some(last) = read.last() else {
+            if Instant::now() >= deadline {
+                break Vec::new();
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            continue;
+        };
+        next_after = last.position;
+        let events: Vec<RecordedEvent> = read
+            .into_iter()
+            .filter(|event| query.serves(event))
+            .collect();
+        if !events.is_empty() {
+            break events;
+        }
+    };
+
+    let next = page_href(next_after, limit, &query);
+    let page = FeedPage {
+        events,
+        links: vec![
+            FeedLink {
+                rel: "self".to_string(),
+                href: page_href(query.after, limit, &query),
+            },
+            FeedLink {
+                rel: "next".to_string(),
+                href: next.clone(),
+            },
+        ],
+    };
+    Ok((
+        AppendHeaders([(LINK, format!("<{}>; rel=\"next\"", next))]),
+        Json(page),
+    ))
+}
+
+fn page_href(after: u64, limit: usize, query: &FeedQuery) -> String {
+    let mut params = form_urlencoded::Serializer::new(String::new());
+    params
+        .append_pair("after", &after.to_string())
+        .append_pair("limit", &limit.to_string());
+    if let Some(stream_types) = &query.stream_type {
+        params.append_pair("stream_type", stream_types);
+    }
+    format!("/events?{}", params.finish())
+}
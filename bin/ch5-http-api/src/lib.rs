@@ -0,0 +1,155 @@
+#[cfg(feature = "feed")]
+pub mod feed;
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ch2_account_balance::{
+    AccountBalance, AccountBalanceCommand, AccountBalanceError, AccountBalanceRejection,
+};
+use eventsourcing::store::{EventStore, StoreError};
+use serde::{Deserialize, Serialize};
+
+/// Routes translating HTTP requests into commands and queries against the
+/// account actors of chapter 2.
+///
+/// - `POST /accounts/{id}/deposit` and `POST /accounts/{id}/withdraw` take
+///   `{"value": 100}` and reply the new balance, or `422` with the rejection.
+/// - `GET /accounts/{id}/balance` replies the current balance.
+///
+/// The event log is served separately by `feed::router`, see [`app`].
+pub fn router(accounts: AccountBalance) -> Router {
+    Router::new()
+        .route("/accounts/{id}/deposit", post(deposit))
+        .route("/accounts/{id}/withdraw", post(withdraw))
+        .route("/accounts/{id}/balance", get(balance))
+        .with_state(accounts)
+}
+
+/// The account routes of [`router`], and the event log feed when built with
+/// the `feed` feature.
+pub fn app(accounts: AccountBalance, store: Arc<dyn EventStore>) -> Router {
+    let app = router(accounts);
+    #[cfg(feature = "feed")]
+    let app = app.merge(feed::router(store));
+    #[cfg(not(feature = "feed"))]
+    {
+        let _ = store;
+        tracing::warn!("built without the feed, /events is not served");
+    }
+    app
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmountRequest {
+    pub value: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceResponse {
+    pub account_number: String,
+    pub balance: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Why a request failed, mapped to a status code.
+#[derive(Debug)]
+pub enum ApiError {
+    Rejected(AccountBalanceRejection),
+    Account(AccountBalanceError),
+    Store(StoreError),
+}
+
+impl From<AccountBalanceRejection> for ApiError {
+    fn from(rejection: AccountBalanceRejection) -> Self {
+        ApiError::Rejected(rejection)
+    }
+}
+
+impl From<AccountBalanceError> for ApiError {
+    fn from(err: AccountBalanceError) -> Self {
+        ApiError::Account(err)
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(err: StoreError) -> Self {
+        ApiError::Store(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::Rejected(rejection) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, rejection.to_string())
+            }
+            ApiError::Account(
+                err @ (AccountBalanceError::QueryTimeout { .. }
+                | AccountBalanceError::CommandTimeout { .. }),
+            ) => (StatusCode::GATEWAY_TIMEOUT, err.to_string()),
+            ApiError::Account(err) => {
+                tracing::error!("account request failed: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
+            ApiError::Store(err) => {
+                tracing::error!("event store request failed: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
+        };
+        (status, Json(ErrorResponse { error })).into_response()
+    }
+}
+
+async fn deposit(
+    State(accounts): State<AccountBalance>,
+    Path(account_number): Path<String>,
+    Json(request): Json<AmountRequest>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    let command = AccountBalanceCommand::Deposit {
+        value: request.value,
+    };
+    execute(&accounts, account_number, command).await
+}
+
+async fn withdraw(
+    State(accounts): State<AccountBalance>,
+    Path(account_number): Path<String>,
+    Json(request): Json<AmountRequest>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    let command = AccountBalanceCommand::Withdraw {
+        value: request.value,
+    };
+    execute(&accounts, account_number, command).await
+}
+
+async fn balance(
+    State(accounts): State<AccountBalance>,
+    Path(account_number): Path<String>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    let balance = accounts.balance(&account_number).await?;
+    Ok(Json(BalanceResponse {
+        account_number,
+        balance,
+    }))
+}
+
+async fn execute(
+    accounts: &AccountBalance,
+    account_number: String,
+    command: AccountBalanceCommand,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    let balance = accounts.execute(&account_number, command).await??;
+    Ok(Json(BalanceResponse {
+        account_number,
+        balance,
+    }))
+}
@@ -0,0 +1,52 @@
+use ch2_account_balance::{AccountBalance, AccountLimits};
+use eventsourcing::selftest;
+use eventsourcing::store::{EventStore, InMemoryEventStore, JsonLinesEventStore};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:3000";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Usage: `ch5-http-api [--self-test] [ADDR] [EVENTS.jsonl]`, then e.g.
+///
+/// ```text
+/// curl -X POST -H 'content-type: application/json' -d '{"value":100}' \
+///     localhost:3000/accounts/ACCOUNT1/deposit
+/// curl localhost:3000/accounts/ACCOUNT1/balance
+/// curl 'localhost:3000/events?after=0&wait_secs=10'  # with the `feed` feature
+/// ```
+async fn inner() -> anyhow::Result<()> {
+    let _logging = local_logging::init()?;
+    ch2_account_balance::upcasters().install();
+
+    let mut args = std::env::args().skip(1).filter(|arg| arg != selftest::FLAG);
+    let addr = args.next().unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    let store: Arc<dyn EventStore> = match args.next() {
+        Some(path) => Arc::new(JsonLinesEventStore::open(path)?),
+        None => Arc::new(InMemoryEventStore::new()),
+    };
+    if selftest::requested() {
+        return Ok(selftest::run_and_print(&store).await?);
+    }
+    let accounts = AccountBalance::new(Arc::clone(&store)).with_limits(AccountLimits {
+        overdraft_limit: 50,
+        max_transaction: Some(500),
+    });
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("listening on {}", listener.local_addr()?);
+    let app = ch5_http_api::app(accounts, store);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
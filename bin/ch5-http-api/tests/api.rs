@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use ch2_account_balance::{AccountBalance, AccountLimits};
+use ch5_http_api::{router, BalanceResponse, ErrorResponse};
+use eventsourcing::store::InMemoryEventStore;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let accounts =
+        AccountBalance::new(Arc::new(InMemoryEventStore::new())).with_limits(AccountLimits {
+            overdraft_limit: 0,
+            max_transaction: None,
+        });
+    router(accounts)
+}
+
+async fn send<T: DeserializeOwned>(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, T) {
+    let request = Request::builder().method(method).uri(uri);
+    let request = match body {
+        Some(body) => request
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn commands_update_the_balance() {
+    let app = app();
+
+    let (status, body) = send::<BalanceResponse>(
+        &app,
+        Method::POST,
+        "/accounts/HTTP1/deposit",
+        Some(json!({ "value": 100 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.balance, 100);
+
+    let (status, body) = send::<BalanceResponse>(
+        &app,
+        Method::POST,
+        "/accounts/HTTP1/withdraw",
+        Some(json!({ "value": 30 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.balance, 70);
+
+    let (status, body) =
+        send::<BalanceResponse>(&app, Method::GET, "/accounts/HTTP1/balance", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        BalanceResponse {
+            account_number: "HTTP1".to_string(),
+            balance: 70,
+        }
+    );
+}
+
+#[tokio::test]
+async fn rejections_are_unprocessable() {
+    let app = app();
+
+    let (status, body) = send::<ErrorResponse>(
+        &app,
+        Method::POST,
+        "/accounts/HTTP2/withdraw",
+        Some(json!({ "value": 10 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body.error.contains("overdraft limit"), "{}", body.error);
+
+    let (_, body) =
+        send::<BalanceResponse>(&app, Method::GET, "/accounts/HTTP2/balance", None).await;
+    assert_eq!(body.balance, 0);
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "feed")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::header::LINK;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use ch5_http_api::feed::{self, FeedPage};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn get(app: &Router, uri: &str) -> (String, FeedPage) {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let link = response.headers()[LINK].to_str().unwrap().to_string();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (link, serde_json::from_slice(&bytes).unwrap())
+}
+
+async fn append(store: &InMemoryEventStore, stream_id: &str, count: u64) {
+    store
+        .append(
+            stream_id,
+            ExpectedVersion::Any,
+            (0..count).map(|n| json!({ "n": n })).collect(),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn pages_follow_the_log_in_order() {
+    let store = Arc::new(InMemoryEventStore::new());
+    append(&store, "account/A", 2).await;
+    append(&store, "account/B", 1).await;
+    let app = feed::router(store);
+
+    let (link, page) = get(&app, "/events?limit=2").await;
+    let positions: Vec<u64> = page.events.iter().map(|event| event.position).collect();
+    assert_eq!(positions, [1, 2]);
+    assert_eq!(page.next(), Some("/events?after=2&limit=2"));
+    assert_eq!(link, "</events?after=2&limit=2>; rel=\"next\"");
+
+    let (_, page) = get(&app, page.next().unwrap()).await;
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].stream_id, "account/B");
+    assert_eq!(page.next(), Some("/events?after=3&limit=2"));
+
+    // Caught up: the next link stays put.
+    let (_, page) = get(&app, page.next().unwrap()).await;
+    assert!(page.events.is_empty());
+    assert_eq!(page.next(), Some("/events?after=3&limit=2"));
+}
+
+#[tokio::test]
+async fn caught_up_requests_wait_for_new_events() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let app = feed::router(store.clone());
+
+    let appender = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        append(&store, "account/A", 1).await;
+    });
+    let (_, page) = get(&app, "/events?after=0&wait_secs=5").await;
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].position, 1);
+    appender.await.unwrap();
+}
+
+#[tokio::test]
+async fn internal_streams_are_only_served_by_type() {
+    let store = Arc::new(InMemoryEventStore::new());
+    append(&store, "account/A", 1).await;
+    append(&store, "audit/account/A", 1).await;
+    append(&store, "ops/scheduler", 1).await;
+    append(&store, "schedule/fees", 1).await;
+    append(&store, "selftest/probe", 1).await;
+    append(&store, "transfer/T", 1).await;
+    let app = feed::router(store);
+    let stream_ids = |page: &FeedPage| {
+        page.events
+            .iter()
+            .map(|event| event.stream_id.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let (_, page) = get(&app, "/events").await;
+    assert_eq!(stream_ids(&page), ["account/A", "transfer/T"]);
+
+    // Pages of skipped events still move the position forward.
+    let (_, page) = get(&app, "/events?after=1&limit=2").await;
+    assert_eq!(stream_ids(&page), ["transfer/T"]);
+    assert_eq!(page.next(), Some("/events?after=6&limit=2"));
+
+    let (link, page) = get(&app, "/events?stream_type=audit,transfer&limit=1").await;
+    assert_eq!(stream_ids(&page), ["audit/account/A"]);
+    assert_eq!(
+        link,
+        "</events?after=2&limit=1&stream_type=audit%2Ctransfer>; rel=\"next\""
+    );
+    let (_, page) = get(&app, page.next().unwrap()).await;
+    assert_eq!(stream_ids(&page), ["transfer/T"]);
+}
+
+#[tokio::test]
+async fn next_links_escape_the_stream_type() {
+    let store = Arc::new(InMemoryEventStore::new());
+    append(&store, "odd&type=x/A", 2).await;
+    append(&store, "odd/B", 1).await;
+    let app = feed::router(store);
+
+    let (link, page) = get(&app, "/events?stream_type=odd%26type%3Dx&limit=1").await;
+    assert_eq!(page.events[0].stream_id, "odd&type=x/A");
+    assert_eq!(
+        link,
+        "</events?after=1&limit=1&stream_type=odd%26type%3Dx>; rel=\"next\""
+    );
+    let (_, page) = get(&app, page.next().unwrap()).await;
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].position, 2);
+    let (_, page) = get(&app, page.next().unwrap()).await;
+    assert!(page.events.is_empty());
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use eventsourcing::bus::EventHandler;
+use eventsourcing::envelope::Correlation;
+use eventsourcing::projection::Projector;
+use eventsourcing::store::RecordedEvent;
+use eventsourcing::{Aggregate, AggregateMessage, AggregateRepository};
+use ractor::{async_trait, ActorProcessingErr};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const STREAM_TYPE: &str = "combatant";
+pub const MAX_HEALTH: u32 = 100;
+pub const RESPAWN_DELAY: Duration = Duration::from_millis(200);
+
+pub fn stream_id(name: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, name)
+}
+
+/// An entity of the arena, named by its stream id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Combatant {
+    health: u32,
+}
+
+impl Default for Combatant {
+    fn default() -> Self {
+        Self { health: MAX_HEALTH }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CombatCommand {
+    Attack { attacker: String, damage: u32 },
+    Heal { amount: u32 },
+    Respawn,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CombatEvent {
+    DidTakeDamage { attacker: String, damage: u32 },
+    DidHeal { amount: u32 },
+    Died { killer: String },
+    Respawned { health: u32 },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CombatError {
+    #[error("combatant is already dead")]
+    AlreadyDead,
+    #[error("combatant is still alive")]
+    StillAlive,
+}
+
+impl Combatant {
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+}
+
+impl Aggregate for Combatant {
+    type Command = CombatCommand;
+    type Event = CombatEvent;
+    type Error = CombatError;
+
+    fn handle_command(&self, command: CombatCommand) -> Result<Vec<CombatEvent>, CombatError> {
+        match command {
+            CombatCommand::Attack { .. } | CombatCommand::Heal { .. } if self.health == 0 => {
+                Err(CombatError::AlreadyDead)
+            }
+            CombatCommand::Attack { attacker, damage } => {
+                // Overkill is not recorded, the event carries the health lost.
+                let mut events = vec![CombatEvent::DidTakeDamage {
+                    attacker: attacker.clone(),
+                    damage: damage.min(self.health),
+                }];
+                if damage >= self.health {
+                    events.push(CombatEvent::Died { killer: attacker });
+                }
+                Ok(events)
+            }
+            CombatCommand::Heal { amount } => Ok(vec![CombatEvent::DidHeal {
+                amount: amount.min(MAX_HEALTH - self.health),
+            }]),
+            CombatCommand::Respawn if self.health > 0 => Err(CombatError::StillAlive),
+            CombatCommand::Respawn => Ok(vec![CombatEvent::Respawned { health: MAX_HEALTH }]),
+        }
+    }
+
+    fn apply_event(&mut self, event: CombatEvent) {
+        match event {
+            CombatEvent::DidTakeDamage { damage, .. } => {
+                self.health = self.health.saturating_sub(damage)
+            }
+            CombatEvent::DidHeal { amount } => {
+                self.health = self.health.saturating_add(amount).min(MAX_HEALTH)
+            }
+            CombatEvent::Died { .. } => self.health = 0,
+            CombatEvent::Respawned { health } => self.health = health,
+        }
+    }
+}
+
+/// Read model ranking combatants by kills, then deaths, then damage dealt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    standings: BTreeMap<String, Standing>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub name: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: u64,
+}
+
+impl Leaderboard {
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<_> = self.standings.values().cloned().collect();
+        standings.sort_by(|a, b| {
+            b.kills
+                .cmp(&a.kills)
+                .then(a.deaths.cmp(&b.deaths))
+                .then(b.damage_dealt.cmp(&a.damage_dealt))
+        });
+        standings
+    }
+
+    fn standing(&mut self, name: &str) -> &mut Standing {
+        self.standings
+            .entry(name.to_string())
+            .or_insert_with(|| Standing {
+                name: name.to_string(),
+                ..Default::default()
+            })
+    }
+}
+
+impl Projector for Leaderboard {
+    const NAME: &'static str = "leaderboard";
+}
+
+#[async_trait]
+impl EventHandler for Leaderboard {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        let Some((STREAM_TYPE, name)) = event.stream_id.split_once('/') else {
+            return Ok(());
+        };
+        let Some(payload) = event.decode_known()? else {
+            return Ok(());
+        };
+        match payload {
+            CombatEvent::DidTakeDamage { attacker, damage } => {
+                let standing = self.standing(&attacker);
+                standing.damage_dealt = standing.damage_dealt.saturating_add(u64::from(damage));
+            }
+            CombatEvent::Died { killer } => {
+                let standing = self.standing(&killer);
+                standing.kills = standing.kills.saturating_add(1);
+                let standing = self.standing(name);
+                standing.deaths = standing.deaths.saturating_add(1);
+            }
+            CombatEvent::DidHeal { .. } | CombatEvent::Respawned { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Process manager that reacts to `Died` by sending a `Respawn` command to
+/// the combatant after [`RESPAWN_DELAY`].
+///
+/// It runs in an [`EventSubscriber`](eventsourcing::bus::EventSubscriber) on
+/// the combatant streams. The command continues the correlation of the
+/// death, and is rejected by a combatant already alive again, e.g. when the
+/// log is replayed without a checkpoint.
+#[derive(Clone)]
+pub struct RespawnManager {
+    combatants: AggregateRepository<Combatant>,
+}
+
+impl RespawnManager {
+    pub fn new(combatants: AggregateRepository<Combatant>) -> Self {
+        Self { combatants }
+    }
+}
+
+#[async_trait]
+impl EventHandler for RespawnManager {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        let Some((STREAM_TYPE, _)) = event.stream_id.split_once('/') else {
+            return Ok(());
+        };
+        let Some(CombatEvent::Died { .. }) = event.decode_known()? else {
+            return Ok(());
+        };
+        let combatant = self.combatants.get(&event.stream_id).await?;
+        let correlation = Correlation::caused_by(event);
+        tracing::info!("respawning {} in {:?}", event.stream_id, RESPAWN_DELAY);
+        combatant.send_after(RESPAWN_DELAY, move || AggregateMessage::ExecuteFrom {
+            source: Some("respawn-manager".to_string()),
+            correlation: Some(correlation),
+            command_id: None,
+            command: CombatCommand::Respawn,
+            reply_port: None,
+        });
+        Ok(())
+    }
+}
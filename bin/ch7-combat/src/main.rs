@@ -0,0 +1,74 @@
+use anyhow::Context;
+use ch7_combat::{
+    stream_id, CombatCommand, Combatant, Leaderboard, RespawnManager, RESPAWN_DELAY, STREAM_TYPE,
+};
+use eventsourcing::app::EmbeddedApp;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+async fn inner() -> anyhow::Result<()> {
+    let _logging = local_logging::init()?;
+
+    let builder = EmbeddedApp::in_memory().with_aggregate::<Combatant>();
+    let combatants = builder
+        .repository::<Combatant>()
+        .context("combatants are not registered")?;
+    let app = builder
+        .with_projection::<Leaderboard>()
+        .with_subscriber(
+            "respawn-manager",
+            &[STREAM_TYPE],
+            RespawnManager::new(combatants.clone()),
+        )
+        .build()
+        .await?;
+
+    let attack = |attacker: &str, damage| CombatCommand::Attack {
+        attacker: attacker.to_string(),
+        damage,
+    };
+    for (name, command) in [
+        ("bob", attack("alice", 60)),
+        ("alice", attack("bob", 30)),
+        ("bob", CombatCommand::Heal { amount: 20 }),
+        ("bob", attack("alice", 70)),
+        // Rejected: bob is dead until the respawn manager brings him back.
+        ("bob", attack("alice", 10)),
+    ] {
+        if let Err(err) = combatants
+            .execute_with_reply(&stream_id(name), command.clone(), RPC_TIMEOUT)
+            .await?
+        {
+            println!("{:?} on {} rejected: {}", command, name, err);
+        }
+    }
+
+    tokio::time::sleep(RESPAWN_DELAY * 2).await;
+
+    for name in ["alice", "bob"] {
+        let combatant = combatants.get_state(&stream_id(name), RPC_TIMEOUT).await?;
+        println!("health of {}: {}", name, combatant.health());
+    }
+    for standing in app.query::<Leaderboard>().await?.standings() {
+        println!(
+            "{}: {} kills, {} deaths, {} damage dealt",
+            standing.name, standing.kills, standing.deaths, standing.damage_dealt
+        );
+    }
+
+    app.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use ch7_combat::{
+    stream_id, CombatCommand, CombatError, Combatant, Leaderboard, RespawnManager, Standing,
+    MAX_HEALTH, RESPAWN_DELAY, STREAM_TYPE,
+};
+use eventsourcing::app::{App, EmbeddedApp};
+use eventsourcing::{AggregateRepository, CommandResult};
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+struct Arena {
+    combatants: AggregateRepository<Combatant>,
+    app: App,
+}
+
+impl Arena {
+    async fn new() -> Self {
+        let builder = EmbeddedApp::in_memory().with_aggregate::<Combatant>();
+        let combatants = builder.repository::<Combatant>().unwrap();
+        let app = builder
+            .with_projection::<Leaderboard>()
+            .with_subscriber(
+                "respawn-manager",
+                &[STREAM_TYPE],
+                RespawnManager::new(combatants.clone()),
+            )
+            .build()
+            .await
+            .unwrap();
+        Self { combatants, app }
+    }
+
+    async fn execute(&self, name: &str, command: CombatCommand) -> CommandResult<Combatant> {
+        self.combatants
+            .execute_with_reply(&stream_id(name), command, RPC_TIMEOUT)
+            .await
+            .unwrap()
+    }
+
+    async fn health(&self, name: &str) -> u32 {
+        self.combatants
+            .get_state(&stream_id(name), RPC_TIMEOUT)
+            .await
+            .unwrap()
+            .health()
+    }
+
+    async fn standing(&self, name: &str) -> Standing {
+        self.app
+            .query::<Leaderboard>()
+            .await
+            .unwrap()
+            .standings()
+            .into_iter()
+            .find(|standing| standing.name == name)
+            .unwrap()
+    }
+}
+
+fn attack(attacker: &str, damage: u32) -> CombatCommand {
+    CombatCommand::Attack {
+        attacker: attacker.to_string(),
+        damage,
+    }
+}
+
+#[tokio::test]
+async fn overkill_damage_is_not_counted() {
+    let arena = Arena::new().await;
+
+    arena
+        .execute("overkill-b", attack("overkill-a", 30))
+        .await
+        .unwrap();
+    arena
+        .execute("overkill-b", attack("overkill-a", 500))
+        .await
+        .unwrap();
+    assert_eq!(arena.health("overkill-b").await, 0);
+
+    let killer = arena.standing("overkill-a").await;
+    assert_eq!(killer.kills, 1);
+    assert_eq!(killer.damage_dealt, u64::from(MAX_HEALTH));
+    assert_eq!(arena.standing("overkill-b").await.deaths, 1);
+}
+
+#[tokio::test]
+async fn heals_are_capped_at_max_health() {
+    let arena = Arena::new().await;
+
+    arena.execute("heal-a", attack("heal-b", 10)).await.unwrap();
+    arena
+        .execute("heal-a", CombatCommand::Heal { amount: 50 })
+        .await
+        .unwrap();
+    assert_eq!(arena.health("heal-a").await, MAX_HEALTH);
+}
+
+#[tokio::test]
+async fn dead_combatants_are_respawned() {
+    let arena = Arena::new().await;
+
+    arena
+        .execute("respawn-a", attack("respawn-b", MAX_HEALTH))
+        .await
+        .unwrap();
+    assert_eq!(
+        arena.execute("respawn-a", attack("respawn-b", 10)).await,
+        Err(CombatError::AlreadyDead)
+    );
+    assert_eq!(arena.health("respawn-a").await, 0);
+    assert_eq!(
+        arena.standing("respawn-b").await.damage_dealt,
+        u64::from(MAX_HEALTH)
+    );
+
+    tokio::time::sleep(RESPAWN_DELAY * 2).await;
+    assert_eq!(arena.health("respawn-a").await, MAX_HEALTH);
+}
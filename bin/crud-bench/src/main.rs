@@ -0,0 +1,150 @@
+//! Compares the event-sourced account service with its CRUD baseline on the
+//! same workload, both backed by a SQLite file.
+
+use ch2_account_balance::crud::CrudAccounts;
+use ch2_account_balance::{AccountBalance, AccountBalanceCommand, AccountLimits};
+use eventsourcing::stats::percentile;
+use eventsourcing::store::{EventStore, LogReader};
+use eventstore_sqlite::SqliteEventStore;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ACCOUNTS: usize = 10;
+const COMMANDS: usize = 10_000;
+const READ_BATCH_SIZE: usize = 1000;
+const LIMITS: AccountLimits = AccountLimits {
+    overdraft_limit: 50,
+    max_transaction: Some(500),
+};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+struct Run {
+    elapsed: Duration,
+    latencies: Vec<Duration>,
+    rejected: usize,
+    bytes: u64,
+    /// Rows kept: events for the event-sourced path, accounts for CRUD.
+    rows: usize,
+}
+
+async fn inner() -> anyhow::Result<()> {
+    let _logging = local_logging::init()?;
+
+    let es_path = temp_path("es");
+    let store = Arc::new(SqliteEventStore::open(&es_path)?);
+    let accounts = &AccountBalance::new(store.clone()).with_limits(LIMITS);
+    let mut es = run(|account_number, command| async move {
+        Ok(accounts.execute(&account_number, command).await?.is_ok())
+    })
+    .await?;
+    es.rows = count_events(store.as_ref()).await?;
+    es.bytes = file_size(&es_path);
+
+    let crud_path = temp_path("crud");
+    let crud_accounts = &CrudAccounts::open(&crud_path)?.with_limits(LIMITS);
+    let mut crud = run(|account_number, command| async move {
+        Ok(crud_accounts
+            .execute(&account_number, command)
+            .await?
+            .is_ok())
+    })
+    .await?;
+    crud.rows = ACCOUNTS;
+    crud.bytes = file_size(&crud_path);
+
+    println!(
+        "{} commands over {} accounts, {} rejected",
+        COMMANDS, ACCOUNTS, es.rejected
+    );
+    anyhow::ensure!(
+        es.rejected == crud.rejected,
+        "the two paths disagree on {} commands",
+        es.rejected.abs_diff(crud.rejected)
+    );
+    println!(
+        "{:<15} {:>12} {:>12} {:>12} {:>12} {:>8}",
+        "", "commands/s", "p50", "p99", "bytes", "rows"
+    );
+    for (name, mut run) in [("event-sourced", es), ("crud", crud)] {
+        run.latencies.sort_unstable();
+        println!(
+            "{:<15} {:>12.0} {:>12?} {:>12?} {:>12} {:>8}",
+            name,
+            COMMANDS as f64 / run.elapsed.as_secs_f64(),
+            percentile(&run.latencies, 50),
+            percentile(&run.latencies, 99),
+            run.bytes,
+            run.rows
+        );
+    }
+    // What the extra cost buys: only the event-sourced path can tell how a
+    // balance came about, rebuild it as of any point in time or feed new
+    // projections from the past.
+
+    for path in [es_path, crud_path] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Sends the same deterministic mix of deposits, withdrawals and fees to
+/// `execute`, which replies whether the command was accepted.
+async fn run<F, Fut>(execute: F) -> anyhow::Result<Run>
+where
+    F: Fn(String, AccountBalanceCommand) -> Fut,
+    Fut: Future<Output = anyhow::Result<bool>>,
+{
+    let mut latencies = Vec::with_capacity(COMMANDS);
+    let mut rejected = 0;
+    let started = Instant::now();
+    for i in 0..COMMANDS {
+        let value = (i * 37 % 600) as i64 + 1;
+        let command = match i % 10 {
+            0..=5 => AccountBalanceCommand::Deposit { value },
+            6..=8 => AccountBalanceCommand::Withdraw { value },
+            _ => AccountBalanceCommand::ApplyFee { value: 1 },
+        };
+        let command_started = Instant::now();
+        if !execute(format!("BENCH{}", i % ACCOUNTS), command).await? {
+            rejected += 1;
+        }
+        latencies.push(command_started.elapsed());
+    }
+    Ok(Run {
+        elapsed: started.elapsed(),
+        latencies,
+        rejected,
+        bytes: 0,
+        rows: 0,
+    })
+}
+
+async fn count_events(store: &dyn EventStore) -> anyhow::Result<usize> {
+    let mut count = 0;
+    let mut log = LogReader::new(store, 0, READ_BATCH_SIZE);
+    while let Some(events) = log.next_batch().await? {
+        count += events.len();
+    }
+    Ok(count)
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("crud-bench-{}-{}.db", std::process::id(), name))
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
@@ -0,0 +1,124 @@
+//! Reads the event log persisted by the examples for the `esctl` binary.
+
+pub mod report;
+
+use anyhow::{anyhow, bail};
+use ch2_account_balance::{Account, AccountSummaryProjection};
+use ch4_transfer::transfer::{self, Transfer};
+use eventsourcing::bus::{stream_type, EventHandler};
+use eventsourcing::ops::OpsHistory;
+use eventsourcing::projection::{ProjectionActor, ProjectionMessage, Projector};
+use eventsourcing::snapshot::{Snapshot, SnapshotStore};
+use eventsourcing::store::{EventStore, LogReader};
+use eventsourcing::Aggregate;
+use ractor::{call_t, Actor};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub const READ_BATCH_SIZE: usize = 256;
+const RPC_TIMEOUT_MS: u64 = 60_000;
+
+/// Stream types [`rehydrate`] has an aggregate for. Accounts are folded
+/// with `ch2-account-balance`, whose events `ch4-transfer` does not share.
+pub const REHYDRATED_STREAM_TYPES: &[&str] =
+    &[ch2_account_balance::STREAM_TYPE, transfer::STREAM_TYPE];
+
+/// Latest version of every stream in the log.
+pub async fn stream_versions(store: &dyn EventStore) -> anyhow::Result<BTreeMap<String, u64>> {
+    let mut versions = BTreeMap::new();
+    let mut log = LogReader::new(store, 0, READ_BATCH_SIZE);
+    while let Some(events) = log.next_batch().await? {
+        for event in events {
+            versions.insert(event.stream_id, event.version);
+        }
+    }
+    Ok(versions)
+}
+
+/// Rehydrates the aggregate of `stream_id` from its events up to
+/// `at_version`, or all of them, and returns the version it reached with
+/// the aggregate state.
+pub async fn rehydrate(
+    store: &dyn EventStore,
+    stream_id: &str,
+    at_version: Option<u64>,
+) -> anyhow::Result<(u64, serde_json::Value)> {
+    match stream_type(stream_id) {
+        ch2_account_balance::STREAM_TYPE => fold::<Account>(store, stream_id, at_version).await,
+        transfer::STREAM_TYPE => fold::<Transfer>(store, stream_id, at_version).await,
+        other => bail!(
+            "no aggregate to rehydrate for stream type {:?}, expected one of {:?}",
+            other,
+            REHYDRATED_STREAM_TYPES
+        ),
+    }
+}
+
+async fn fold<A: Aggregate>(
+    store: &dyn EventStore,
+    stream_id: &str,
+    at_version: Option<u64>,
+) -> anyhow::Result<(u64, serde_json::Value)> {
+    let mut aggregate = A::default();
+    let mut version = 0;
+    for recorded in store.read_stream(stream_id, 0).await? {
+        if at_version.is_some_and(|at_version| recorded.version > at_version) {
+            break;
+        }
+        aggregate.apply_event(recorded.decode()?);
+        version = recorded.version;
+    }
+    if version == 0 {
+        bail!("stream {} has no events", stream_id);
+    }
+    Ok((version, serde_json::to_value(aggregate)?))
+}
+
+/// Folds the `ops/...` streams into an [`OpsHistory`].
+pub async fn ops_history(store: &dyn EventStore) -> anyhow::Result<OpsHistory> {
+    let mut history = OpsHistory::default();
+    let mut log = LogReader::new(store, 0, READ_BATCH_SIZE);
+    while let Some(events) = log.next_batch().await? {
+        for event in &events {
+            history
+                .handle_event(event)
+                .await
+                .map_err(|err| anyhow!(err))?;
+        }
+    }
+    Ok(history)
+}
+
+/// Discards the checkpoint of the projection and catches it up from the
+/// start of the log.
+pub async fn rebuild(
+    store: Arc<dyn EventStore>,
+    checkpoints: Arc<dyn SnapshotStore>,
+    projection: &str,
+) -> anyhow::Result<AccountSummaryProjection> {
+    if projection != AccountSummaryProjection::NAME {
+        bail!(
+            "unknown projection {:?}, expected {:?}",
+            projection,
+            AccountSummaryProjection::NAME
+        );
+    }
+    checkpoints
+        .save(Snapshot {
+            stream_id: ProjectionActor::<AccountSummaryProjection>::checkpoint_id(),
+            version: 0,
+            state: serde_json::to_value(AccountSummaryProjection::default())?,
+        })
+        .await?;
+
+    let (actor, handle) = Actor::spawn(
+        None,
+        ProjectionActor::<AccountSummaryProjection>::new(store, checkpoints),
+        (),
+    )
+    .await?;
+    let summary = call_t!(actor, ProjectionMessage::Query, RPC_TIMEOUT_MS)?;
+    actor.stop(None);
+    handle.await?;
+    Ok(summary)
+}
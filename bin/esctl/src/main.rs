@@ -0,0 +1,145 @@
+//! Inspects the event log persisted by the examples.
+//!
+//! ```text
+//! esctl EVENTS streams                   list streams and their versions
+//! esctl EVENTS dump STREAM_ID            print the events of a stream
+//! esctl EVENTS state STREAM_ID [VERSION] rehydrate an account or transfer
+//!                                        as of VERSION
+//! esctl EVENTS rebuild PROJECTION        rebuild a projection from scratch
+//! esctl EVENTS report STREAM_TYPE [SNAPSHOTS.json]
+//!                                        stream lengths, replay times,
+//!                                        snapshot sizes and command mix
+//! esctl EVENTS ops                       operational history: actor failures,
+//!                                        undispatched scheduled commands
+//! ```
+//!
+//! `EVENTS` is a SQLite database for `.db`/`.sqlite` files, JSON lines
+//! otherwise, and projection checkpoints are read from
+//! `EVENTS.checkpoints.json`, as in `ch2-account-balance`. `state` reads
+//! `account/...` streams as `ch2-account-balance` accounts; the accounts of
+//! `ch4-transfer` record other events and cannot be rehydrated.
+
+use anyhow::{bail, Context};
+use esctl::report;
+use eventsourcing::snapshot::{JsonFileSnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, JsonLinesEventStore};
+use eventstore_sqlite::SqliteEventStore;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+async fn inner() -> anyhow::Result<()> {
+    ch2_account_balance::upcasters().install();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((path, command)) = args.split_first() else {
+        bail!("usage: esctl EVENTS (streams | dump STREAM_ID | state STREAM_ID [VERSION] | rebuild PROJECTION | report STREAM_TYPE [SNAPSHOTS.json] | ops)");
+    };
+    anyhow::ensure!(Path::new(path).exists(), "{} does not exist", path);
+    let store: Arc<dyn EventStore> = if path.ends_with(".db") || path.ends_with(".sqlite") {
+        Arc::new(SqliteEventStore::open(path)?)
+    } else {
+        Arc::new(JsonLinesEventStore::open(path)?)
+    };
+
+    match command {
+        [command] if command == "streams" => streams(store.as_ref()).await,
+        [command, stream_id] if command == "dump" => dump(store.as_ref(), stream_id).await,
+        [command, stream_id, at_version @ ..] if command == "state" && at_version.len() <= 1 => {
+            let at_version = match at_version.first() {
+                Some(version) => Some(version.parse().context("VERSION must be a number")?),
+                None => None,
+            };
+            state(store.as_ref(), stream_id, at_version).await
+        }
+        [command, projection] if command == "rebuild" => {
+            let checkpoints = JsonFileSnapshotStore::open(format!("{}.checkpoints.json", path))?;
+            rebuild(store, Arc::new(checkpoints), projection).await
+        }
+        [command, stream_type, snapshots @ ..] if command == "report" && snapshots.len() <= 1 => {
+            let snapshots = match snapshots.first() {
+                Some(path) => Some(JsonFileSnapshotStore::open(path)?),
+                None => None,
+            };
+            report::report(
+                store.as_ref(),
+                snapshots
+                    .as_ref()
+                    .map(|snapshots| snapshots as &dyn SnapshotStore),
+                stream_type,
+            )
+            .await
+        }
+        [command] if command == "ops" => ops(store.as_ref()).await,
+        _ => bail!("unknown command {:?}", command.join(" ")),
+    }
+}
+
+async fn streams(store: &dyn EventStore) -> anyhow::Result<()> {
+    for (stream_id, version) in &esctl::stream_versions(store).await? {
+        println!("{:<40} {:>8}", stream_id, version);
+    }
+    Ok(())
+}
+
+async fn dump(store: &dyn EventStore, stream_id: &str) -> anyhow::Result<()> {
+    let events = store.read_stream(stream_id, 0).await?;
+    if events.is_empty() {
+        bail!("stream {} has no events", stream_id);
+    }
+    for event in &events {
+        println!("{}", serde_json::to_string_pretty(event)?);
+    }
+    Ok(())
+}
+
+async fn state(
+    store: &dyn EventStore,
+    stream_id: &str,
+    at_version: Option<u64>,
+) -> anyhow::Result<()> {
+    let (version, state) = esctl::rehydrate(store, stream_id, at_version).await?;
+    println!("{} at version {}", stream_id, version);
+    println!("{}", serde_json::to_string_pretty(&state)?);
+    Ok(())
+}
+
+async fn ops(store: &dyn EventStore) -> anyhow::Result<()> {
+    let history = esctl::ops_history(store).await?;
+    if history.counts.is_empty() {
+        println!("no operational events");
+    }
+    for (kind, count) in &history.counts {
+        println!("{:<20} {:>8}", kind, count);
+    }
+    for entry in &history.recent {
+        println!(
+            "{:>15} {:<20} {:?}",
+            entry.timestamp_ms, entry.stream_id, entry.event
+        );
+    }
+    Ok(())
+}
+
+async fn rebuild(
+    store: Arc<dyn EventStore>,
+    checkpoints: Arc<dyn SnapshotStore>,
+    projection: &str,
+) -> anyhow::Result<()> {
+    let summary = esctl::rebuild(store, checkpoints, projection).await?;
+    println!("rebuilt {}", projection);
+    for (account, account_summary) in &summary.accounts {
+        println!("{}: {:?}", account, account_summary);
+    }
+    Ok(())
+}
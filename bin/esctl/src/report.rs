@@ -0,0 +1,170 @@
+//! Unit economics of the aggregates of one stream type: how long their
+//! streams grow, how costly they are to rehydrate and which commands they
+//! receive, with hints on when to snapshot, close the books or shard.
+
+use anyhow::bail;
+use eventsourcing::audit::{self, CommandOutcome, CommandRecord};
+use eventsourcing::bus::stream_type;
+use eventsourcing::snapshot::SnapshotStore;
+use eventsourcing::stats::percentile;
+use eventsourcing::store::{EventStore, LogReader};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::{rehydrate, READ_BATCH_SIZE, REHYDRATED_STREAM_TYPES};
+
+/// Streams longer than this are worth snapshotting or closing the books on.
+const LONG_STREAM: u64 = 10_000;
+const SLOW_REPLAY: Duration = Duration::from_millis(100);
+const LARGE_SNAPSHOT: usize = 64 * 1024;
+/// Share of all the events of the type above which a single stream is a
+/// hot spot, e.g. an account every transfer goes through.
+const HOT_STREAM_SHARE: f64 = 0.5;
+
+#[derive(Default)]
+struct CommandCounts {
+    accepted: usize,
+    rejected: usize,
+}
+
+pub async fn report(
+    store: &dyn EventStore,
+    snapshots: Option<&dyn SnapshotStore>,
+    aggregate_type: &str,
+) -> anyhow::Result<()> {
+    let mut versions = BTreeMap::new();
+    let mut commands: BTreeMap<String, CommandCounts> = BTreeMap::new();
+    let mut log = LogReader::new(store, 0, READ_BATCH_SIZE);
+    while let Some(events) = log.next_batch().await? {
+        for event in events {
+            if stream_type(&event.stream_id) == aggregate_type {
+                versions.insert(event.stream_id, event.version);
+            } else if is_audit_of(&event.stream_id, aggregate_type) {
+                let record: CommandRecord = event.decode()?;
+                let counts = commands.entry(command_name(&record.command)).or_default();
+                match record.outcome {
+                    CommandOutcome::Accepted { .. } => counts.accepted += 1,
+                    CommandOutcome::Rejected { .. } => counts.rejected += 1,
+                }
+            }
+        }
+    }
+    if versions.is_empty() {
+        bail!("no streams of type {:?}", aggregate_type);
+    }
+
+    let mut lengths: Vec<u64> = versions.values().copied().collect();
+    lengths.sort_unstable();
+    let total: u64 = lengths.iter().sum();
+
+    let rehydrated = REHYDRATED_STREAM_TYPES.contains(&aggregate_type);
+    let mut replay_times = Vec::with_capacity(versions.len());
+    let mut snapshot_sizes = Vec::new();
+    for stream_id in versions.keys() {
+        if rehydrated {
+            let started = Instant::now();
+            rehydrate(store, stream_id, None).await?;
+            replay_times.push(started.elapsed());
+        }
+        if let Some(snapshots) = snapshots {
+            if let Some(snapshot) = snapshots.load(stream_id).await? {
+                snapshot_sizes.push(serde_json::to_vec(&snapshot.state)?.len());
+            }
+        }
+    }
+    replay_times.sort_unstable();
+    snapshot_sizes.sort_unstable();
+
+    println!("{} streams, {} events", versions.len(), total);
+    print_distribution("stream length", &lengths, |length| length.to_string());
+    if !rehydrated {
+        println!("no aggregate to time the replays of {:?}", aggregate_type);
+    }
+    print_distribution("replay time", &replay_times, |time| format!("{:?}", time));
+    if snapshots.is_some() {
+        println!("{} streams with a snapshot", snapshot_sizes.len());
+        print_distribution("snapshot bytes", &snapshot_sizes, |size| size.to_string());
+    }
+    if commands.is_empty() {
+        println!("no audit trail for {:?}", aggregate_type);
+    }
+    for (command, counts) in &commands {
+        println!(
+            "{:<30} {:>8} accepted {:>8} rejected",
+            command, counts.accepted, counts.rejected
+        );
+    }
+
+    let (hot_stream, hot_length) = versions
+        .iter()
+        .max_by_key(|(_, version)| **version)
+        .expect("versions is not empty");
+    let mut hints = Vec::new();
+    if percentile(&lengths, 99) > LONG_STREAM {
+        hints.push(format!(
+            "streams grow past {} events, snapshot them or close the books periodically",
+            LONG_STREAM
+        ));
+    }
+    if !replay_times.is_empty() && percentile(&replay_times, 99) > SLOW_REPLAY {
+        hints.push(format!(
+            "replays take over {:?}, snapshot more often",
+            SLOW_REPLAY
+        ));
+    }
+    if snapshot_sizes
+        .last()
+        .is_some_and(|size| *size > LARGE_SNAPSHOT)
+    {
+        hints.push(format!(
+            "snapshots exceed {} bytes, keep less history in the aggregate state",
+            LARGE_SNAPSHOT
+        ));
+    }
+    if versions.len() > 1 && *hot_length as f64 > HOT_STREAM_SHARE * total as f64 {
+        hints.push(format!(
+            "{} holds {} of the {} events, consider sharding it",
+            hot_stream, hot_length, total
+        ));
+    }
+    if hints.is_empty() {
+        hints.push("nothing to act on".to_string());
+    }
+    for hint in hints {
+        println!("hint: {}", hint);
+    }
+    Ok(())
+}
+
+/// Whether `stream_id` is the audit stream of an aggregate of `aggregate_type`.
+pub fn is_audit_of(stream_id: &str, aggregate_type: &str) -> bool {
+    stream_id
+        .strip_prefix(audit::STREAM_TYPE)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .is_some_and(|audited| stream_type(audited) == aggregate_type)
+}
+
+/// Variant name of a command from its debug representation, e.g. `Deposit`
+/// for `Deposit { value: 100 }`.
+pub fn command_name(command: &str) -> String {
+    command
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or(command)
+        .to_string()
+}
+
+fn print_distribution<T: Copy>(name: &str, values: &[T], format: impl Fn(T) -> String) {
+    if values.is_empty() {
+        return;
+    }
+    println!(
+        "{:<15} min {} p50 {} p90 {} p99 {} max {}",
+        name,
+        format(values[0]),
+        format(percentile(values, 50)),
+        format(percentile(values, 90)),
+        format(percentile(values, 99)),
+        format(values[values.len() - 1])
+    );
+}
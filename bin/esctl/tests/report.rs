@@ -0,0 +1,55 @@
+use ch2_account_balance::AccountBalanceEventPayload;
+use esctl::report::{command_name, is_audit_of, report};
+use eventsourcing::envelope::EventEnvelope;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use serde_json::json;
+
+#[test]
+fn command_names_are_the_variant_names() {
+    assert_eq!(command_name("Deposit { value: 100 }"), "Deposit");
+    assert_eq!(command_name("RecordDebit"), "RecordDebit");
+    assert_eq!(command_name("Start(\"T\")"), "Start");
+    assert_eq!(command_name("record_fee"), "record_fee");
+}
+
+#[test]
+fn audit_streams_are_matched_by_the_audited_stream_type() {
+    assert!(is_audit_of("audit/account/A", "account"));
+    assert!(!is_audit_of("audit/transfer/T", "account"));
+    assert!(!is_audit_of("account/A", "account"));
+    assert!(!is_audit_of("auditor/account/A", "account"));
+}
+
+#[tokio::test]
+async fn reports_rehydrate_the_streams() {
+    let store = InMemoryEventStore::new();
+    let deposit = EventEnvelope::new(
+        "account/A",
+        &AccountBalanceEventPayload::AmountDeposited { value: 1 },
+        1,
+    )
+    .unwrap();
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::NoStream,
+            vec![serde_json::to_value(deposit).unwrap()],
+        )
+        .await
+        .unwrap();
+    report(&store, None, "account").await.unwrap();
+
+    // Replays fold the events into the aggregate rather than just parse them.
+    store
+        .append("account/B", ExpectedVersion::NoStream, vec![json!({})])
+        .await
+        .unwrap();
+    assert!(report(&store, None, "account").await.is_err());
+
+    // Streams without an aggregate are still reported, without replay times.
+    store
+        .append("calculator/1", ExpectedVersion::NoStream, vec![json!({})])
+        .await
+        .unwrap();
+    report(&store, None, "calculator").await.unwrap();
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use ch2_account_balance::{AccountBalanceEventPayload, AccountSummaryProjection};
+use ch4_transfer::transfer::{self, TransferEvent};
+use eventsourcing::envelope::EventEnvelope;
+use eventsourcing::projection::Projector;
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use serde::Serialize;
+use serde_json::json;
+
+async fn append<E: Serialize>(store: &dyn EventStore, stream_id: &str, events: &[E]) {
+    let payloads = events
+        .iter()
+        .map(|event| {
+            serde_json::to_value(EventEnvelope::new(stream_id, event, 1).unwrap()).unwrap()
+        })
+        .collect();
+    store
+        .append(stream_id, ExpectedVersion::Any, payloads)
+        .await
+        .unwrap();
+}
+
+async fn store() -> InMemoryEventStore {
+    let store = InMemoryEventStore::new();
+    append(
+        &store,
+        "account/A",
+        &[
+            AccountBalanceEventPayload::AmountDeposited { value: 100 },
+            AccountBalanceEventPayload::AmountWithdrawn { value: 30 },
+            AccountBalanceEventPayload::FeeApplied { value: 5 },
+        ],
+    )
+    .await;
+    append(
+        &store,
+        &transfer::stream_id("T"),
+        &[
+            TransferEvent::Started {
+                from: "A".to_string(),
+                to: "B".to_string(),
+                amount: 10,
+            },
+            TransferEvent::Debited,
+        ],
+    )
+    .await;
+    store
+}
+
+#[tokio::test]
+async fn accounts_are_rehydrated_as_of_a_version() {
+    let store = store().await;
+
+    assert_eq!(
+        esctl::rehydrate(&store, "account/A", None).await.unwrap(),
+        (3, json!({ "balance": 65 }))
+    );
+    assert_eq!(
+        esctl::rehydrate(&store, "account/A", Some(2))
+            .await
+            .unwrap(),
+        (2, json!({ "balance": 70 }))
+    );
+}
+
+#[tokio::test]
+async fn transfers_are_rehydrated_with_their_own_aggregate() {
+    let store = store().await;
+
+    let (version, state) = esctl::rehydrate(&store, "transfer/T", None).await.unwrap();
+    assert_eq!(version, 2);
+    assert_eq!(state["status"], json!("Debited"));
+    assert_eq!(state["amount"], json!(10));
+}
+
+#[tokio::test]
+async fn streams_without_an_aggregate_are_rejected() {
+    let store = store().await;
+    append(
+        &store,
+        "calculator/1",
+        &[json!({ "DidAdd": { "value": 1 } })],
+    )
+    .await;
+
+    let err = esctl::rehydrate(&store, "calculator/1", None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("\"calculator\""), "{}", err);
+    assert!(esctl::rehydrate(&store, "account/B", None).await.is_err());
+}
+
+#[tokio::test]
+async fn stream_versions_cover_the_whole_log() {
+    let store = store().await;
+
+    let versions = esctl::stream_versions(&store).await.unwrap();
+    assert_eq!(
+        versions.into_iter().collect::<Vec<_>>(),
+        [("account/A".to_string(), 3), ("transfer/T".to_string(), 2)]
+    );
+}
+
+#[tokio::test]
+async fn rebuild_discards_the_checkpoint() {
+    let store: Arc<dyn EventStore> = Arc::new(store().await);
+    let checkpoints: Arc<dyn SnapshotStore> = Arc::new(InMemorySnapshotStore::new());
+
+    let summary = esctl::rebuild(
+        Arc::clone(&store),
+        Arc::clone(&checkpoints),
+        AccountSummaryProjection::NAME,
+    )
+    .await
+    .unwrap();
+    assert_eq!(summary.accounts["A"].balance, 65);
+    let summary = esctl::rebuild(store, checkpoints, AccountSummaryProjection::NAME)
+        .await
+        .unwrap();
+    assert_eq!(summary.accounts["A"].balance, 65);
+
+    assert!(esctl::rebuild(
+        Arc::new(InMemoryEventStore::new()),
+        Arc::new(InMemorySnapshotStore::new()),
+        "unknown"
+    )
+    .await
+    .is_err());
+}
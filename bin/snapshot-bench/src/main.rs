@@ -0,0 +1,105 @@
+//! Compares aggregate rehydration time with and without snapshots.
+
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+use eventsourcing::{Aggregate, AggregateActor, AggregateMessage};
+use ractor::Actor;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const STREAM_LENGTHS: [u64; 3] = [1_000, 10_000, 100_000];
+const SNAPSHOT_FREQUENCY: u64 = 100;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+pub struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match inner().await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+async fn inner() -> anyhow::Result<()> {
+    let _logging = local_logging::init()?;
+
+    println!(
+        "{:>10} {:>18} {:>18}",
+        "events", "full replay", "snapshot + tail"
+    );
+    for length in STREAM_LENGTHS {
+        let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+        let snapshots: Arc<dyn SnapshotStore> = Arc::new(InMemorySnapshotStore::new());
+        let stream_id = format!("counter/{}", length);
+
+        let writer = AggregateActor::<Counter>::new(Arc::clone(&store))
+            .with_snapshots(Arc::clone(&snapshots), SNAPSHOT_FREQUENCY);
+        let (actor, handle) = Actor::spawn(None, writer, stream_id.clone()).await?;
+        for _ in 0..length {
+            actor.send_message(AggregateMessage::Execute(Increment { by: 1 }))?;
+        }
+        actor.drain()?;
+        handle.await?;
+
+        let full_replay =
+            time_rehydration(AggregateActor::new(Arc::clone(&store)), &stream_id).await?;
+        let with_snapshot = time_rehydration(
+            AggregateActor::new(store).with_snapshots(snapshots, SNAPSHOT_FREQUENCY),
+            &stream_id,
+        )
+        .await?;
+        println!(
+            "{:>10} {:>18?} {:>18?}",
+            length, full_replay, with_snapshot
+        );
+    }
+
+    Ok(())
+}
+
+/// Spawning only returns once `pre_start` has rehydrated the aggregate.
+async fn time_rehydration(
+    actor: AggregateActor<Counter>,
+    stream_id: &str,
+) -> anyhow::Result<Duration> {
+    let started = Instant::now();
+    let (actor, handle) = Actor::spawn(None, actor, stream_id.to_string()).await?;
+    let elapsed = started.elapsed();
+
+    actor.stop(None);
+    handle.await?;
+    Ok(elapsed)
+}
@@ -0,0 +1,345 @@
+//! Event distribution across processes over NATS JetStream.
+//!
+//! [`NatsRelay`] forwards committed events to a JetStream stream, and
+//! [`NatsSubscriber`] feeds them to an [`EventHandler`] in another process
+//! through a durable consumer. Both resume where they stopped: the relay
+//! after the last event in the JetStream stream, the subscriber after the
+//! last event it handled.
+//!
+//! The relay is the outbox of the event store: it is left out of builds
+//! without the `outbox` feature, for processes that only subscribe.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::consumer::{pull, AckPolicy, PullConsumer};
+use async_nats::jetstream::context::Publish;
+use async_nats::jetstream::{self, stream, AckKind};
+use eventsourcing::bus::{event_span, stream_type, Checkpoint, EventHandler};
+#[cfg(feature = "outbox")]
+use eventsourcing::bus::{EventBus, EventSubscriber};
+use eventsourcing::snapshot::SnapshotStore;
+#[cfg(feature = "outbox")]
+use eventsourcing::store::EventStore;
+use eventsourcing::store::RecordedEvent;
+use futures::StreamExt;
+use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+const DEFAULT_MAX_DELIVER: i64 = 10;
+const NAK_DELAY: Duration = Duration::from_secs(1);
+
+/// A JetStream stream carrying events, with one subject per stream type:
+/// `<prefix>.<stream type>`, where the prefix is the lowercased stream name.
+#[derive(Clone)]
+pub struct NatsEventBus {
+    jetstream: jetstream::Context,
+    stream_name: String,
+    subject_prefix: String,
+}
+
+impl NatsEventBus {
+    /// Connects to the server at `url` and creates the JetStream stream
+    /// `stream_name` unless it exists.
+    pub async fn connect(url: &str, stream_name: &str) -> Result<Self, async_nats::Error> {
+        let jetstream = jetstream::new(async_nats::connect(url).await?);
+        let subject_prefix = stream_name.to_lowercase();
+        jetstream
+            .get_or_create_stream(stream::Config {
+                name: stream_name.to_string(),
+                subjects: vec![format!("{}.>", subject_prefix)],
+                ..Default::default()
+            })
+            .await?;
+        Ok(Self {
+            jetstream,
+            stream_name: stream_name.to_string(),
+            subject_prefix,
+        })
+    }
+
+    /// Publishes `event` and waits until JetStream has stored it.
+    ///
+    /// The message id is the stream id and version of the event, so
+    /// republishing it within the duplicate window of the stream is a no-op.
+    pub async fn publish(&self, event: &RecordedEvent) -> Result<(), async_nats::Error> {
+        let publish = Publish::build()
+            .payload(serde_json::to_vec(event)?.into())
+            .message_id(format!("{}/{}", event.stream_id, event.version));
+        self.jetstream
+            .send_publish(self.subject(stream_type(&event.stream_id)), publish)
+            .await?
+            .await?;
+        Ok(())
+    }
+
+    /// Global position of the last event in the JetStream stream, 0 if it is
+    /// empty.
+    pub async fn last_position(&self) -> Result<u64, async_nats::Error> {
+        let mut stream = self.jetstream.get_stream(&self.stream_name).await?;
+        let last_sequence = stream.info().await?.state.last_sequence;
+        if last_sequence == 0 {
+            return Ok(0);
+        }
+        let message = stream.get_raw_message(last_sequence).await?;
+        let event: RecordedEvent = serde_json::from_slice(&message.payload)?;
+        Ok(event.position)
+    }
+
+    fn subject(&self, stream_type: &str) -> String {
+        format!("{}.{}", self.subject_prefix, stream_type)
+    }
+}
+
+/// Forwards events to JetStream.
+///
+/// Run it in the [`EventSubscriber`] built by [`NatsRelay::subscriber`], so
+/// events committed while NATS was unreachable are read back from the store
+/// and published once it is back.
+#[cfg(feature = "outbox")]
+pub struct NatsRelay {
+    bus: NatsEventBus,
+}
+
+#[cfg(feature = "outbox")]
+impl NatsRelay {
+    pub fn new(bus: NatsEventBus) -> Self {
+        Self { bus }
+    }
+
+    /// Subscriber relaying the events of `stream_types` from `store`,
+    /// starting after the last event already in the JetStream stream rather
+    /// than republishing the whole log on every start.
+    ///
+    /// A relay stopped between a publish and the next one may republish the
+    /// last event, which JetStream drops as a duplicate.
+    pub async fn subscriber(
+        &self,
+        store: Arc<dyn EventStore>,
+        events: EventBus,
+        stream_types: &[&str],
+    ) -> Result<EventSubscriber<Self>, async_nats::Error> {
+        let last_position = self.bus.last_position().await?;
+        tracing::info!(last_position, "resuming NATS relay");
+        Ok(EventSubscriber::new(store, events, stream_types).starting_after(last_position))
+    }
+}
+
+#[cfg(feature = "outbox")]
+#[async_trait]
+impl EventHandler for NatsRelay {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.bus.publish(event).await?;
+        Ok(())
+    }
+}
+
+/// Actor feeding the events of some stream types from JetStream to an
+/// [`EventHandler`].
+///
+/// The durable consumer remembers which events were acknowledged, so a
+/// restarted subscriber resumes where it left off. An event is acknowledged
+/// once handled; if the handler fails, it is redelivered after a delay, up to
+/// a maximum number of deliveries. Only one event is in flight at a time to
+/// keep them in order, and redelivered events at or before the last handled
+/// position are acknowledged without handling them again.
+///
+/// An event handled but not acknowledged before a crash is redelivered. To
+/// still recognize it after a restart, save the last handled position with
+/// [`NatsSubscriber::with_checkpoints`].
+pub struct NatsSubscriber<H> {
+    bus: NatsEventBus,
+    durable_name: String,
+    stream_types: Vec<String>,
+    max_deliver: i64,
+    checkpoints: Option<Arc<dyn SnapshotStore>>,
+    _handler: PhantomData<fn() -> H>,
+}
+
+#[derive(Debug)]
+pub enum NatsSubscriberMessage {
+    Delivered(jetstream::Message),
+}
+
+pub struct NatsSubscriberState<H> {
+    inbox: Inbox<H>,
+    pull: JoinHandle<()>,
+}
+
+/// Feeds delivered events to an [`EventHandler`] for a [`NatsSubscriber`],
+/// skipping those at or before its [`Checkpoint`].
+pub struct Inbox<H> {
+    handler: H,
+    checkpoint: Checkpoint,
+}
+
+impl<H: EventHandler> Inbox<H> {
+    pub fn new(handler: H, checkpoint: Checkpoint) -> Self {
+        Self {
+            handler,
+            checkpoint,
+        }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Handles the event delivered on `subject` and tells how to acknowledge
+    /// it: malformed events are terminated, failed ones redelivered later.
+    pub async fn deliver(
+        &mut self,
+        subject: &str,
+        payload: &[u8],
+    ) -> Result<AckKind, ActorProcessingErr> {
+        let event: RecordedEvent = match serde_json::from_slice(payload) {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::error!("dropping malformed event {}: {}", subject, err);
+                return Ok(AckKind::Term);
+            }
+        };
+        if event.position <= self.checkpoint.position() {
+            return Ok(AckKind::Ack);
+        }
+        match self
+            .handler
+            .handle_event(&event)
+            .instrument(event_span(&event))
+            .await
+        {
+            Ok(()) => {
+                self.checkpoint.advance(event.position).await?;
+                Ok(AckKind::Ack)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "failed to handle event at position {}: {}",
+                    event.position,
+                    err
+                );
+                Ok(AckKind::Nak(Some(NAK_DELAY)))
+            }
+        }
+    }
+}
+
+impl<H> NatsSubscriber<H> {
+    pub fn new(bus: NatsEventBus, durable_name: &str, stream_types: &[&str]) -> Self {
+        Self {
+            bus,
+            durable_name: durable_name.to_string(),
+            stream_types: stream_types.iter().map(|t| t.to_string()).collect(),
+            max_deliver: DEFAULT_MAX_DELIVER,
+            checkpoints: None,
+            _handler: PhantomData,
+        }
+    }
+
+    /// Saves the position of the last handled event in `checkpoints`, keyed
+    /// `nats-subscriber/<durable name>`.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<dyn SnapshotStore>) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Gives up on an event after it has been delivered `max_deliver` times.
+    pub fn with_max_deliver(mut self, max_deliver: i64) -> Self {
+        self.max_deliver = max_deliver;
+        self
+    }
+}
+
+#[async_trait]
+impl<H: EventHandler> Actor for NatsSubscriber<H> {
+    type Msg = NatsSubscriberMessage;
+    type State = NatsSubscriberState<H>;
+    type Arguments = H;
+
+    async fn pre_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        handler: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let checkpoint = match &self.checkpoints {
+            Some(checkpoints) => {
+                let id = format!("nats-subscriber/{}", self.durable_name);
+                Checkpoint::load(Arc::clone(checkpoints), id).await?
+            }
+            None => Checkpoint::at(0),
+        };
+        let consumer: PullConsumer = self
+            .bus
+            .jetstream
+            .get_stream(&self.bus.stream_name)
+            .await?
+            .get_or_create_consumer(
+                &self.durable_name,
+                pull::Config {
+                    durable_name: Some(self.durable_name.clone()),
+                    filter_subjects: self
+                        .stream_types
+                        .iter()
+                        .map(|stream_type| self.bus.subject(stream_type))
+                        .collect(),
+                    ack_policy: AckPolicy::Explicit,
+                    max_deliver: self.max_deliver,
+                    max_ack_pending: 1,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let mut messages = consumer.messages().await?;
+
+        let pull = tokio::spawn(async move {
+            while let Some(message) = messages.next().await {
+                match message {
+                    Ok(message) => {
+                        if myself
+                            .send_message(NatsSubscriberMessage::Delivered(message))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Err(err) => tracing::warn!("failed to pull from JetStream: {}", err),
+                }
+            }
+        });
+
+        Ok(NatsSubscriberState {
+            inbox: Inbox::new(handler, checkpoint),
+            pull,
+        })
+    }
+
+    async fn post_stop(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        state.pull.abort();
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            NatsSubscriberMessage::Delivered(message) => {
+                let ack = state
+                    .inbox
+                    .deliver(&message.subject, &message.payload)
+                    .await?;
+                message.ack_with(ack).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex};
+
+use async_nats::jetstream::AckKind;
+use eventbus_nats::Inbox;
+use eventsourcing::bus::{Checkpoint, EventHandler};
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::RecordedEvent;
+use ractor::{async_trait, ActorProcessingErr};
+use serde_json::json;
+
+const CHECKPOINT_ID: &str = "nats-subscriber/test";
+
+/// Positions handled, failing on the ones in `failing`.
+#[derive(Clone, Default)]
+struct Collect {
+    handled: Arc<Mutex<Vec<u64>>>,
+    failing: Vec<u64>,
+}
+
+#[async_trait]
+impl EventHandler for Collect {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        if self.failing.contains(&event.position) {
+            return Err("handler failed".into());
+        }
+        self.handled.lock().unwrap().push(event.position);
+        Ok(())
+    }
+}
+
+fn payload(position: u64) -> Vec<u8> {
+    serde_json::to_vec(&RecordedEvent {
+        stream_id: "account/A".to_string(),
+        version: position,
+        position,
+        payload: json!({ "n": position }),
+    })
+    .unwrap()
+}
+
+async fn inbox(checkpoints: &Arc<dyn SnapshotStore>, handler: Collect) -> Inbox<Collect> {
+    let checkpoint = Checkpoint::load(Arc::clone(checkpoints), CHECKPOINT_ID.to_string())
+        .await
+        .unwrap();
+    Inbox::new(handler, checkpoint)
+}
+
+#[tokio::test]
+async fn redelivered_events_are_skipped_across_restarts() {
+    let checkpoints: Arc<dyn SnapshotStore> = Arc::new(InMemorySnapshotStore::new());
+    let handler = Collect::default();
+    let mut first = inbox(&checkpoints, handler.clone()).await;
+    for position in [1, 2, 1] {
+        let ack = first.deliver("events.account", &payload(position)).await;
+        assert!(matches!(ack, Ok(AckKind::Ack)));
+    }
+    assert_eq!(*handler.handled.lock().unwrap(), vec![1, 2]);
+
+    // Handled but not acknowledged before the restart, then redelivered.
+    let mut second = inbox(&checkpoints, handler.clone()).await;
+    for position in [2, 3] {
+        let ack = second.deliver("events.account", &payload(position)).await;
+        assert!(matches!(ack, Ok(AckKind::Ack)));
+    }
+    assert_eq!(*handler.handled.lock().unwrap(), vec![1, 2, 3]);
+    let checkpoint = checkpoints.load(CHECKPOINT_ID).await.unwrap().unwrap();
+    assert_eq!(checkpoint.version, 3);
+}
+
+#[tokio::test]
+async fn failed_events_are_redelivered_and_malformed_ones_dropped() {
+    let handler = Collect {
+        failing: vec![1],
+        ..Collect::default()
+    };
+    let mut inbox = Inbox::new(handler, Checkpoint::at(0));
+
+    assert!(matches!(
+        inbox.deliver("events.account", &payload(1)).await,
+        Ok(AckKind::Nak(Some(_)))
+    ));
+    assert!(matches!(
+        inbox.deliver("events.account", b"{").await,
+        Ok(AckKind::Term)
+    ));
+    assert!(inbox.handler().handled.lock().unwrap().is_empty());
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "outbox")]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eventbus_nats::{NatsEventBus, NatsRelay, NatsSubscriber};
+use eventsourcing::bus::EventHandler;
+use eventsourcing::store::RecordedEvent;
+use ractor::{async_trait, Actor, ActorProcessingErr};
+use serde_json::json;
+
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<u64>>>);
+
+#[async_trait]
+impl EventHandler for Collect {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.0.lock().unwrap().push(event.position);
+        Ok(())
+    }
+}
+
+fn event(stream_id: &str, position: u64) -> RecordedEvent {
+    RecordedEvent {
+        stream_id: stream_id.to_string(),
+        version: position,
+        position,
+        payload: json!({ "n": position }),
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a NATS server with JetStream enabled at $NATS_URL"]
+async fn relayed_events_reach_a_subscriber_once() {
+    let url = std::env::var("NATS_URL").unwrap_or_else(|_| "localhost:4222".to_string());
+    let stream_name = format!("TEST_{}", std::process::id());
+    let bus = NatsEventBus::connect(&url, &stream_name).await.unwrap();
+
+    let mut relay = NatsRelay::new(bus.clone());
+    for position in 1..=3 {
+        relay
+            .handle_event(&event("account/A", position))
+            .await
+            .unwrap();
+    }
+    // Republished after a relay restart, deduplicated by JetStream.
+    relay.handle_event(&event("account/A", 2)).await.unwrap();
+    relay.handle_event(&event("audit/A", 4)).await.unwrap();
+
+    let collected = Collect::default();
+    let (subscriber, _) = Actor::spawn(
+        None,
+        NatsSubscriber::<Collect>::new(bus, "test", &["account"]),
+        collected.clone(),
+    )
+    .await
+    .unwrap();
+    for _ in 0..50 {
+        if collected.0.lock().unwrap().len() >= 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    subscriber.stop(None);
+
+    assert_eq!(*collected.0.lock().unwrap(), vec![1, 2, 3]);
+}
@@ -0,0 +1,468 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+
+use crate::audit::{AuditLog, CommandOutcome, CommandRecord};
+use crate::budget::YieldBudget;
+use crate::bus::EventBus;
+use crate::envelope::{self, Correlation, EventEnvelope, Upcasters};
+#[cfg(feature = "ops")]
+use crate::ops::{OpsEvent, OpsLog};
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::store::{EventStore, ExpectedVersion, DEDUPE_WINDOW};
+use crate::Aggregate;
+
+/// Actor hosting a single aggregate instance backed by an event stream.
+///
+/// The spawn argument is the stream id. On start the actor loads the latest
+/// snapshot, if snapshots are enabled, and replays the rest of the stream.
+/// Events produced by a command are appended to the stream before they are
+/// applied.
+///
+/// A command sent with a `command_id` records it as the
+/// [`event_id`](EventEnvelope::event_id) of its events. Resending it, e.g.
+/// after a timeout, replies the events recorded the first time instead of
+/// handling it again, as long as it is within the last [`DEDUPE_WINDOW`]
+/// commands sent with an id. A rejected command records nothing, so resending
+/// it decides again.
+pub struct AggregateActor<A> {
+    store: Arc<dyn EventStore>,
+    snapshots: Option<Snapshots>,
+    bus: Option<EventBus>,
+    audit: Option<AuditLog>,
+    #[cfg(feature = "ops")]
+    ops: Option<OpsLog>,
+    upcasters: Upcasters,
+    _aggregate: PhantomData<fn() -> A>,
+}
+
+#[derive(Clone)]
+struct Snapshots {
+    store: Arc<dyn SnapshotStore>,
+    frequency: u64,
+}
+
+/// Outcome of a command: the persisted events or the domain rejection.
+pub type CommandResult<A> = Result<Vec<<A as Aggregate>::Event>, <A as Aggregate>::Error>;
+
+#[derive(Debug)]
+pub enum AggregateMessage<A: Aggregate> {
+    /// Fire-and-forget command. Rejections are only logged.
+    Execute(A::Command),
+    /// Command whose outcome is sent back once the events are persisted.
+    ExecuteWithReply(A::Command, RpcReplyPort<CommandResult<A>>),
+    /// Command attributed to `source` in the audit log, continuing the work
+    /// of `correlation` and handled once per `command_id`, all optional, with
+    /// an optional reply port.
+    ExecuteFrom {
+        source: Option<String>,
+        correlation: Option<Correlation>,
+        command_id: Option<String>,
+        command: A::Command,
+        reply_port: Option<RpcReplyPort<CommandResult<A>>>,
+    },
+    /// Current aggregate, reflecting every command handled before it.
+    GetState(RpcReplyPort<A>),
+}
+
+#[derive(Debug)]
+pub struct AggregateState<A: Aggregate> {
+    stream_id: String,
+    version: u64,
+    snapshot_version: u64,
+    aggregate: A,
+    /// Ids of the last [`DEDUPE_WINDOW`] commands applied, with their events.
+    recent_commands: VecDeque<(String, Vec<A::Event>)>,
+}
+
+impl<A> AggregateActor<A> {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self {
+            store,
+            snapshots: None,
+            bus: None,
+            audit: None,
+            #[cfg(feature = "ops")]
+            ops: None,
+            upcasters: Upcasters::new(),
+            _aggregate: PhantomData,
+        }
+    }
+
+    /// Saves a snapshot every time `frequency` events have been appended since
+    /// the last one.
+    pub fn with_snapshots(mut self, store: Arc<dyn SnapshotStore>, frequency: u64) -> Self {
+        self.snapshots = Some(Snapshots {
+            store,
+            frequency: frequency.max(1),
+        });
+        self
+    }
+
+    /// Publishes committed events on `bus`.
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Records every command received, accepted or rejected, in `audit`.
+    ///
+    /// Records are appended after the events of the command, and failing to
+    /// append one is only logged.
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Records in `ops` the errors that stop the actor while it rehydrates or
+    /// handles a command.
+    #[cfg(feature = "ops")]
+    pub fn with_ops_log(mut self, ops: OpsLog) -> Self {
+        self.ops = Some(ops);
+        self
+    }
+
+    /// Migrates events written with older schema versions while rehydrating.
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+        self
+    }
+}
+
+impl<A> Clone for AggregateActor<A> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            snapshots: self.snapshots.clone(),
+            bus: self.bus.clone(),
+            audit: self.audit.clone(),
+            #[cfg(feature = "ops")]
+            ops: self.ops.clone(),
+            upcasters: self.upcasters.clone(),
+            _aggregate: PhantomData,
+        }
+    }
+}
+
+impl<A: Aggregate> AggregateState<A> {
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn aggregate(&self) -> &A {
+        &self.aggregate
+    }
+
+    fn recent_command(&self, command_id: &str) -> Option<&[A::Event]> {
+        self.recent_commands
+            .iter()
+            .find(|(id, _)| id == command_id)
+            .map(|(_, events)| events.as_slice())
+    }
+
+    /// Remembers `event` as produced by `command_id`, after the previous
+    /// events of the same command.
+    fn remember(&mut self, command_id: &str, event: A::Event) {
+        match self.recent_commands.back_mut() {
+            Some((id, events)) if id == command_id => events.push(event),
+            _ => {
+                if self.recent_commands.len() == DEDUPE_WINDOW {
+                    self.recent_commands.pop_front();
+                }
+                self.recent_commands
+                    .push_back((command_id.to_string(), vec![event]));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<A: Aggregate> Actor for AggregateActor<A> {
+    type Msg = AggregateMessage<A>;
+    type State = AggregateState<A>;
+    type Arguments = String;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        stream_id: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let result = self.rehydrate(stream_id.clone()).await;
+        self.record_failure(&stream_id, result.as_ref().err()).await;
+        result
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        use tracing::{field, Instrument};
+
+        let (command, command_id, reply_port, source, correlation) = match message {
+            AggregateMessage::Execute(command) => (command, None, None, None, None),
+            AggregateMessage::ExecuteWithReply(command, reply_port) => {
+                (command, None, Some(reply_port), None, None)
+            }
+            AggregateMessage::ExecuteFrom {
+                source,
+                correlation,
+                command_id,
+                command,
+                reply_port,
+            } => (command, command_id, reply_port, source, correlation),
+            AggregateMessage::GetState(reply_port) => {
+                let _ = reply_port.send(state.aggregate.clone());
+                return Ok(());
+            }
+        };
+        let correlation = correlation.unwrap_or_default();
+        let tracing_span = tracing::info_span!(
+            "handle",
+            stream_id = %state.stream_id,
+            correlation_id = %correlation.correlation_id,
+            causation_id = ?correlation.causation_id,
+            ?source,
+            ?command_id,
+            ?command,
+            events = field::Empty
+        );
+        let result = self
+            .handle_command(command, command_id, reply_port, source, &correlation, state)
+            .instrument(tracing_span)
+            .await;
+        self.record_failure(&state.stream_id, result.as_ref().err())
+            .await;
+        result
+    }
+}
+
+impl<A: Aggregate> AggregateActor<A> {
+    /// Records an error stopping the actor in the ops log, if there is one.
+    #[cfg(feature = "ops")]
+    async fn record_failure(&self, stream_id: &str, error: Option<&ActorProcessingErr>) {
+        if let (Some(err), Some(ops)) = (error, &self.ops) {
+            ops.record_or_log(OpsEvent::ActorFailed {
+                stream_id: stream_id.to_string(),
+                reason: err.to_string(),
+            })
+            .await;
+        }
+    }
+
+    #[cfg(not(feature = "ops"))]
+    async fn record_failure(&self, _stream_id: &str, _error: Option<&ActorProcessingErr>) {}
+
+    async fn rehydrate(&self, stream_id: String) -> Result<AggregateState<A>, ActorProcessingErr> {
+        let mut state = AggregateState {
+            stream_id,
+            version: 0,
+            snapshot_version: 0,
+            aggregate: A::default(),
+            recent_commands: VecDeque::new(),
+        };
+
+        if let Some(snapshots) = &self.snapshots {
+            if let Some(snapshot) = snapshots.store.load(&state.stream_id).await? {
+                state.aggregate = serde_json::from_value(snapshot.state)?;
+                state.version = snapshot.version;
+                state.snapshot_version = snapshot.version;
+            }
+        }
+
+        let snapshot_version = state.version;
+        let mut budget = YieldBudget::default();
+        for recorded in self
+            .store
+            .read_stream(&state.stream_id, state.version)
+            .await?
+        {
+            let event: A::Event = self.upcasters.decode(&recorded)?;
+            if let Some(command_id) = envelope::event_id(&recorded.payload) {
+                state.remember(command_id, event.clone());
+            }
+            state.aggregate.apply_event(event);
+            state.version = recorded.version;
+            budget.tick().await;
+        }
+        tracing::debug!(
+            stream_id = %state.stream_id,
+            snapshot_version,
+            version = state.version,
+            "aggregate rehydrated"
+        );
+
+        Ok(state)
+    }
+
+    async fn handle_command(
+        &self,
+        command: A::Command,
+        command_id: Option<String>,
+        reply_port: Option<RpcReplyPort<CommandResult<A>>>,
+        source: Option<String>,
+        correlation: &Correlation,
+        state: &mut AggregateState<A>,
+    ) -> Result<(), ActorProcessingErr> {
+        let description = self.audit.as_ref().map(|_| format!("{:?}", command));
+        let result = self
+            .execute(command, command_id.as_deref(), correlation, state)
+            .await?;
+        if let (Some(audit), Some(command)) = (&self.audit, description) {
+            let outcome = match &result {
+                Ok(events) => CommandOutcome::Accepted {
+                    events: events.len(),
+                },
+                Err(err) => CommandOutcome::Rejected {
+                    reason: err.to_string(),
+                },
+            };
+            let record = CommandRecord {
+                stream_id: state.stream_id.clone(),
+                source,
+                command,
+                outcome,
+                recorded_at_ms: crate::now_ms(),
+            };
+            // The events are committed by now, so the command has succeeded
+            // whether or not its record makes it to the audit log.
+            if let Err(err) = audit.record(&record).await {
+                tracing::error!(
+                    stream_id = %state.stream_id,
+                    "failed to record command in the audit log: {}",
+                    err
+                );
+            }
+        }
+        match reply_port {
+            Some(reply_port) => {
+                let _ = reply_port.send(result);
+            }
+            None => {
+                if let Err(err) = result {
+                    tracing::error!("failed to handle command: {}", err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Persists and applies the events produced by `command`, or returns the
+    /// events already recorded under `command_id`.
+    ///
+    /// Domain rejections are returned in the inner result, while store
+    /// failures stop the actor.
+    async fn execute(
+        &self,
+        command: A::Command,
+        command_id: Option<&str>,
+        correlation: &Correlation,
+        state: &mut AggregateState<A>,
+    ) -> Result<CommandResult<A>, ActorProcessingErr> {
+        if let Some(events) = command_id.and_then(|id| state.recent_command(id)) {
+            tracing::info!("skipping duplicate command {:?}", command_id);
+            return Ok(Ok(events.to_vec()));
+        }
+        let events = match state.aggregate.handle_command(command) {
+            Ok(events) => events,
+            Err(err) => return Ok(Err(err)),
+        };
+        tracing::Span::current().record("events", tracing::field::debug(&events));
+
+        let payloads = events
+            .iter()
+            .map(|event| {
+                let mut envelope = EventEnvelope::new(&state.stream_id, event, A::EVENT_VERSION)?
+                    .with_correlation(correlation);
+                envelope.event_id = command_id.map(str::to_string);
+                serde_json::to_value(envelope)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let previous_version = state.version;
+        state.version = self
+            .store
+            .append(
+                &state.stream_id,
+                ExpectedVersion::Exact(previous_version),
+                payloads,
+            )
+            .await?;
+        if let (Some(command_id), true) = (command_id, state.version == previous_version) {
+            // The store recognized a retried append, whose id this actor did
+            // not replay, e.g. because it was recorded before the snapshot.
+            return self.recorded_events(command_id, state).await.map(Ok);
+        }
+        if let Some(bus) = &self.bus {
+            bus.publish(
+                &self
+                    .store
+                    .read_stream(&state.stream_id, previous_version)
+                    .await?,
+            );
+        }
+        let mut budget = YieldBudget::default();
+        for event in events.clone() {
+            if let Some(command_id) = command_id {
+                state.remember(command_id, event.clone());
+            }
+            state.aggregate.apply_event(event);
+            budget.tick().await;
+        }
+        tracing::debug!("state: {:?}", state.aggregate);
+
+        self.maybe_snapshot(state).await?;
+        Ok(Ok(events))
+    }
+
+    /// Events recorded under `command_id` among the last [`DEDUPE_WINDOW`]
+    /// events of the stream, where the store looks for retried appends.
+    async fn recorded_events(
+        &self,
+        command_id: &str,
+        state: &mut AggregateState<A>,
+    ) -> Result<Vec<A::Event>, ActorProcessingErr> {
+        let after_version = state.version.saturating_sub(DEDUPE_WINDOW as u64);
+        let mut events = Vec::new();
+        for recorded in self
+            .store
+            .read_stream(&state.stream_id, after_version)
+            .await?
+        {
+            if envelope::event_id(&recorded.payload) == Some(command_id) {
+                let event: A::Event = self.upcasters.decode(&recorded)?;
+                state.remember(command_id, event.clone());
+                events.push(event);
+            }
+        }
+        tracing::info!("skipping duplicate command {}", command_id);
+        Ok(events)
+    }
+
+    async fn maybe_snapshot(&self, state: &mut AggregateState<A>) -> Result<(), ActorProcessingErr> {
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(());
+        };
+        if state.version - state.snapshot_version < snapshots.frequency {
+            return Ok(());
+        }
+
+        snapshots
+            .store
+            .save(Snapshot {
+                stream_id: state.stream_id.clone(),
+                version: state.version,
+                state: serde_json::to_value(&state.aggregate)?,
+            })
+            .await?;
+        state.snapshot_version = state.version;
+        tracing::debug!(version = state.version, "snapshot saved");
+        Ok(())
+    }
+}
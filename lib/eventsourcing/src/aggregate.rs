@@ -0,0 +1,23 @@
+use std::fmt::Debug;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Pure domain logic of an event-sourced aggregate.
+///
+/// Commands are validated against the current state and turned into events by
+/// [`Aggregate::handle_command`]. The state only ever changes by applying those
+/// events in [`Aggregate::apply_event`], starting from [`Default::default`].
+pub trait Aggregate: Clone + Default + Debug + Serialize + DeserializeOwned + Send + 'static {
+    type Command: Debug + Send + 'static;
+    type Event: Clone + Debug + Serialize + DeserializeOwned + Send + 'static;
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Schema version of [`Aggregate::Event`] recorded with new events. Bump
+    /// it when the serialized shape changes and register an
+    /// [`Upcaster`](crate::envelope::Upcaster) for the old version.
+    const EVENT_VERSION: u32 = 1;
+
+    fn handle_command(&self, command: Self::Command) -> Result<Vec<Self::Event>, Self::Error>;
+
+    fn apply_event(&mut self, event: Self::Event);
+}
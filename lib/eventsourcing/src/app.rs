@@ -0,0 +1,471 @@
+//! Wiring of the subsystems of a binary.
+//!
+//! An [`AppBuilder`] registers the aggregates, projections, event subscribers
+//! and schedules of a binary over one event store, and starts them as an
+//! [`App`]. Subsystems left out of the build by the feature profile are
+//! skipped with a warning rather than an error, so one `main` runs under
+//! every profile. An [`EmbeddedApp`] starts such a builder with the smallest
+//! setup, everything in one process.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ractor::concurrency::JoinHandle;
+use ractor::errors::SpawnErr;
+use ractor::{call_t, Actor, ActorCell, ActorProcessingErr, ActorRef};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::bus::{EventBus, EventHandler, EventSubscriber};
+#[cfg(feature = "scheduler")]
+use crate::ops::OpsLog;
+use crate::projection::{ProjectionActor, ProjectionMessage, Projector};
+#[cfg(feature = "scheduler")]
+use crate::scheduler::{CommandDispatcher, ScheduledCommand, Scheduler, SchedulerMessage};
+use crate::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use crate::store::{EventStore, InMemoryEventStore};
+use crate::{Aggregate, AggregateActor, AggregateRepository};
+
+const RPC_TIMEOUT_MS: u64 = 1000;
+
+/// A command sent to the aggregate of `stream_id` every `period`, the first
+/// time one period after it is scheduled.
+#[derive(Debug, Clone)]
+pub struct RecurringCommand {
+    /// A command already pending under this id is left as it is, so a
+    /// schedule kept in a persistent log is only set up by the first run.
+    pub schedule_id: String,
+    pub stream_id: String,
+    /// Serialized aggregate command.
+    pub command: Value,
+    pub period: Duration,
+}
+
+/// A subsystem of an [`App`] failed to start or to answer.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct AppError(ActorProcessingErr);
+
+/// Collects the subsystems of an [`App`] before starting them.
+pub struct AppBuilder {
+    store: Arc<dyn EventStore>,
+    bus: EventBus,
+    checkpoints: Arc<dyn SnapshotStore>,
+    /// Repositories keyed by the type id of their aggregate.
+    repositories: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    projections: Vec<Spawn>,
+    subscribers: Vec<Spawn>,
+    schedules: Vec<Schedule>,
+}
+
+/// Spawns a registered projection or subscriber once the app is built.
+type Spawn = Box<dyn FnOnce(Wiring) -> SpawnFuture + Send>;
+type SpawnFuture = Pin<Box<dyn Future<Output = Result<Started, SpawnErr>> + Send>>;
+
+/// What the actors of an app share.
+#[derive(Clone)]
+struct Wiring {
+    store: Arc<dyn EventStore>,
+    bus: EventBus,
+    checkpoints: Arc<dyn SnapshotStore>,
+}
+
+struct Started {
+    actor: ActorCell,
+    handle: JoinHandle<()>,
+    /// Type id of the projector and reference of a projection.
+    projection: Option<(TypeId, Box<dyn Any + Send + Sync>)>,
+}
+
+struct Schedule {
+    name: String,
+    commands: Vec<RecurringCommand>,
+    #[cfg(feature = "scheduler")]
+    dispatcher: Arc<dyn CommandDispatcher>,
+}
+
+/// Entry point of an app running in one process, e.g. a test or a small
+/// example: the builders it returns keep checkpoints in memory, and
+/// aggregates, projections and subscribers are registered on them as usual.
+///
+/// ```ignore
+/// let builder = EmbeddedApp::in_memory().with_aggregate::<Counter>();
+/// let app = builder.with_projection::<Total>().build().await?;
+/// ```
+pub struct EmbeddedApp;
+
+/// The running subsystems of a binary, see [`AppBuilder`].
+pub struct App {
+    store: Arc<dyn EventStore>,
+    bus: EventBus,
+    repositories: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    projections: Vec<Started>,
+    subscribers: Vec<Started>,
+    #[cfg(feature = "scheduler")]
+    schedulers: Vec<(ActorRef<SchedulerMessage>, JoinHandle<()>)>,
+}
+
+impl AppBuilder {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self {
+            bus: EventBus::for_store(&store),
+            store,
+            checkpoints: Arc::new(InMemorySnapshotStore::new()),
+            repositories: HashMap::new(),
+            projections: Vec::new(),
+            subscribers: Vec::new(),
+            schedules: Vec::new(),
+        }
+    }
+
+    /// Saves the checkpoints of the projections and subscribers in
+    /// `checkpoints`, so they resume where they stopped. They are kept in
+    /// memory otherwise, and rebuilt from the whole log on every start.
+    pub fn with_checkpoints(mut self, checkpoints: Arc<dyn SnapshotStore>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn EventStore> {
+        &self.store
+    }
+
+    /// Bus on which the registered aggregates publish their events.
+    pub fn bus(&self) -> &EventBus {
+        &self.bus
+    }
+
+    /// Hosts the aggregates of type `A`, publishing their events on the bus.
+    pub fn with_aggregate<A: Aggregate>(self) -> Self {
+        let actor = AggregateActor::<A>::new(Arc::clone(&self.store));
+        self.with_aggregate_actor(actor)
+    }
+
+    /// Like [`AppBuilder::with_aggregate`] with an actor set up beforehand,
+    /// e.g. with snapshots or an audit log.
+    pub fn with_aggregate_actor<A: Aggregate>(mut self, actor: AggregateActor<A>) -> Self {
+        let repository = AggregateRepository::new(actor.with_event_bus(self.bus.clone()));
+        self.repositories
+            .insert(TypeId::of::<A>(), Box::new(repository));
+        self
+    }
+
+    /// Repository of the aggregates of type `A`, if registered, e.g. to hand
+    /// to a subscriber before the app is built.
+    pub fn repository<A: Aggregate>(&self) -> Option<AggregateRepository<A>> {
+        repository(&self.repositories)
+    }
+
+    /// Maintains the projection `P`, see [`ProjectionActor`].
+    pub fn with_projection<P: Projector>(mut self) -> Self {
+        self.projections.push(Box::new(|wiring: Wiring| {
+            Box::pin(async move {
+                let (actor, handle) = Actor::spawn(
+                    None,
+                    ProjectionActor::<P>::new(wiring.store, wiring.checkpoints),
+                    (),
+                )
+                .await?;
+                Ok(Started {
+                    actor: actor.get_cell(),
+                    handle,
+                    projection: Some((TypeId::of::<P>(), Box::new(actor))),
+                })
+            })
+        }));
+        self
+    }
+
+    /// Feeds the events of `stream_types` to `handler`, e.g. a process
+    /// manager or a notifier, checkpointed under `name`, see
+    /// [`EventSubscriber`].
+    pub fn with_subscriber<H: EventHandler>(
+        mut self,
+        name: &str,
+        stream_types: &[&str],
+        handler: H,
+    ) -> Self {
+        let name = name.to_string();
+        let stream_types: Vec<String> = stream_types.iter().map(|t| t.to_string()).collect();
+        self.subscribers.push(Box::new(move |wiring: Wiring| {
+            Box::pin(async move {
+                let stream_types: Vec<&str> = stream_types.iter().map(String::as_str).collect();
+                let (actor, handle) = Actor::spawn(
+                    None,
+                    EventSubscriber::<H>::new(wiring.store, wiring.bus, &stream_types)
+                        .with_checkpoints(wiring.checkpoints, &name),
+                    handler,
+                )
+                .await?;
+                Ok(Started {
+                    actor: actor.get_cell(),
+                    handle,
+                    projection: None,
+                })
+            })
+        }));
+        self
+    }
+
+    /// Runs the schedule `name`, sending its commands with `dispatcher`, and
+    /// adds `commands` to it. Without the `scheduler` feature, no command is
+    /// sent.
+    #[cfg(feature = "scheduler")]
+    pub fn with_schedule(
+        mut self,
+        name: &str,
+        dispatcher: Arc<dyn CommandDispatcher>,
+        commands: Vec<RecurringCommand>,
+    ) -> Self {
+        self.schedules.push(Schedule {
+            name: name.to_string(),
+            commands,
+            dispatcher,
+        });
+        self
+    }
+
+    /// Runs the schedule `name`, sending its commands with `dispatcher`, and
+    /// adds `commands` to it. Without the `scheduler` feature, no command is
+    /// sent.
+    #[cfg(not(feature = "scheduler"))]
+    pub fn with_schedule<D>(
+        mut self,
+        name: &str,
+        _dispatcher: Arc<D>,
+        commands: Vec<RecurringCommand>,
+    ) -> Self {
+        self.schedules.push(Schedule {
+            name: name.to_string(),
+            commands,
+        });
+        self
+    }
+
+    /// Starts the subsystems compiled in: the projections, then the
+    /// subscribers, then the schedules, so that nothing sends a command
+    /// before what reacts to its events is running. Aggregate actors are
+    /// spawned on demand by their repositories.
+    ///
+    /// If one fails to start, those already started are shut down.
+    pub async fn build(self) -> Result<App, AppError> {
+        let wiring = Wiring {
+            store: Arc::clone(&self.store),
+            bus: self.bus.clone(),
+            checkpoints: self.checkpoints,
+        };
+        let mut app = App {
+            store: self.store,
+            bus: self.bus,
+            repositories: self.repositories,
+            projections: Vec::new(),
+            subscribers: Vec::new(),
+            #[cfg(feature = "scheduler")]
+            schedulers: Vec::new(),
+        };
+        let started: Result<(), AppError> = async {
+            for spawn in self.projections {
+                app.projections
+                    .push(spawn(wiring.clone()).await.map_err(AppError::spawn)?);
+            }
+            for spawn in self.subscribers {
+                app.subscribers
+                    .push(spawn(wiring.clone()).await.map_err(AppError::spawn)?);
+            }
+            for schedule in self.schedules {
+                #[cfg(feature = "scheduler")]
+                app.schedulers.push(
+                    start_schedule(&app.store, schedule)
+                        .await
+                        .map_err(AppError)?,
+                );
+                #[cfg(not(feature = "scheduler"))]
+                tracing::warn!(
+                    "built without the scheduler, the {} commands of the {} schedule are not sent",
+                    schedule.commands.len(),
+                    schedule.name
+                );
+            }
+            Ok(())
+        }
+        .await;
+        match started {
+            Ok(()) => Ok(app),
+            Err(err) => {
+                if let Err(err) = app.shutdown().await {
+                    tracing::warn!("failed to shut down a partially started app: {}", err);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+impl EmbeddedApp {
+    /// Builder over a fresh in-memory store.
+    pub fn in_memory() -> AppBuilder {
+        AppBuilder::new(Arc::new(InMemoryEventStore::new()))
+    }
+
+    /// Builder over `store`, e.g. an `eventstore_sqlite::SqliteEventStore` to
+    /// keep the log across runs.
+    pub fn with_store(store: Arc<dyn EventStore>) -> AppBuilder {
+        AppBuilder::new(store)
+    }
+}
+
+impl App {
+    pub fn builder(store: Arc<dyn EventStore>) -> AppBuilder {
+        AppBuilder::new(store)
+    }
+
+    pub fn store(&self) -> &Arc<dyn EventStore> {
+        &self.store
+    }
+
+    pub fn bus(&self) -> &EventBus {
+        &self.bus
+    }
+
+    /// Repository of the aggregates of type `A`, if registered.
+    pub fn repository<A: Aggregate>(&self) -> Option<AggregateRepository<A>> {
+        repository(&self.repositories)
+    }
+
+    /// Actor maintaining the projection `P`, if registered.
+    pub fn projection<P: Projector>(&self) -> Option<ActorRef<ProjectionMessage<P>>> {
+        self.projections.iter().find_map(|started| {
+            let (projector, actor) = started.projection.as_ref()?;
+            if *projector != TypeId::of::<P>() {
+                return None;
+            }
+            actor
+                .downcast_ref::<ActorRef<ProjectionMessage<P>>>()
+                .cloned()
+        })
+    }
+
+    /// Read model of the projection `P`, caught up with the log.
+    pub async fn query<P: Projector>(&self) -> Result<P, AppError> {
+        let Some(projection) = self.projection::<P>() else {
+            return Err(AppError(
+                format!("projection {} is not registered", P::NAME).into(),
+            ));
+        };
+        call_t!(projection, ProjectionMessage::Query, RPC_TIMEOUT_MS)
+            .map_err(|err| AppError(err.into()))
+    }
+
+    /// Schedule id and due time in milliseconds since the Unix epoch of the
+    /// pending commands of every schedule, none without the `scheduler`
+    /// feature.
+    #[cfg(feature = "scheduler")]
+    pub async fn scheduled(&self) -> Result<Vec<(String, u64)>, AppError> {
+        let mut scheduled = Vec::new();
+        for (scheduler, _) in &self.schedulers {
+            let pending = call_t!(scheduler, SchedulerMessage::List, RPC_TIMEOUT_MS)
+                .map_err(|err| AppError(err.into()))?;
+            for command in pending {
+                scheduled.push((command.schedule_id, command.due_at_ms));
+            }
+        }
+        Ok(scheduled)
+    }
+
+    /// Schedule id and due time in milliseconds since the Unix epoch of the
+    /// pending commands of every schedule, none without the `scheduler`
+    /// feature.
+    #[cfg(not(feature = "scheduler"))]
+    pub async fn scheduled(&self) -> Result<Vec<(String, u64)>, AppError> {
+        Ok(Vec::new())
+    }
+
+    /// Stops the subsystems in the reverse order of [`AppBuilder::build`]:
+    /// the schedules first, so no command is sent anymore, then the
+    /// subscribers once they have handled the events already delivered to
+    /// them, and the projections last. Aggregate actors are left to stop with
+    /// the process, every command they accepted is in the store.
+    pub async fn shutdown(self) -> Result<(), AppError> {
+        #[cfg(feature = "scheduler")]
+        for (scheduler, handle) in self.schedulers {
+            scheduler.stop(None);
+            handle.await.map_err(|err| AppError(err.into()))?;
+        }
+        for subscriber in self.subscribers {
+            // A subscriber already stopped has nothing left to drain.
+            if subscriber.actor.drain().is_err() {
+                tracing::debug!("subscriber {:?} already stopped", subscriber.actor.get_id());
+            }
+            subscriber
+                .handle
+                .await
+                .map_err(|err| AppError(err.into()))?;
+        }
+        for projection in self.projections {
+            projection.actor.stop(None);
+            projection
+                .handle
+                .await
+                .map_err(|err| AppError(err.into()))?;
+        }
+        Ok(())
+    }
+}
+
+impl AppError {
+    fn spawn(err: SpawnErr) -> Self {
+        Self(err.into())
+    }
+}
+
+fn repository<A: Aggregate>(
+    repositories: &HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+) -> Option<AggregateRepository<A>> {
+    repositories
+        .get(&TypeId::of::<A>())?
+        .downcast_ref::<AggregateRepository<A>>()
+        .cloned()
+}
+
+#[cfg(feature = "scheduler")]
+async fn start_schedule(
+    store: &Arc<dyn EventStore>,
+    schedule: Schedule,
+) -> Result<(ActorRef<SchedulerMessage>, JoinHandle<()>), ActorProcessingErr> {
+    let (scheduler, handle) = Actor::spawn(
+        None,
+        Scheduler::new(Arc::clone(store), schedule.dispatcher)
+            .with_ops_log(OpsLog::new(Arc::clone(store), "scheduler")),
+        schedule.name,
+    )
+    .await?;
+    let pending = call_t!(scheduler, SchedulerMessage::List, RPC_TIMEOUT_MS)?;
+    let now_ms = crate::now_ms();
+    for recurring in schedule.commands {
+        if pending
+            .iter()
+            .any(|command| command.schedule_id == recurring.schedule_id)
+        {
+            continue;
+        }
+        let every_ms = recurring.period.as_millis() as u64;
+        let command = ScheduledCommand {
+            schedule_id: recurring.schedule_id,
+            stream_id: recurring.stream_id,
+            command: recurring.command,
+            due_at_ms: now_ms + every_ms,
+            every_ms: Some(every_ms),
+        };
+        call_t!(
+            scheduler,
+            SchedulerMessage::Schedule,
+            RPC_TIMEOUT_MS,
+            command
+        )?;
+    }
+    Ok((scheduler, handle))
+}
@@ -0,0 +1,84 @@
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::EventEnvelope;
+use crate::store::{EventStore, ExpectedVersion, StoreError};
+
+pub const STREAM_TYPE: &str = "audit";
+
+/// Audit stream recording the commands received by the aggregate in
+/// `stream_id`.
+pub fn stream_id(stream_id: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, stream_id)
+}
+
+/// A command received by an aggregate, whether it was accepted or not.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandRecord {
+    pub stream_id: String,
+    /// Who sent the command, if the sender identified itself.
+    pub source: Option<String>,
+    /// Debug representation of the command.
+    pub command: String,
+    pub outcome: CommandOutcome,
+    /// Milliseconds since the Unix epoch.
+    pub recorded_at_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommandOutcome {
+    Accepted { events: usize },
+    Rejected { reason: String },
+}
+
+/// Keeps a [`CommandRecord`] for every command in a separate `audit/<stream>`
+/// stream per aggregate, so rejected commands leave a trace that the domain
+/// events alone don't.
+#[derive(Clone)]
+pub struct AuditLog {
+    store: Arc<dyn EventStore>,
+}
+
+impl AuditLog {
+    pub fn new(store: Arc<dyn EventStore>) -> Self {
+        Self { store }
+    }
+
+    /// Appends `record` in an [`EventEnvelope`], like any other event, so
+    /// that signing stores accept it.
+    pub async fn record(&self, record: &CommandRecord) -> Result<(), StoreError> {
+        let stream_id = stream_id(&record.stream_id);
+        let envelope = EventEnvelope::new(&stream_id, record, 1)?;
+        self.store
+            .append(
+                &stream_id,
+                ExpectedVersion::Any,
+                vec![serde_json::to_value(envelope)?],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Commands received by the aggregate in `stream_id`, in arrival order,
+    /// recorded at a time within `range` (milliseconds since the Unix epoch).
+    pub async fn query(
+        &self,
+        stream_id: &str,
+        range: impl RangeBounds<u64>,
+    ) -> Result<Vec<CommandRecord>, StoreError> {
+        let mut records = Vec::new();
+        for recorded in self
+            .store
+            .read_stream(&self::stream_id(stream_id), 0)
+            .await?
+        {
+            let record: CommandRecord = recorded.decode()?;
+            if range.contains(&record.recorded_at_ms) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
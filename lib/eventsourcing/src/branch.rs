@@ -0,0 +1,80 @@
+//! Sandboxed copies of streams for what-if analysis.
+
+use std::sync::Arc;
+
+use ractor::{Actor, SpawnErr};
+
+use crate::store::{EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent, StoreError};
+use crate::{Aggregate, AggregateActor, AggregateRef};
+
+/// A throwaway event store seeded with the history of real streams.
+///
+/// Forking copies events from a base store into the branch, which is an
+/// in-memory store of its own. Commands executed against the branch never
+/// touch the real history, and dropping the branch discards them. Read models
+/// of the branch can be built by a
+/// [`ProjectionActor`](crate::projection::ProjectionActor) over
+/// [`Branch::store`].
+#[derive(Default)]
+pub struct Branch {
+    store: Arc<InMemoryEventStore>,
+}
+
+impl Branch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies the events of `stream_id` up to and including `at_version` and
+    /// returns the version of the forked stream.
+    pub async fn fork(
+        &self,
+        base: &dyn EventStore,
+        stream_id: &str,
+        at_version: u64,
+    ) -> Result<u64, StoreError> {
+        self.fork_where(base, stream_id, |event| event.version <= at_version)
+            .await
+    }
+
+    /// Copies the events of `stream_id` accepted by `keep`, e.g. to see the
+    /// stream as if one event had never happened. Kept events are renumbered
+    /// consecutively.
+    pub async fn fork_where(
+        &self,
+        base: &dyn EventStore,
+        stream_id: &str,
+        mut keep: impl FnMut(&RecordedEvent) -> bool,
+    ) -> Result<u64, StoreError> {
+        let payloads: Vec<_> = base
+            .read_stream(stream_id, 0)
+            .await?
+            .into_iter()
+            .filter(|event| keep(event))
+            .map(|event| event.payload)
+            .collect();
+        if payloads.is_empty() {
+            return Ok(0);
+        }
+        self.store
+            .append(stream_id, ExpectedVersion::NoStream, payloads)
+            .await
+    }
+
+    pub fn store(&self) -> Arc<dyn EventStore> {
+        Arc::clone(&self.store) as Arc<dyn EventStore>
+    }
+
+    /// Spawns an unnamed actor for a forked stream, so it does not clash with
+    /// the registered actor of the real stream. It has no event bus, keeping
+    /// hypothetical events away from live subscribers.
+    pub async fn spawn<A: Aggregate>(&self, stream_id: &str) -> Result<AggregateRef<A>, SpawnErr> {
+        let (actor, _) = Actor::spawn(
+            None,
+            AggregateActor::<A>::new(self.store()),
+            stream_id.to_string(),
+        )
+        .await?;
+        Ok(actor)
+    }
+}
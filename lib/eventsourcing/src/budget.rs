@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+/// Events handled between two forced yields.
+pub const DEFAULT_YIELD_EVERY: usize = 128;
+/// Longest time spent without yielding, whatever the number of events.
+pub const DEFAULT_MAX_BUSY: Duration = Duration::from_millis(10);
+
+/// Execution budget for loops folding many events.
+///
+/// With the bundled stores, replaying a stream or catching up a projection
+/// never awaits anything that really suspends, so the loop would hold its
+/// tokio worker until done. Calling [`YieldBudget::tick`] once per event
+/// yields back to the scheduler every `yield_every` events, or sooner once
+/// `max_busy` has elapsed, so other actors keep running.
+#[derive(Debug)]
+pub struct YieldBudget {
+    yield_every: usize,
+    max_busy: Duration,
+    handled: usize,
+    busy_since: Instant,
+}
+
+impl Default for YieldBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_YIELD_EVERY, DEFAULT_MAX_BUSY)
+    }
+}
+
+impl YieldBudget {
+    pub fn new(yield_every: usize, max_busy: Duration) -> Self {
+        Self {
+            yield_every: yield_every.max(1),
+            max_busy,
+            handled: 0,
+            busy_since: Instant::now(),
+        }
+    }
+
+    /// Accounts for one event, yielding if the budget is spent.
+    pub async fn tick(&mut self) {
+        self.handled += 1;
+        if self.handled >= self.yield_every || self.busy_since.elapsed() >= self.max_busy {
+            tokio::task::yield_now().await;
+            self.handled = 0;
+            self.busy_since = Instant::now();
+        }
+    }
+}
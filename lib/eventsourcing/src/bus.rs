@@ -0,0 +1,295 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use ractor::{async_trait, pg, Actor, ActorProcessingErr, ActorRef};
+use tracing::Instrument;
+
+use crate::budget::YieldBudget;
+use crate::envelope;
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::store::{EventStore, RecordedEvent, StoreError};
+
+const GROUP_PREFIX: &str = "eventsourcing.bus";
+const CATCH_UP_BATCH_SIZE: usize = 256;
+
+/// Publishes committed events to subscribers via `ractor::pg` groups, one
+/// group per bus and stream type.
+///
+/// Delivery through the groups is best effort. [`EventSubscriber`] turns it
+/// into at-least-once delivery by reading anything it missed back from the
+/// store.
+///
+/// Publishers and subscribers only meet on a bus of the same name, and
+/// positions are only meaningful within one store, so each store needs its
+/// own bus, see [`EventBus::for_store`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventBus {
+    name: Arc<str>,
+}
+
+/// Stream type of a stream id, i.e. the part before the first `/`.
+pub fn stream_type(stream_id: &str) -> &str {
+    stream_id
+        .split_once('/')
+        .map_or(stream_id, |(stream_type, _)| stream_type)
+}
+
+/// Stream types this crate records about the system rather than the domain:
+/// audit trails, operational events, schedules and self-test probes.
+/// Operational events and schedules are listed with or without the `ops` and
+/// `scheduler` features, as another process may write them to the same log.
+pub const INTERNAL_STREAM_TYPES: &[&str] = &[
+    crate::audit::STREAM_TYPE,
+    "ops",
+    "schedule",
+    crate::selftest::STREAM_TYPE,
+];
+
+impl EventBus {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// Bus of the events of `store`, distinct from the bus of any other
+    /// store alive in the process.
+    pub fn for_store(store: &Arc<dyn EventStore>) -> Self {
+        Self::new(&format!("store-{:p}", Arc::as_ptr(store) as *const ()))
+    }
+
+    pub fn publish(&self, events: &[RecordedEvent]) {
+        for event in events {
+            for member in pg::get_members(&self.group(stream_type(&event.stream_id))) {
+                if let Err(err) = member.send_message(SubscriberMessage::Published(event.clone())) {
+                    tracing::debug!("failed to publish to {:?}: {}", member.get_id(), err);
+                }
+            }
+        }
+    }
+
+    pub fn subscribe(&self, stream_type: &str, subscriber: &ActorRef<SubscriberMessage>) {
+        pg::join(self.group(stream_type), vec![subscriber.get_cell()]);
+    }
+
+    fn group(&self, stream_type: &str) -> String {
+        format!("{}/{}/{}", GROUP_PREFIX, self.name, stream_type)
+    }
+}
+
+/// Span in which an event is handled, carrying its correlation id, see
+/// [`Correlation`](crate::envelope::Correlation).
+pub fn event_span(event: &RecordedEvent) -> tracing::Span {
+    tracing::info_span!(
+        "handle_event",
+        stream_id = %event.stream_id,
+        version = event.version,
+        correlation_id = envelope::correlation_id(&event.payload)
+    )
+}
+
+/// Reacts to committed events.
+///
+/// Read models should decode with [`RecordedEvent::decode_known`], so events
+/// added by a newer release running alongside are skipped rather than
+/// stopping the handler.
+#[async_trait]
+pub trait EventHandler: Send + 'static {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr>;
+}
+
+/// Actor feeding the events of some stream types to an [`EventHandler`].
+///
+/// It catches up from the store on start, then follows the bus. Whenever a
+/// published event is not the next one in the global log, the events in
+/// between are read back from the store, so nothing is skipped even if a
+/// publish is lost. Events at or before the last seen position are dropped.
+///
+/// Without checkpoints, every start catches up from the beginning of the
+/// log, or from [`EventSubscriber::starting_after`]. With them, see
+/// [`EventSubscriber::with_checkpoints`], it resumes after the last event
+/// handled before it stopped.
+pub struct EventSubscriber<H> {
+    store: Arc<dyn EventStore>,
+    bus: EventBus,
+    stream_types: Vec<String>,
+    checkpoints: Option<(Arc<dyn SnapshotStore>, String)>,
+    start_position: u64,
+    _handler: PhantomData<fn() -> H>,
+}
+
+/// Global position of the last event handled by a subscriber, saved in a
+/// snapshot store, if any, so it survives restarts.
+pub struct Checkpoint {
+    position: u64,
+    store: Option<(Arc<dyn SnapshotStore>, String)>,
+}
+
+impl Checkpoint {
+    /// Starts at `position` and is not saved.
+    pub fn at(position: u64) -> Self {
+        Self {
+            position,
+            store: None,
+        }
+    }
+
+    /// Loads the checkpoint saved in `store` under `id`, starting at 0 if
+    /// there is none.
+    pub async fn load(store: Arc<dyn SnapshotStore>, id: String) -> Result<Self, StoreError> {
+        let position = store
+            .load(&id)
+            .await?
+            .map_or(0, |checkpoint| checkpoint.version);
+        Ok(Self {
+            position,
+            store: Some((store, id)),
+        })
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Moves the checkpoint to `position` and saves it.
+    pub async fn advance(&mut self, position: u64) -> Result<(), StoreError> {
+        self.position = position;
+        if let Some((store, id)) = &self.store {
+            store
+                .save(Snapshot {
+                    stream_id: id.clone(),
+                    version: position,
+                    state: serde_json::Value::Null,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SubscriberMessage {
+    Published(RecordedEvent),
+}
+
+pub struct SubscriberState<H> {
+    handler: H,
+    /// Global position up to which the log has been scanned.
+    checkpoint: Checkpoint,
+}
+
+impl<H> EventSubscriber<H> {
+    pub fn new(store: Arc<dyn EventStore>, bus: EventBus, stream_types: &[&str]) -> Self {
+        Self {
+            store,
+            bus,
+            stream_types: stream_types.iter().map(|t| t.to_string()).collect(),
+            checkpoints: None,
+            start_position: 0,
+            _handler: PhantomData,
+        }
+    }
+
+    /// Skips the events at or before `position`, e.g. those a relay has
+    /// already forwarded. A saved checkpoint further along takes precedence.
+    pub fn starting_after(mut self, position: u64) -> Self {
+        self.start_position = position;
+        self
+    }
+
+    /// Saves the position reached in `checkpoints`, keyed
+    /// `subscriber/<name>`, after each event or batch of events handled.
+    ///
+    /// Only the position is saved. A handler keeping state must be able to
+    /// rebuild it, or be a [`Projector`](crate::projection::Projector).
+    pub fn with_checkpoints(mut self, checkpoints: Arc<dyn SnapshotStore>, name: &str) -> Self {
+        self.checkpoints = Some((checkpoints, format!("subscriber/{}", name)));
+        self
+    }
+}
+
+#[async_trait]
+impl<H: EventHandler> Actor for EventSubscriber<H> {
+    type Msg = SubscriberMessage;
+    type State = SubscriberState<H>;
+    type Arguments = H;
+
+    async fn pre_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        handler: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        // Join before catching up so events committed meanwhile are queued.
+        for stream_type in &self.stream_types {
+            self.bus.subscribe(stream_type, &myself);
+        }
+
+        let mut checkpoint = match &self.checkpoints {
+            Some((store, id)) => Checkpoint::load(Arc::clone(store), id.clone()).await?,
+            None => Checkpoint::at(0),
+        };
+        if checkpoint.position() < self.start_position {
+            checkpoint.position = self.start_position;
+        }
+        let mut state = SubscriberState {
+            handler,
+            checkpoint,
+        };
+        self.catch_up(&mut state).await?;
+        Ok(state)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            SubscriberMessage::Published(event) => {
+                if event.position == state.checkpoint.position() + 1 {
+                    state
+                        .handler
+                        .handle_event(&event)
+                        .instrument(event_span(&event))
+                        .await?;
+                    state.checkpoint.advance(event.position).await?;
+                } else if event.position > state.checkpoint.position() {
+                    self.catch_up(state).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<H: EventHandler> EventSubscriber<H> {
+    async fn catch_up(&self, state: &mut SubscriberState<H>) -> Result<(), ActorProcessingErr> {
+        let mut budget = YieldBudget::default();
+        loop {
+            let events = self
+                .store
+                .read_all(state.checkpoint.position(), CATCH_UP_BATCH_SIZE)
+                .await?;
+            let Some(last) = events.last() else {
+                return Ok(());
+            };
+            let last_position = last.position;
+
+            for event in events
+                .iter()
+                .filter(|event| {
+                    let event_type = stream_type(&event.stream_id);
+                    self.stream_types.iter().any(|t| t == event_type)
+                })
+            {
+                state
+                    .handler
+                    .handle_event(event)
+                    .instrument(event_span(event))
+                    .await?;
+                budget.tick().await;
+            }
+            state.checkpoint.advance(last_position).await?;
+        }
+    }
+}
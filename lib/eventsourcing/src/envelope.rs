@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::store::{RecordedEvent, StoreError};
+
+/// Persisted form of an event: the serialized event plus the metadata needed
+/// to migrate it once the event type changes shape.
+///
+/// Fields added by a newer release are ignored, so both releases can read
+/// each other's events during a rolling deploy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    /// Name of the Rust event type, without its module path.
+    pub event_type: String,
+    /// Schema version the payload was written with.
+    pub version: u32,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub stream_id: String,
+    /// Id assigned by the sender, used to detect redelivered events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// See [`Correlation`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<String>,
+    pub payload: Value,
+    /// Hex-encoded ed25519 signature over the rest of the envelope, see
+    /// `signing::SigningEventStore`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl EventEnvelope {
+    pub fn new<E: Serialize>(
+        stream_id: &str,
+        event: &E,
+        version: u32,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            event_type: event_type::<E>().to_string(),
+            version,
+            timestamp_ms: crate::now_ms(),
+            stream_id: stream_id.to_string(),
+            event_id: None,
+            correlation_id: None,
+            causation_id: None,
+            payload: serde_json::to_value(event)?,
+            signature: None,
+        })
+    }
+
+    pub fn with_event_id(mut self, event_id: impl Into<String>) -> Self {
+        self.event_id = Some(event_id.into());
+        self
+    }
+
+    pub fn with_correlation(mut self, correlation: &Correlation) -> Self {
+        self.correlation_id = Some(correlation.correlation_id.clone());
+        self.causation_id = correlation.causation_id.clone();
+        self
+    }
+
+    /// Reads the envelope of a recorded event of type `E`.
+    ///
+    /// Payloads appended without an envelope, see [`is_envelope`], are taken
+    /// as version 1 of the event, with no timestamp.
+    pub fn from_recorded<E>(recorded: &RecordedEvent) -> Result<Self, StoreError> {
+        if is_envelope(&recorded.payload) {
+            return Ok(Self::deserialize(&recorded.payload)?);
+        }
+        Ok(Self {
+            event_type: event_type::<E>().to_string(),
+            version: 1,
+            timestamp_ms: 0,
+            stream_id: recorded.stream_id.clone(),
+            event_id: None,
+            correlation_id: None,
+            causation_id: None,
+            payload: recorded.payload.clone(),
+            signature: None,
+        })
+    }
+
+    /// Decodes the payload after bringing it to the latest version known to
+    /// the [installed](Upcasters::install) upcasters.
+    pub fn decode<E: DeserializeOwned>(&self) -> Result<E, StoreError> {
+        let installed = Upcasters::installed().read().expect("upcasters poisoned");
+        if installed.is_latest(self) {
+            return Ok(E::deserialize(&self.payload)?);
+        }
+        Ok(E::deserialize(installed.upcast(self.clone()).payload)?)
+    }
+
+    /// The envelope brought to the latest version known to the
+    /// [installed](Upcasters::install) upcasters.
+    pub fn upcasted(self) -> Self {
+        let installed = Upcasters::installed().read().expect("upcasters poisoned");
+        if installed.is_latest(&self) {
+            return self;
+        }
+        installed.upcast(self)
+    }
+}
+
+/// Whether a recorded payload is a serialized [`EventEnvelope`] rather than
+/// an event appended without one.
+pub fn is_envelope(payload: &Value) -> bool {
+    payload.get("event_type").is_some() && payload.get("payload").is_some()
+}
+
+/// Event id of a serialized [`EventEnvelope`], without decoding the payload.
+pub fn event_id(payload: &Value) -> Option<&str> {
+    payload.get("event_id")?.as_str()
+}
+
+/// Correlation id of a serialized [`EventEnvelope`].
+pub fn correlation_id(payload: &Value) -> Option<&str> {
+    payload.get("correlation_id")?.as_str()
+}
+
+/// Links the events of a command to the work that led to it.
+///
+/// Every command and event following from one initial command share its
+/// correlation id. They are logged in spans carrying it, so a command, its
+/// events and the projection updates they trigger can be found together,
+/// and exported as one trace by `local-logging`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correlation {
+    pub correlation_id: String,
+    /// Event, as `stream_id@version`, whose handler sent the command. `None`
+    /// for the initial command.
+    pub causation_id: Option<String>,
+}
+
+impl Correlation {
+    /// Starts a new chain of work.
+    pub fn new() -> Self {
+        Self {
+            correlation_id: crate::new_id(),
+            causation_id: None,
+        }
+    }
+
+    /// Correlation of the commands sent while handling `event`.
+    pub fn caused_by(event: &RecordedEvent) -> Self {
+        Self {
+            correlation_id: correlation_id(&event.payload)
+                .map_or_else(crate::new_id, str::to_string),
+            causation_id: Some(format!("{}@{}", event.stream_id, event.version)),
+        }
+    }
+}
+
+impl Default for Correlation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event_type<E>() -> &'static str {
+    let name = std::any::type_name::<E>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Migrates the payload of an event type from one schema version to the
+/// next, e.g. renaming a variant or a field in the serialized JSON.
+pub trait Upcaster: Send + Sync + 'static {
+    fn upcast(&self, payload: Value) -> Value;
+}
+
+impl<F> Upcaster for F
+where
+    F: Fn(Value) -> Value + Send + Sync + 'static,
+{
+    fn upcast(&self, payload: Value) -> Value {
+        self(payload)
+    }
+}
+
+/// Registry of [`Upcaster`]s applied to events at read time.
+///
+/// An event is brought to the latest version known to the registry for its
+/// event type by running, for each version in between, the upcaster
+/// registered for it, if any.
+///
+/// Upcasters [installed](Upcasters::install) in the process apply to every
+/// decode, e.g. [`RecordedEvent::decode`] in projections and event handlers.
+#[derive(Clone, Default)]
+pub struct Upcasters {
+    upcasters: HashMap<(String, u32), Arc<dyn Upcaster>>,
+    latest_versions: HashMap<String, u32>,
+}
+
+impl Upcasters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds these upcasters to the ones applied by every decode in the
+    /// process, usually once at startup.
+    pub fn install(self) {
+        let mut installed = Self::installed().write().expect("upcasters poisoned");
+        for ((event_type, from_version), upcaster) in self.upcasters {
+            installed.insert(event_type, from_version, upcaster);
+        }
+    }
+
+    fn installed() -> &'static RwLock<Upcasters> {
+        static INSTALLED: OnceLock<RwLock<Upcasters>> = OnceLock::new();
+        INSTALLED.get_or_init(Default::default)
+    }
+
+    /// Registers `upcaster` to turn version `from_version` of `event_type`
+    /// into version `from_version + 1`.
+    pub fn register(
+        mut self,
+        event_type: impl Into<String>,
+        from_version: u32,
+        upcaster: impl Upcaster,
+    ) -> Self {
+        self.insert(event_type.into(), from_version, Arc::new(upcaster));
+        self
+    }
+
+    fn insert(&mut self, event_type: String, from_version: u32, upcaster: Arc<dyn Upcaster>) {
+        let latest_version = self.latest_versions.entry(event_type.clone()).or_default();
+        *latest_version = (*latest_version).max(from_version + 1);
+        self.upcasters.insert((event_type, from_version), upcaster);
+    }
+
+    fn is_latest(&self, envelope: &EventEnvelope) -> bool {
+        self.latest_versions
+            .get(&envelope.event_type)
+            .is_none_or(|&latest_version| envelope.version >= latest_version)
+    }
+
+    pub fn upcast(&self, mut envelope: EventEnvelope) -> EventEnvelope {
+        let latest_version = self
+            .latest_versions
+            .get(&envelope.event_type)
+            .copied()
+            .unwrap_or_default();
+        while envelope.version < latest_version {
+            let key = (envelope.event_type.clone(), envelope.version);
+            if let Some(upcaster) = self.upcasters.get(&key) {
+                envelope.payload = upcaster.upcast(envelope.payload);
+            }
+            envelope.version += 1;
+        }
+        envelope
+    }
+
+    /// Decodes a recorded event after bringing it to the latest version known
+    /// to these and the installed upcasters.
+    pub fn decode<E: DeserializeOwned>(&self, recorded: &RecordedEvent) -> Result<E, StoreError> {
+        self.upcast(EventEnvelope::from_recorded::<E>(recorded)?)
+            .decode()
+    }
+}
@@ -0,0 +1,157 @@
+//! Anonymized exports of the event log, e.g. to attach to a bug report.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde_json::Value;
+
+use crate::envelope;
+use crate::store::{EventStore, LogReader, RecordedEvent, StoreError};
+
+const EXPORT_BATCH_SIZE: usize = 256;
+const REDACTED: &str = "[redacted]";
+/// Envelope fields holding ids that may embed stream ids, see
+/// [`Anonymizer::id`].
+const ID_FIELDS: &[&str] = &["event_id", "correlation_id", "causation_id"];
+
+/// Rewrites events so they can be shared without leaking real data.
+///
+/// The part of a stream id after its stream type is always replaced by an
+/// alias, and so are the payload fields registered with
+/// [`Anonymizer::pseudonymize`]. Aliases are consistent: the same value gets
+/// the same alias wherever it appears, so an account number in a payload
+/// still matches the stream of that account. Fields registered with
+/// [`Anonymizer::redact`] are blanked out. The event, correlation and
+/// causation ids of an envelope are rewritten with the same aliases.
+///
+/// Field paths are JSON pointers into the event, i.e. the payload of its
+/// [`EventEnvelope`](crate::envelope::EventEnvelope) if it has one.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    redacted: Vec<String>,
+    pseudonymized: Vec<String>,
+    aliases: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn redact(mut self, pointer: impl Into<String>) -> Self {
+        self.redacted.push(pointer.into());
+        self
+    }
+
+    pub fn pseudonymize(mut self, pointer: impl Into<String>) -> Self {
+        self.pseudonymized.push(pointer.into());
+        self
+    }
+
+    /// Alias of `value`, allocated on first use.
+    pub fn alias(&mut self, value: &str) -> String {
+        let next = self.aliases.len() + 1;
+        self.aliases
+            .entry(value.to_string())
+            .or_insert_with(|| format!("anon-{}", next))
+            .clone()
+    }
+
+    pub fn stream_id(&mut self, stream_id: &str) -> String {
+        match stream_id.split_once('/') {
+            Some((stream_type, id)) => format!("{}/{}", stream_type, self.alias(id)),
+            None => self.alias(stream_id),
+        }
+    }
+
+    /// Alias of an event, correlation or causation id. Ids made of a stream
+    /// id, optionally followed by `@` and a version or due time, e.g. the
+    /// causation id `account/ACCOUNT1@2` or the scheduled occurrence
+    /// `monthly-fee/ACCOUNT1@1700000000000`, keep their shape with the
+    /// stream id anonymized. Other ids are aliased whole, as senders may
+    /// build them from real data.
+    pub fn id(&mut self, id: &str) -> String {
+        let (stream_id, suffix) = match id.rsplit_once('@') {
+            Some((stream_id, suffix)) => (stream_id, Some(suffix)),
+            None => (id, None),
+        };
+        if !stream_id.contains('/') {
+            return self.alias(id);
+        }
+        let stream_id = self.stream_id(stream_id);
+        match suffix {
+            Some(suffix) => format!("{}@{}", stream_id, suffix),
+            None => stream_id,
+        }
+    }
+
+    pub fn anonymize(&mut self, event: &RecordedEvent) -> RecordedEvent {
+        let stream_id = self.stream_id(&event.stream_id);
+        let mut payload = event.payload.clone();
+        if envelope::is_envelope(&payload) {
+            payload["stream_id"] = Value::String(stream_id.clone());
+            for field in ID_FIELDS {
+                if let Some(Value::String(id)) = payload.get(*field) {
+                    let id = self.id(id);
+                    payload[*field] = Value::String(id);
+                }
+            }
+            // The signature no longer matches the rewritten envelope.
+            if let Some(fields) = payload.as_object_mut() {
+                fields.remove("signature");
+            }
+            self.anonymize_fields(&mut payload["payload"]);
+        } else {
+            self.anonymize_fields(&mut payload);
+        }
+        RecordedEvent {
+            stream_id,
+            version: event.version,
+            position: event.position,
+            payload,
+        }
+    }
+
+    fn anonymize_fields(&mut self, payload: &mut Value) {
+        for pointer in &self.redacted {
+            if let Some(field) = payload.pointer_mut(pointer) {
+                *field = Value::String(REDACTED.to_string());
+            }
+        }
+        for pointer in self.pseudonymized.clone() {
+            if let Some(field) = payload.pointer_mut(&pointer) {
+                let value = match &*field {
+                    Value::String(value) => value.clone(),
+                    other => other.to_string(),
+                };
+                *field = Value::String(self.alias(&value));
+            }
+        }
+    }
+}
+
+/// Writes the anonymized events of the streams accepted by `select` to `out`,
+/// one JSON document per line, and returns how many were written.
+///
+/// Global positions are renumbered from 1, so the output can be opened as a
+/// [`JsonLinesEventStore`](crate::store::JsonLinesEventStore).
+pub async fn export(
+    store: &dyn EventStore,
+    mut select: impl FnMut(&str) -> bool,
+    anonymizer: &mut Anonymizer,
+    out: &mut impl Write,
+) -> Result<usize, StoreError> {
+    let mut log = LogReader::new(store, 0, EXPORT_BATCH_SIZE);
+    let mut exported = 0;
+    while let Some(events) = log.next_batch().await? {
+        for event in events.iter().filter(|event| select(&event.stream_id)) {
+            exported += 1;
+            let mut event = anonymizer.anonymize(event);
+            event.position = exported as u64;
+            serde_json::to_writer(&mut *out, &event)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(exported)
+}
@@ -0,0 +1,46 @@
+mod actor;
+mod aggregate;
+pub mod app;
+pub mod audit;
+pub mod branch;
+pub mod budget;
+pub mod bus;
+pub mod envelope;
+pub mod export;
+#[cfg(feature = "ops")]
+pub mod ops;
+pub mod projection;
+mod repository;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod selftest;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod snapshot;
+pub mod stats;
+pub mod store;
+pub mod testing;
+
+pub use actor::{AggregateActor, AggregateMessage, AggregateState, CommandResult};
+pub use aggregate::Aggregate;
+pub use repository::{AggregateRef, AggregateRepository};
+
+/// Id unique across processes for all practical purposes.
+pub(crate) fn new_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{:x}-{:x}-{:x}",
+        now_ms(),
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
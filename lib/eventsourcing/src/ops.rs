@@ -0,0 +1,142 @@
+//! Operational history of the system itself, e.g. actors stopped by an error
+//! or scheduled commands that could not be sent.
+//!
+//! Operational events are appended to `ops/<component>` streams of the same
+//! store as the domain events, so they can be projected, exported and
+//! inspected with the same tools.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+use ractor::{async_trait, ActorProcessingErr};
+use serde::{Deserialize, Serialize};
+
+use crate::bus::{stream_type, EventHandler};
+use crate::envelope::EventEnvelope;
+use crate::projection::Projector;
+use crate::store::{EventStore, ExpectedVersion, RecordedEvent, StoreError};
+
+pub const STREAM_TYPE: &str = "ops";
+
+/// Events kept by [`OpsHistory`], the oldest are dropped first.
+pub const RECENT_LIMIT: usize = 100;
+
+/// Stream of the operational events of `component`.
+pub fn stream_id(component: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, component)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpsEvent {
+    /// An aggregate actor stopped on an error while rehydrating or handling a
+    /// command, e.g. a store failure, an event that no longer decodes or a
+    /// concurrent writer. It is respawned on next use.
+    ActorFailed { stream_id: String, reason: String },
+    /// A scheduled command could not be sent. It is retried on the next tick,
+    /// recorded once per occurrence.
+    DispatchFailed {
+        schedule_id: String,
+        stream_id: String,
+        reason: String,
+    },
+    /// A scheduled command that does not deserialize is no longer sent, see
+    /// `scheduler::ScheduleEvent::Parked`.
+    CommandParked {
+        schedule_id: String,
+        stream_id: String,
+        reason: String,
+    },
+}
+
+impl OpsEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OpsEvent::ActorFailed { .. } => "ActorFailed",
+            OpsEvent::DispatchFailed { .. } => "DispatchFailed",
+            OpsEvent::CommandParked { .. } => "CommandParked",
+        }
+    }
+}
+
+/// Appends the operational events of a component to its `ops/<component>`
+/// stream.
+#[derive(Clone)]
+pub struct OpsLog {
+    store: Arc<dyn EventStore>,
+    stream_id: String,
+}
+
+impl OpsLog {
+    pub fn new(store: Arc<dyn EventStore>, component: &str) -> Self {
+        Self {
+            store,
+            stream_id: stream_id(component),
+        }
+    }
+
+    pub async fn record(&self, event: &OpsEvent) -> Result<(), StoreError> {
+        let envelope = EventEnvelope::new(&self.stream_id, event, 1)?;
+        self.store
+            .append(
+                &self.stream_id,
+                ExpectedVersion::Any,
+                vec![serde_json::to_value(envelope)?],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Records `event`, only logging a failure to do so, as it usually comes
+    /// with the failure being recorded.
+    pub(crate) async fn record_or_log(&self, event: OpsEvent) {
+        if let Err(err) = self.record(&event).await {
+            tracing::error!("failed to record {:?}: {}", event, err);
+        }
+    }
+}
+
+/// An operational event with the stream and time it was recorded at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpsEntry {
+    pub stream_id: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub event: OpsEvent,
+}
+
+/// Read model of the operational history: how many events of each kind were
+/// recorded and the last [`RECENT_LIMIT`] of them, oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpsHistory {
+    pub counts: BTreeMap<String, u64>,
+    pub recent: VecDeque<OpsEntry>,
+}
+
+impl Projector for OpsHistory {
+    const NAME: &'static str = "ops-history";
+}
+
+#[async_trait]
+impl EventHandler for OpsHistory {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        if stream_type(&event.stream_id) != STREAM_TYPE {
+            return Ok(());
+        }
+        // Kinds added by a newer release are skipped rather than stopping
+        // the projection.
+        let Some(ops_event) = event.decode_known::<OpsEvent>()? else {
+            return Ok(());
+        };
+        let envelope = EventEnvelope::from_recorded::<OpsEvent>(event)?;
+        *self.counts.entry(ops_event.kind().to_string()).or_default() += 1;
+        if self.recent.len() == RECENT_LIMIT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(OpsEntry {
+            stream_id: event.stream_id.clone(),
+            timestamp_ms: envelope.timestamp_ms,
+            event: ops_event,
+        });
+        Ok(())
+    }
+}
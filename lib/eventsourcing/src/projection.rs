@@ -0,0 +1,157 @@
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::Instrument;
+
+use crate::budget::YieldBudget;
+use crate::bus::{event_span, EventHandler};
+use crate::snapshot::{Snapshot, SnapshotStore};
+use crate::store::EventStore;
+
+const CATCH_UP_BATCH_SIZE: usize = 256;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A read model built by folding the global event log.
+///
+/// Events are fed through [`EventHandler::handle_event`] in global order. The
+/// projection is serialized together with its checkpoint, so a restarted
+/// projection resumes with a read model matching the checkpoint.
+pub trait Projector: EventHandler + Clone + Debug + Default + Serialize + DeserializeOwned {
+    /// Unique name used as the checkpoint key.
+    const NAME: &'static str;
+}
+
+/// Actor maintaining a [`Projector`] from the global log of an [`EventStore`].
+///
+/// It polls the store periodically, and also catches up before answering a
+/// query so readers see every event committed before they asked. After each
+/// batch, the projection and the global position it has reached are saved
+/// as a snapshot keyed `projection/<NAME>`.
+pub struct ProjectionActor<P> {
+    store: Arc<dyn EventStore>,
+    checkpoints: Arc<dyn SnapshotStore>,
+    poll_interval: Duration,
+    _projector: PhantomData<fn() -> P>,
+}
+
+#[derive(Debug)]
+pub enum ProjectionMessage<P> {
+    CatchUp,
+    Query(RpcReplyPort<P>),
+}
+
+pub struct ProjectionState<P> {
+    projection: P,
+    position: u64,
+}
+
+impl<P: Projector> ProjectionActor<P> {
+    pub fn new(store: Arc<dyn EventStore>, checkpoints: Arc<dyn SnapshotStore>) -> Self {
+        Self {
+            store,
+            checkpoints,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            _projector: PhantomData,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Key of the checkpoint in the snapshot store.
+    pub fn checkpoint_id() -> String {
+        format!("projection/{}", P::NAME)
+    }
+
+    async fn catch_up(&self, state: &mut ProjectionState<P>) -> Result<(), ActorProcessingErr> {
+        let mut budget = YieldBudget::default();
+        loop {
+            let events = self
+                .store
+                .read_all(state.position, CATCH_UP_BATCH_SIZE)
+                .await?;
+            if events.is_empty() {
+                return Ok(());
+            }
+
+            for event in &events {
+                state
+                    .projection
+                    .handle_event(event)
+                    .instrument(event_span(event))
+                    .await?;
+                state.position = event.position;
+                budget.tick().await;
+            }
+            self.checkpoints
+                .save(Snapshot {
+                    stream_id: Self::checkpoint_id(),
+                    version: state.position,
+                    state: serde_json::to_value(&state.projection)?,
+                })
+                .await?;
+            tracing::debug!(projection = P::NAME, position = state.position, "checkpoint saved");
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Projector> Actor for ProjectionActor<P> {
+    type Msg = ProjectionMessage<P>;
+    type State = ProjectionState<P>;
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let mut state = match self.checkpoints.load(&Self::checkpoint_id()).await? {
+            Some(checkpoint) => ProjectionState {
+                projection: serde_json::from_value(checkpoint.state)?,
+                position: checkpoint.version,
+            },
+            None => ProjectionState {
+                projection: P::default(),
+                position: 0,
+            },
+        };
+        tracing::info!(
+            projection = P::NAME,
+            position = state.position,
+            "resuming projection"
+        );
+
+        self.catch_up(&mut state).await?;
+        Ok(state)
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        myself.send_interval(self.poll_interval, || ProjectionMessage::CatchUp);
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        self.catch_up(state).await?;
+        if let ProjectionMessage::Query(reply_port) = message {
+            let _ = reply_port.send(state.projection.clone());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use ractor::errors::{MessagingErr, RactorErr, SpawnErr};
+use ractor::rpc::CallResult;
+use ractor::{call_t, Actor, ActorRef, RpcReplyPort};
+
+use crate::envelope::Correlation;
+use crate::{Aggregate, AggregateActor, AggregateMessage, CommandResult};
+
+pub type AggregateRef<A> = ActorRef<AggregateMessage<A>>;
+
+/// Locates the actor hosting a stream, spawning it on first use.
+///
+/// Actors are registered under a name derived from the aggregate type and the
+/// stream id, so every repository of the same aggregate type in the process
+/// shares them.
+pub struct AggregateRepository<A> {
+    actor: AggregateActor<A>,
+    source: Option<String>,
+}
+
+impl<A> Clone for AggregateRepository<A> {
+    fn clone(&self) -> Self {
+        Self {
+            actor: self.actor.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+impl<A: Aggregate> AggregateRepository<A> {
+    pub fn new(actor: AggregateActor<A>) -> Self {
+        Self {
+            actor,
+            source: None,
+        }
+    }
+
+    /// Attributes the commands sent through this repository to `source` in
+    /// the audit log.
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn where_is(stream_id: &str) -> Option<AggregateRef<A>> {
+        AggregateRef::<A>::where_is(Self::via(stream_id))
+    }
+
+    pub async fn get(&self, stream_id: &str) -> Result<AggregateRef<A>, SpawnErr> {
+        if let Some(actor) = Self::where_is(stream_id) {
+            return Ok(actor);
+        }
+        match Actor::spawn(
+            Some(Self::via(stream_id)),
+            self.actor.clone(),
+            stream_id.to_string(),
+        )
+        .await
+        {
+            Ok((actor, _)) => Ok(actor),
+            // Lost a race against a concurrent spawn of the same stream.
+            Err(SpawnErr::ActorAlreadyRegistered(name)) => {
+                Self::where_is(stream_id).ok_or(SpawnErr::ActorAlreadyRegistered(name))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sends a command without waiting for its outcome.
+    pub async fn execute(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+    ) -> Result<(), RactorErr<AggregateMessage<A>>> {
+        let actor = self.get(stream_id).await?;
+        actor.send_message(self.message(command, None, None, None))?;
+        Ok(())
+    }
+
+    /// Sends a command continuing the work of `correlation`, e.g. from an
+    /// event handler, without waiting for its outcome.
+    pub async fn execute_correlated(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+        correlation: Correlation,
+    ) -> Result<(), RactorErr<AggregateMessage<A>>> {
+        let actor = self.get(stream_id).await?;
+        actor.send_message(self.message(command, Some(correlation), None, None))?;
+        Ok(())
+    }
+
+    pub async fn execute_with_reply(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+        timeout: Duration,
+    ) -> Result<CommandResult<A>, RactorErr<AggregateMessage<A>>> {
+        self.call(stream_id, command, None, None, timeout).await
+    }
+
+    /// Sends a command continuing the work of `correlation` and waits for its
+    /// outcome, e.g. from an event handler that must not lose the command.
+    pub async fn execute_correlated_with_reply(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+        correlation: Correlation,
+        timeout: Duration,
+    ) -> Result<CommandResult<A>, RactorErr<AggregateMessage<A>>> {
+        self.call(stream_id, command, Some(correlation), None, timeout)
+            .await
+    }
+
+    /// Like [`AggregateRepository::execute_correlated_with_reply`], recording
+    /// the events under `command_id`, so that resending the command after a
+    /// transient failure does not apply it twice.
+    pub async fn execute_once(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+        command_id: String,
+        correlation: Correlation,
+        timeout: Duration,
+    ) -> Result<CommandResult<A>, RactorErr<AggregateMessage<A>>> {
+        self.call(
+            stream_id,
+            command,
+            Some(correlation),
+            Some(command_id),
+            timeout,
+        )
+        .await
+    }
+
+    async fn call(
+        &self,
+        stream_id: &str,
+        command: A::Command,
+        correlation: Option<Correlation>,
+        command_id: Option<String>,
+        timeout: Duration,
+    ) -> Result<CommandResult<A>, RactorErr<AggregateMessage<A>>> {
+        let actor = self.get(stream_id).await?;
+        let result = actor
+            .call(
+                |reply_port| self.message(command, correlation, command_id, Some(reply_port)),
+                Some(timeout),
+            )
+            .await?;
+        match result {
+            CallResult::Success(result) => Ok(result),
+            CallResult::Timeout => Err(RactorErr::Timeout),
+            CallResult::SenderError => Err(RactorErr::from(MessagingErr::ChannelClosed)),
+        }
+    }
+
+    pub async fn get_state(
+        &self,
+        stream_id: &str,
+        timeout: Duration,
+    ) -> Result<A, RactorErr<AggregateMessage<A>>> {
+        let actor = self.get(stream_id).await?;
+        call_t!(
+            actor,
+            AggregateMessage::GetState,
+            timeout.as_millis() as u64
+        )
+    }
+
+    fn message(
+        &self,
+        command: A::Command,
+        correlation: Option<Correlation>,
+        command_id: Option<String>,
+        reply_port: Option<RpcReplyPort<CommandResult<A>>>,
+    ) -> AggregateMessage<A> {
+        match (&self.source, correlation, command_id, reply_port) {
+            (None, None, None, None) => AggregateMessage::Execute(command),
+            (None, None, None, Some(reply_port)) => {
+                AggregateMessage::ExecuteWithReply(command, reply_port)
+            }
+            (source, correlation, command_id, reply_port) => AggregateMessage::ExecuteFrom {
+                source: source.clone(),
+                correlation,
+                command_id,
+                command,
+                reply_port,
+            },
+        }
+    }
+
+    fn via(stream_id: &str) -> String {
+        format!("{}/{}", std::any::type_name::<A>(), stream_id)
+    }
+}
@@ -0,0 +1,407 @@
+//! Commands executed at a later time, e.g. a monthly fee per account.
+//!
+//! The schedule is itself event-sourced in a `schedule/<name>` stream, so
+//! pending commands survive restarts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::envelope::{Correlation, EventEnvelope};
+use crate::ops::{OpsEvent, OpsLog};
+use crate::store::{EventStore, ExpectedVersion};
+use crate::{Aggregate, AggregateRepository};
+
+pub const STREAM_TYPE: &str = "schedule";
+
+const DEFAULT_RESOLUTION: Duration = Duration::from_secs(1);
+/// How long [`AggregateRepository`] waits for a dispatched command.
+const DISPATCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Attempts at dispatching a command that does not deserialize before it is
+/// parked. It may have been scheduled by a newer release that is still
+/// rolling out.
+const MAX_INVALID_ATTEMPTS: u32 = 3;
+
+/// A command to send to the aggregate in `stream_id` once `due_at_ms` has
+/// passed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledCommand {
+    /// Scheduling again under the same id replaces the pending command.
+    pub schedule_id: String,
+    pub stream_id: String,
+    /// Serialized aggregate command, see [`CommandDispatcher`].
+    pub command: Value,
+    /// Milliseconds since the Unix epoch.
+    pub due_at_ms: u64,
+    /// Period in milliseconds of a recurring command.
+    pub every_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleEvent {
+    Scheduled(ScheduledCommand),
+    /// The occurrence due at `due_at_ms` was sent. A recurring command is
+    /// due again one period later, others are done.
+    Fired {
+        schedule_id: String,
+        due_at_ms: u64,
+    },
+    Cancelled {
+        schedule_id: String,
+    },
+    /// The command did not deserialize in several attempts at the occurrence
+    /// due at `due_at_ms` and is no longer sent. Scheduling it again resumes
+    /// it.
+    Parked {
+        schedule_id: String,
+        due_at_ms: u64,
+        reason: String,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum DispatchError {
+    /// The command is not one of the aggregate, e.g. it was scheduled by a
+    /// newer release.
+    #[error("invalid command: {0}")]
+    InvalidCommand(#[from] serde_json::Error),
+    /// Retried on the next tick.
+    #[error(transparent)]
+    Failed(ActorProcessingErr),
+}
+
+/// Sends a scheduled command to its aggregate and waits for it to be
+/// handled.
+///
+/// `occurrence_id` is the same for every attempt at one occurrence of a
+/// command, and different for the next occurrence of a recurring one.
+/// Dispatchers should record it as the event id of the events the command
+/// produces, so that an attempt retried after the events were persisted,
+/// e.g. because the scheduler crashed before recording it, is not applied
+/// twice.
+#[async_trait]
+pub trait CommandDispatcher: Send + Sync + 'static {
+    async fn dispatch(
+        &self,
+        stream_id: &str,
+        command: Value,
+        occurrence_id: &str,
+    ) -> Result<(), DispatchError>;
+}
+
+/// Commands rejected by the aggregate are logged and not retried. The
+/// occurrence id is recorded as the command id and the causation of the
+/// command.
+#[async_trait]
+impl<A: Aggregate> CommandDispatcher for AggregateRepository<A>
+where
+    A::Command: DeserializeOwned + Sync,
+{
+    async fn dispatch(
+        &self,
+        stream_id: &str,
+        command: Value,
+        occurrence_id: &str,
+    ) -> Result<(), DispatchError> {
+        let correlation = Correlation {
+            causation_id: Some(occurrence_id.to_string()),
+            ..Correlation::new()
+        };
+        let result = self
+            .execute_once(
+                stream_id,
+                serde_json::from_value(command)?,
+                occurrence_id.to_string(),
+                correlation,
+                DISPATCH_TIMEOUT,
+            )
+            .await
+            .map_err(|err| DispatchError::Failed(err.into()))?;
+        if let Err(rejection) = result {
+            tracing::warn!("scheduled command on {} rejected: {}", stream_id, rejection);
+        }
+        Ok(())
+    }
+}
+
+/// Id of the occurrence of the command `schedule_id` due at `due_at_ms`,
+/// see [`CommandDispatcher`].
+pub fn occurrence_id(schedule_id: &str, due_at_ms: u64) -> String {
+    format!("{}@{}", schedule_id, due_at_ms)
+}
+
+/// Actor firing the due commands of a schedule.
+///
+/// Due commands are checked every `resolution`, using the ractor interval
+/// timer. A command is recorded as fired only once its aggregate has handled
+/// it, so delivery is at-least-once: a crash in between sends it again after
+/// the restart, under the same occurrence id. Occurrences missed while the
+/// scheduler was down are sent one per tick.
+pub struct Scheduler<D: ?Sized> {
+    store: Arc<dyn EventStore>,
+    dispatcher: Arc<D>,
+    resolution: Duration,
+    ops: Option<OpsLog>,
+}
+
+#[derive(Debug)]
+pub enum SchedulerMessage {
+    /// Replies once the command is persisted in the schedule.
+    Schedule(ScheduledCommand, RpcReplyPort<()>),
+    /// Replies whether a pending command was cancelled.
+    Cancel(String, RpcReplyPort<bool>),
+    /// Pending commands, ordered by schedule id.
+    List(RpcReplyPort<Vec<ScheduledCommand>>),
+    Tick,
+}
+
+pub struct SchedulerState {
+    stream_id: String,
+    version: u64,
+    pending: BTreeMap<String, ScheduledCommand>,
+    /// Attempts at dispatching commands that did not deserialize, by schedule
+    /// id. Not persisted, a restart allows new attempts.
+    invalid_attempts: HashMap<String, u32>,
+    /// Occurrence, by schedule id, whose failure to dispatch was recorded in
+    /// the ops log, so retries on later ticks are not recorded again.
+    failed_occurrences: HashMap<String, u64>,
+}
+
+impl<D: ?Sized> Scheduler<D> {
+    pub fn new(store: Arc<dyn EventStore>, dispatcher: Arc<D>) -> Self {
+        Self {
+            store,
+            dispatcher,
+            resolution: DEFAULT_RESOLUTION,
+            ops: None,
+        }
+    }
+
+    /// How often due commands are checked, one second by default.
+    pub fn with_resolution(mut self, resolution: Duration) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Records in `ops` the commands that could not be dispatched.
+    pub fn with_ops_log(mut self, ops: OpsLog) -> Self {
+        self.ops = Some(ops);
+        self
+    }
+}
+
+/// Stream of the schedule named `name`.
+pub fn stream_id(name: &str) -> String {
+    format!("{}/{}", STREAM_TYPE, name)
+}
+
+#[async_trait]
+impl<D: CommandDispatcher + ?Sized> Actor for Scheduler<D> {
+    type Msg = SchedulerMessage;
+    type State = SchedulerState;
+    /// Name of the schedule.
+    type Arguments = String;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        name: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let mut state = SchedulerState {
+            stream_id: stream_id(&name),
+            version: 0,
+            pending: BTreeMap::new(),
+            invalid_attempts: HashMap::new(),
+            failed_occurrences: HashMap::new(),
+        };
+        for recorded in self.store.read_stream(&state.stream_id, 0).await? {
+            state.apply(recorded.decode()?);
+            state.version = recorded.version;
+        }
+        tracing::debug!(
+            stream_id = %state.stream_id,
+            pending = state.pending.len(),
+            "schedule loaded"
+        );
+        Ok(state)
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        myself.send_interval(self.resolution, || SchedulerMessage::Tick);
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            SchedulerMessage::Schedule(command, reply_port) => {
+                if state.pending.get(&command.schedule_id) != Some(&command) {
+                    self.persist(state, ScheduleEvent::Scheduled(command))
+                        .await?;
+                }
+                let _ = reply_port.send(());
+            }
+            SchedulerMessage::Cancel(schedule_id, reply_port) => {
+                let pending = state.pending.contains_key(&schedule_id);
+                if pending {
+                    self.persist(state, ScheduleEvent::Cancelled { schedule_id })
+                        .await?;
+                }
+                let _ = reply_port.send(pending);
+            }
+            SchedulerMessage::List(reply_port) => {
+                let _ = reply_port.send(state.pending.values().cloned().collect());
+            }
+            SchedulerMessage::Tick => self.fire_due(state).await?,
+        }
+        Ok(())
+    }
+}
+
+impl<D: CommandDispatcher + ?Sized> Scheduler<D> {
+    async fn fire_due(&self, state: &mut SchedulerState) -> Result<(), ActorProcessingErr> {
+        let now_ms = crate::now_ms();
+        let due: Vec<ScheduledCommand> = state
+            .pending
+            .values()
+            .filter(|command| command.due_at_ms <= now_ms)
+            .cloned()
+            .collect();
+        for command in due {
+            let occurrence_id = occurrence_id(&command.schedule_id, command.due_at_ms);
+            let event = match self
+                .dispatcher
+                .dispatch(&command.stream_id, command.command, &occurrence_id)
+                .await
+            {
+                Ok(()) => ScheduleEvent::Fired {
+                    schedule_id: command.schedule_id,
+                    due_at_ms: command.due_at_ms,
+                },
+                Err(DispatchError::InvalidCommand(err))
+                    if state.invalid_attempt(&command.schedule_id) >= MAX_INVALID_ATTEMPTS =>
+                {
+                    tracing::error!("parking {}: {}", command.schedule_id, err);
+                    if let Some(ops) = &self.ops {
+                        ops.record_or_log(OpsEvent::CommandParked {
+                            schedule_id: command.schedule_id.clone(),
+                            stream_id: command.stream_id,
+                            reason: err.to_string(),
+                        })
+                        .await;
+                    }
+                    ScheduleEvent::Parked {
+                        schedule_id: command.schedule_id,
+                        due_at_ms: command.due_at_ms,
+                        reason: err.to_string(),
+                    }
+                }
+                // Left pending and retried on the next tick.
+                Err(err) => {
+                    if !state.dispatch_failed(&command.schedule_id, command.due_at_ms) {
+                        tracing::debug!("failed again to dispatch {}: {}", occurrence_id, err);
+                        continue;
+                    }
+                    tracing::warn!("failed to dispatch {}: {}", occurrence_id, err);
+                    if let Some(ops) = &self.ops {
+                        ops.record_or_log(OpsEvent::DispatchFailed {
+                            schedule_id: command.schedule_id,
+                            stream_id: command.stream_id,
+                            reason: err.to_string(),
+                        })
+                        .await;
+                    }
+                    continue;
+                }
+            };
+            self.persist(state, event).await?;
+        }
+        Ok(())
+    }
+
+    async fn persist(
+        &self,
+        state: &mut SchedulerState,
+        event: ScheduleEvent,
+    ) -> Result<(), ActorProcessingErr> {
+        let envelope = EventEnvelope::new(&state.stream_id, &event, 1)?;
+        state.version = self
+            .store
+            .append(
+                &state.stream_id,
+                ExpectedVersion::Exact(state.version),
+                vec![serde_json::to_value(envelope)?],
+            )
+            .await?;
+        state.apply(event);
+        Ok(())
+    }
+}
+
+impl SchedulerState {
+    /// Counts a failed attempt at dispatching an invalid command and returns
+    /// the attempts so far.
+    fn invalid_attempt(&mut self, schedule_id: &str) -> u32 {
+        let attempts = self
+            .invalid_attempts
+            .entry(schedule_id.to_string())
+            .or_default();
+        *attempts += 1;
+        *attempts
+    }
+
+    /// Notes a failed attempt at dispatching the occurrence due at
+    /// `due_at_ms` and returns whether it is the first one.
+    fn dispatch_failed(&mut self, schedule_id: &str, due_at_ms: u64) -> bool {
+        self.failed_occurrences
+            .insert(schedule_id.to_string(), due_at_ms)
+            != Some(due_at_ms)
+    }
+
+    fn forget_attempts(&mut self, schedule_id: &str) {
+        self.invalid_attempts.remove(schedule_id);
+        self.failed_occurrences.remove(schedule_id);
+    }
+
+    fn apply(&mut self, event: ScheduleEvent) {
+        match event {
+            ScheduleEvent::Scheduled(command) => {
+                self.forget_attempts(&command.schedule_id);
+                self.pending.insert(command.schedule_id.clone(), command);
+            }
+            ScheduleEvent::Fired {
+                schedule_id,
+                due_at_ms,
+            } => {
+                self.forget_attempts(&schedule_id);
+                let Some(command) = self.pending.get_mut(&schedule_id) else {
+                    return;
+                };
+                match command.every_ms {
+                    Some(every_ms) => command.due_at_ms = due_at_ms + every_ms.max(1),
+                    None => {
+                        self.pending.remove(&schedule_id);
+                    }
+                }
+            }
+            ScheduleEvent::Cancelled { schedule_id }
+            | ScheduleEvent::Parked { schedule_id, .. } => {
+                self.forget_attempts(&schedule_id);
+                self.pending.remove(&schedule_id);
+            }
+        }
+    }
+}
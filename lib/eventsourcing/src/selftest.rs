@@ -0,0 +1,255 @@
+//! End-to-end smoke test run on boot, e.g. after a deployment.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use ractor::{async_trait, call_t, Actor, ActorProcessingErr};
+use serde::{Deserialize, Serialize};
+
+use crate::bus::{stream_type, EventHandler};
+use crate::envelope::{self, EventEnvelope};
+use crate::projection::{ProjectionActor, ProjectionMessage, Projector};
+use crate::snapshot::{InMemorySnapshotStore, Snapshot, SnapshotStore};
+use crate::store::{EventStore, ExpectedVersion, RecordedEvent};
+
+/// Stream type reserved for probe events. Handlers subscribed to other stream
+/// types never see them.
+pub const STREAM_TYPE: &str = "selftest";
+
+/// Command-line flag asking a binary to run the self-test and exit.
+pub const FLAG: &str = "--self-test";
+
+/// Event id of the probe. Stores skip an append whose event ids are all among
+/// the latest events of the stream, so however often the self-test runs, the
+/// probe stream keeps a single event.
+const PROBE_EVENT_ID: &str = "selftest-probe";
+
+const QUERY_TIMEOUT_MS: u64 = 1000;
+
+/// Outcome of each check, in the order they ran.
+///
+/// Checks stop at the first failure since later ones depend on it.
+#[derive(Debug, Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<(&'static str, Result<(), String>)>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, result)| result.is_ok())
+    }
+
+    fn check(&mut self, name: &'static str, result: Result<(), String>) -> bool {
+        let passed = result.is_ok();
+        self.checks.push((name, result));
+        passed
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, result) in &self.checks {
+            match result {
+                Ok(()) => writeln!(f, "self-test {}: pass", name)?,
+                Err(err) => writeln!(f, "self-test {}: FAIL: {}", name, err)?,
+            }
+        }
+        write!(
+            f,
+            "self-test {}",
+            if self.passed() { "passed" } else { "failed" }
+        )
+    }
+}
+
+/// Returned by [`run_and_print`] when a check failed.
+#[derive(Debug)]
+pub struct SelfTestFailed;
+
+impl fmt::Display for SelfTestFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("self-test failed")
+    }
+}
+
+impl Error for SelfTestFailed {}
+
+/// Whether the binary was started with [`FLAG`].
+pub fn requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == FLAG)
+}
+
+/// Runs the self-test against `store` and prints the report, for binaries
+/// started with [`FLAG`].
+pub async fn run_and_print(store: &Arc<dyn EventStore>) -> Result<(), SelfTestFailed> {
+    let report = run(store).await;
+    println!("{}", report);
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(SelfTestFailed)
+    }
+}
+
+/// Appends the probe event to the `selftest/probe` stream of `store`, reads
+/// it back, folds it with a [`ProjectionActor`], and, with the `scheduler`
+/// feature, checks that an actor receives a timer tick.
+pub async fn run(store: &Arc<dyn EventStore>) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    let stream_id = format!("{}/probe", STREAM_TYPE);
+
+    let appended = match probe(&stream_id) {
+        Ok(probe) => store
+            .append(&stream_id, ExpectedVersion::Any, vec![probe])
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = appended {
+        report.check("append", Err(err));
+        return report;
+    }
+    report.check("append", Ok(()));
+
+    let read_back = match store.read_stream(&stream_id, 0).await {
+        Ok(events) => events,
+        Err(err) => {
+            report.check("read back", Err(err.to_string()));
+            return report;
+        }
+    };
+    let Some(probe) = read_back
+        .into_iter()
+        .rfind(|event| envelope::event_id(&event.payload) == Some(PROBE_EVENT_ID))
+    else {
+        report.check("read back", Err("probe event not found".to_string()));
+        return report;
+    };
+    report.check("read back", Ok(()));
+
+    if !report.check("projection fold", fold(store, &probe).await) {
+        return report;
+    }
+
+    #[cfg(feature = "scheduler")]
+    report.check("scheduler tick", tick::check().await);
+    report
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Probe {
+    written_at_ms: u64,
+}
+
+fn probe(stream_id: &str) -> Result<serde_json::Value, serde_json::Error> {
+    let probe = Probe {
+        written_at_ms: crate::now_ms(),
+    };
+    serde_json::to_value(EventEnvelope::new(stream_id, &probe, 1)?.with_event_id(PROBE_EVENT_ID))
+}
+
+/// Number of probe events folded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProbeCount(u64);
+
+impl Projector for ProbeCount {
+    const NAME: &'static str = "selftest-probe-count";
+}
+
+#[async_trait]
+impl EventHandler for ProbeCount {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        if stream_type(&event.stream_id) == STREAM_TYPE {
+            event.decode::<Probe>()?;
+            self.0 += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a projection started right before `probe` folds it, without
+/// scanning the rest of the log.
+async fn fold(store: &Arc<dyn EventStore>, probe: &RecordedEvent) -> Result<(), String> {
+    let checkpoints = Arc::new(InMemorySnapshotStore::new());
+    checkpoints
+        .save(Snapshot {
+            stream_id: ProjectionActor::<ProbeCount>::checkpoint_id(),
+            version: probe.position - 1,
+            state: serde_json::to_value(ProbeCount::default()).map_err(|err| err.to_string())?,
+        })
+        .await
+        .map_err(|err| err.to_string())?;
+    let (projection, _) = Actor::spawn(
+        None,
+        ProjectionActor::<ProbeCount>::new(Arc::clone(store), checkpoints),
+        (),
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+    let count = call_t!(projection, ProjectionMessage::Query, QUERY_TIMEOUT_MS);
+    projection.stop(None);
+    match count {
+        Ok(ProbeCount(1)) => Ok(()),
+        Ok(ProbeCount(count)) => Err(format!("folded {} probe events, expected 1", count)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Timer check, run with the `scheduler` feature whose actor relies on it.
+#[cfg(feature = "scheduler")]
+mod tick {
+    use std::time::Duration;
+
+    use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef};
+    use tokio::sync::oneshot;
+
+    const TICK_DELAY: Duration = Duration::from_millis(10);
+    const TICK_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Checks that a message scheduled with `send_after` reaches an actor.
+    pub(super) async fn check() -> Result<(), String> {
+        let (ticked, on_tick) = oneshot::channel();
+        let (probe, _) = Actor::spawn(None, TickProbe, ticked)
+            .await
+            .map_err(|err| err.to_string())?;
+        probe.send_after(TICK_DELAY, || Tick);
+        let result = tokio::time::timeout(TICK_TIMEOUT, on_tick).await;
+        probe.stop(None);
+        match result {
+            Ok(Ok(())) => Ok(()),
+            _ => Err(format!("no tick within {:?}", TICK_TIMEOUT)),
+        }
+    }
+
+    struct TickProbe;
+
+    struct Tick;
+
+    #[async_trait]
+    impl Actor for TickProbe {
+        type Msg = Tick;
+        type State = Option<oneshot::Sender<()>>;
+        type Arguments = oneshot::Sender<()>;
+
+        async fn pre_start(
+            &self,
+            _myself: ActorRef<Self::Msg>,
+            ticked: Self::Arguments,
+        ) -> Result<Self::State, ActorProcessingErr> {
+            Ok(Some(ticked))
+        }
+
+        async fn handle(
+            &self,
+            _myself: ActorRef<Self::Msg>,
+            _message: Self::Msg,
+            ticked: &mut Self::State,
+        ) -> Result<(), ActorProcessingErr> {
+            if let Some(ticked) = ticked.take() {
+                let _ = ticked.send(());
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Ed25519 signatures on event envelopes, so that consumers of the events,
+//! e.g. over [`bus`](crate::bus) relays, can check they were written by this
+//! system.
+//!
+//! Enabled with the `signing` feature.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ed25519_dalek::{Signature, Signer, Verifier};
+use ractor::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::envelope::EventEnvelope;
+use crate::store::{EventStore, ExpectedVersion, RecordedEvent, StoreError};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("event {version} of {stream_id} is not an envelope")]
+    NotAnEnvelope { stream_id: String, version: u64 },
+    #[error("event {version} of {stream_id} is not signed")]
+    Unsigned { stream_id: String, version: u64 },
+    #[error("event {version} of {stream_id} has an invalid signature")]
+    Invalid { stream_id: String, version: u64 },
+}
+
+/// Signs the envelope with `key` as event `version` of its stream,
+/// replacing any previous signature.
+pub fn sign(
+    envelope: &mut EventEnvelope,
+    version: u64,
+    key: &SigningKey,
+) -> Result<(), serde_json::Error> {
+    envelope.signature = None;
+    let unsigned = serde_json::to_value(&*envelope)?;
+    envelope.signature = Some(signature(&envelope.stream_id, version, &unsigned, key)?);
+    Ok(())
+}
+
+/// Whether the envelope carries a signature by `key` of its other fields as
+/// event `version` of its stream.
+///
+/// An envelope deserialized from one written by a newer release has lost
+/// the fields it added and fails to verify, see [`verify_recorded`].
+pub fn verify(envelope: &EventEnvelope, version: u64, key: &VerifyingKey) -> bool {
+    match serde_json::to_value(envelope) {
+        Ok(envelope_value) => verify_value(&envelope.stream_id, version, &envelope_value, key),
+        Err(_) => false,
+    }
+}
+
+/// Like [`sign`], on a serialized envelope of `stream_id`. Fields unknown to
+/// this release, e.g. added by a newer one, are signed as they are.
+fn sign_value(
+    envelope: &mut Value,
+    stream_id: &str,
+    version: u64,
+    key: &SigningKey,
+) -> Result<(), serde_json::Error> {
+    if let Some(fields) = envelope.as_object_mut() {
+        fields.remove("signature");
+        let signature = signature(stream_id, version, envelope, key)?;
+        envelope["signature"] = Value::String(signature);
+    }
+    Ok(())
+}
+
+/// Like [`verify`], on a serialized envelope of `stream_id`, covering the
+/// fields unknown to this release.
+fn verify_value(stream_id: &str, version: u64, envelope: &Value, key: &VerifyingKey) -> bool {
+    let Some(signature) = envelope
+        .get("signature")
+        .and_then(Value::as_str)
+        .and_then(from_hex)
+    else {
+        return false;
+    };
+    let mut unsigned = envelope.clone();
+    if let Some(fields) = unsigned.as_object_mut() {
+        fields.remove("signature");
+    }
+    match message(stream_id, version, &unsigned) {
+        Ok(message) => key
+            .verify(&message, &Signature::from_bytes(&signature))
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn signature(
+    stream_id: &str,
+    version: u64,
+    unsigned: &Value,
+    key: &SigningKey,
+) -> Result<String, serde_json::Error> {
+    let message = message(stream_id, version, unsigned)?;
+    Ok(to_hex(&key.sign(&message).to_bytes()))
+}
+
+/// Verifies a recorded event, which must be an [`EventEnvelope`] signed for
+/// the stream and version it was recorded at, so that an event copied to
+/// another stream or replayed later in its own is rejected.
+pub fn verify_recorded(event: &RecordedEvent, key: &VerifyingKey) -> Result<(), SignatureError> {
+    let envelope =
+        EventEnvelope::deserialize(&event.payload).map_err(|_| SignatureError::NotAnEnvelope {
+            stream_id: event.stream_id.clone(),
+            version: event.version,
+        })?;
+    if envelope.signature.is_none() {
+        return Err(SignatureError::Unsigned {
+            stream_id: event.stream_id.clone(),
+            version: event.version,
+        });
+    }
+    if envelope.stream_id != event.stream_id
+        || !verify_value(&event.stream_id, event.version, &event.payload, key)
+    {
+        return Err(SignatureError::Invalid {
+            stream_id: event.stream_id.clone(),
+            version: event.version,
+        });
+    }
+    Ok(())
+}
+
+/// Bytes signed for a serialized envelope, without its signature, recorded
+/// as event `version` of `stream_id`: the canonical JSON of
+/// `[stream_id, version, envelope]`.
+fn message(stream_id: &str, version: u64, unsigned: &Value) -> Result<Vec<u8>, serde_json::Error> {
+    let value = serde_json::to_value((stream_id, version, unsigned))?;
+    let mut message = Vec::new();
+    write_canonical(&value, &mut message)?;
+    Ok(message)
+}
+
+/// Writes `value` as compact JSON with the keys of every object sorted, so
+/// that the bytes don't depend on the field order of the source or on the
+/// `preserve_order` feature of `serde_json`.
+fn write_canonical(value: &Value, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(values) => {
+            out.push(b'[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                write_canonical(value, out)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(fields) => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_unstable_by_key(|(key, _)| key.as_str());
+            out.push(b'{');
+            for (index, (key, value)) in fields.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical(value, out)?;
+            }
+            out.push(b'}');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+/// Store decorator signing envelopes on append and verifying them on read.
+///
+/// Envelopes are signed as appended, fields unknown to this release
+/// included, so that they cannot be altered either.
+///
+/// Payloads appended without an envelope are rejected, as are events read
+/// back without a valid signature. Actors, subscribers and projections
+/// reading through this store therefore only see authenticated events.
+///
+/// Signatures cover the version of the event, so appends with
+/// [`ExpectedVersion::Any`] are made at the version last seen in the stream
+/// and retried if another writer got there first.
+pub struct SigningEventStore {
+    inner: Arc<dyn EventStore>,
+    signing_key: SigningKey,
+    verifying_key: VerifyingKey,
+    /// Last version seen per stream, to read only newer events when looking
+    /// up the version of an append with [`ExpectedVersion::Any`].
+    versions: Mutex<HashMap<String, u64>>,
+}
+
+impl SigningEventStore {
+    pub fn new(inner: Arc<dyn EventStore>, signing_key: SigningKey) -> Self {
+        let verifying_key = signing_key.verifying_key();
+        Self {
+            inner,
+            signing_key,
+            verifying_key,
+            versions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Key consumers need to verify the events, see [`verify_recorded`].
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.verifying_key
+    }
+
+    fn verify_all(&self, events: &[RecordedEvent]) -> Result<(), StoreError> {
+        for event in events {
+            verify_recorded(event, &self.verifying_key)
+                .map_err(|err| StoreError::Backend(Box::new(err)))?;
+        }
+        Ok(())
+    }
+
+    async fn stream_version(&self, stream_id: &str) -> Result<u64, StoreError> {
+        let seen = self.seen_version(stream_id);
+        let version = self
+            .inner
+            .read_stream(stream_id, seen)
+            .await?
+            .last()
+            .map_or(seen, |event| event.version);
+        self.see_version(stream_id, version);
+        Ok(version)
+    }
+
+    fn seen_version(&self, stream_id: &str) -> u64 {
+        let versions = self.versions.lock().expect("versions poisoned");
+        versions.get(stream_id).copied().unwrap_or_default()
+    }
+
+    fn see_version(&self, stream_id: &str, version: u64) {
+        let mut versions = self.versions.lock().expect("versions poisoned");
+        let seen = versions.entry(stream_id.to_string()).or_default();
+        *seen = version.max(*seen);
+    }
+
+    /// Signs the envelopes in `payloads` as the events following `version`
+    /// of the stream.
+    fn sign_all(
+        &self,
+        stream_id: &str,
+        version: u64,
+        payloads: &[Value],
+    ) -> Result<Vec<Value>, StoreError> {
+        let mut signed = Vec::with_capacity(payloads.len());
+        for (payload, version) in payloads.iter().zip(version + 1..) {
+            let envelope = EventEnvelope::deserialize(payload).map_err(|_| {
+                StoreError::Backend(Box::new(SignatureError::NotAnEnvelope {
+                    stream_id: stream_id.to_string(),
+                    version,
+                }))
+            })?;
+            let mut payload = payload.clone();
+            sign_value(
+                &mut payload,
+                &envelope.stream_id,
+                version,
+                &self.signing_key,
+            )?;
+            signed.push(payload);
+        }
+        Ok(signed)
+    }
+}
+
+#[async_trait]
+impl EventStore for SigningEventStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<Value>,
+    ) -> Result<u64, StoreError> {
+        loop {
+            let version = match expected_version {
+                ExpectedVersion::Exact(version) => version,
+                ExpectedVersion::NoStream => 0,
+                ExpectedVersion::Any => self.stream_version(stream_id).await?,
+            };
+            let signed = self.sign_all(stream_id, version, &payloads)?;
+            let result = self
+                .inner
+                .append(stream_id, ExpectedVersion::Exact(version), signed)
+                .await;
+            match result {
+                Ok(version) => {
+                    self.see_version(stream_id, version);
+                    return Ok(version);
+                }
+                Err(StoreError::WrongExpectedVersion { .. })
+                    if expected_version == ExpectedVersion::Any => {}
+                Err(StoreError::WrongExpectedVersion { actual, .. }) => {
+                    return Err(StoreError::WrongExpectedVersion {
+                        stream_id: stream_id.to_string(),
+                        expected: expected_version,
+                        actual,
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let events = self.inner.read_stream(stream_id, after_version).await?;
+        self.verify_all(&events)?;
+        Ok(events)
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let events = self.inner.read_all(after_position, limit).await?;
+        self.verify_all(&events)?;
+        Ok(events)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; 64]> {
+    let mut bytes = [0; 64];
+    if hex.len() != 2 * bytes.len() || !hex.is_ascii() {
+        return None;
+    }
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ractor::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::store::StoreError;
+
+/// Serialized aggregate state as of a stream version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub stream_id: String,
+    pub version: u64,
+    pub state: serde_json::Value,
+}
+
+/// Keeps the latest snapshot of each stream.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync + 'static {
+    async fn load(&self, stream_id: &str) -> Result<Option<Snapshot>, StoreError>;
+
+    async fn save(&self, snapshot: Snapshot) -> Result<(), StoreError>;
+}
+
+#[derive(Default)]
+pub struct InMemorySnapshotStore {
+    snapshots: Mutex<HashMap<String, Snapshot>>,
+}
+
+impl InMemorySnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for InMemorySnapshotStore {
+    async fn load(&self, stream_id: &str) -> Result<Option<Snapshot>, StoreError> {
+        let snapshots = self.snapshots.lock().expect("snapshots poisoned");
+        Ok(snapshots.get(stream_id).cloned())
+    }
+
+    async fn save(&self, snapshot: Snapshot) -> Result<(), StoreError> {
+        let mut snapshots = self.snapshots.lock().expect("snapshots poisoned");
+        snapshots.insert(snapshot.stream_id.clone(), snapshot);
+        Ok(())
+    }
+}
+
+/// Snapshot store persisting all snapshots in a single JSON file.
+///
+/// The file is rewritten on every save through a temporary file and a rename,
+/// so a crash leaves either the old or the new content.
+pub struct JsonFileSnapshotStore {
+    path: PathBuf,
+    snapshots: Mutex<HashMap<String, Snapshot>>,
+}
+
+impl JsonFileSnapshotStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let snapshots = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            snapshots: Mutex::new(snapshots),
+        })
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for JsonFileSnapshotStore {
+    async fn load(&self, stream_id: &str) -> Result<Option<Snapshot>, StoreError> {
+        let snapshots = self.snapshots.lock().expect("snapshots poisoned");
+        Ok(snapshots.get(stream_id).cloned())
+    }
+
+    async fn save(&self, snapshot: Snapshot) -> Result<(), StoreError> {
+        let mut snapshots = self.snapshots.lock().expect("snapshots poisoned");
+        // Only committed to the map once on disk, so a failed save is not
+        // served by later loads.
+        let mut next = snapshots.clone();
+        next.insert(snapshot.stream_id.clone(), snapshot);
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&next)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        *snapshots = next;
+        Ok(())
+    }
+}
@@ -0,0 +1,7 @@
+//! Summary statistics shared by the benchmarks and the `esctl` reports.
+
+/// Nearest-rank percentile of sorted, non-empty `values`.
+pub fn percentile<T: Copy>(values: &[T], p: usize) -> T {
+    let rank = (values.len() * p).div_ceil(100).max(1);
+    values[rank - 1]
+}
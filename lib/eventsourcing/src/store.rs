@@ -0,0 +1,233 @@
+mod file;
+mod memory;
+
+use ractor::async_trait;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::envelope::{self, EventEnvelope};
+
+pub use file::JsonLinesEventStore;
+pub use memory::InMemoryEventStore;
+
+/// Number of most recent events of a stream checked for duplicate event ids
+/// on append.
+pub const DEDUPE_WINDOW: usize = 128;
+
+/// Optimistic-concurrency check performed when appending to a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedVersion {
+    /// Append regardless of the current stream version.
+    Any,
+    /// The stream must not contain any events yet.
+    NoStream,
+    /// The stream version must be exactly this value.
+    Exact(u64),
+}
+
+/// An event as persisted in the store.
+///
+/// `version` is the 1-based sequence number within the stream and `position`
+/// is the 1-based sequence number in the global log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub stream_id: String,
+    pub version: u64,
+    pub position: u64,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("stream {stream_id} is at version {actual}, expected {expected:?}")]
+    WrongExpectedVersion {
+        stream_id: String,
+        expected: ExpectedVersion,
+        actual: u64,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Error raised by a store backend living in another crate.
+    #[error(transparent)]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[async_trait]
+pub trait EventStore: Send + Sync + 'static {
+    /// Appends events to the stream and returns the new stream version.
+    ///
+    /// A retried append, whose events all carry an
+    /// [`event_id`](EventEnvelope::event_id) found among the last
+    /// [`DEDUPE_WINDOW`] events of the stream, appends nothing and returns the
+    /// current version regardless of `expected_version`.
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<serde_json::Value>,
+    ) -> Result<u64, StoreError>;
+
+    /// Reads the events of a stream whose version is greater than `after_version`.
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError>;
+
+    /// Reads at most `limit` events from all streams whose global position is
+    /// greater than `after_position`.
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError>;
+}
+
+impl RecordedEvent {
+    /// Decodes the payload, unwrapping its [`EventEnvelope`] if it has one and
+    /// upcasting it with the [installed](crate::envelope::Upcasters::install)
+    /// upcasters.
+    pub fn decode<E: DeserializeOwned>(&self) -> Result<E, StoreError> {
+        EventEnvelope::from_recorded::<E>(self)?.decode()
+    }
+
+    /// Like [`RecordedEvent::decode`], but `None` for a variant `E` does not
+    /// have, e.g. one added by a newer release running side by side during a
+    /// deploy. Read models use it to skip such events instead of stopping.
+    ///
+    /// The variant is looked up by its tag, so `E` must be an externally
+    /// tagged enum, serde's default, for unknown variants to be skipped.
+    pub fn decode_known<E: DeserializeOwned>(&self) -> Result<Option<E>, StoreError> {
+        let envelope = EventEnvelope::from_recorded::<E>(self)?.upcasted();
+        let tag = variant_tag(&envelope.payload);
+        if let (Some(variants), Some(tag)) = (variant_names::<E>(), tag) {
+            if !variants.contains(&tag) {
+                tracing::warn!(
+                    stream_id = %self.stream_id,
+                    version = self.version,
+                    "skipping unknown event {}",
+                    tag
+                );
+                return Ok(None);
+            }
+        }
+        Ok(Some(E::deserialize(envelope.payload)?))
+    }
+}
+
+/// Tag of an externally tagged enum variant: the string of a unit variant or
+/// the only key of the others.
+fn variant_tag(payload: &serde_json::Value) -> Option<&str> {
+    match payload {
+        serde_json::Value::String(tag) => Some(tag),
+        serde_json::Value::Object(fields) if fields.len() == 1 => {
+            fields.keys().next().map(String::as_str)
+        }
+        _ => None,
+    }
+}
+
+/// Variant names of `E`, as passed by its `Deserialize` impl to
+/// `deserialize_enum`. `None` if `E` is not an externally tagged enum.
+fn variant_names<E: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut variants = None;
+    let _ = E::deserialize(VariantNames(&mut variants));
+    variants
+}
+
+/// Deserializer failing on everything, after recording the variants it is
+/// asked for.
+struct VariantNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for VariantNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not an enum"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(variants);
+        Err(de::Error::custom("variant names read"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Whether every payload has an event id that is already in `recent`, see
+/// [`EventStore::append`].
+pub fn is_retried_append<'a>(
+    payloads: &[serde_json::Value],
+    recent: impl IntoIterator<Item = &'a serde_json::Value>,
+) -> bool {
+    let Some(event_ids) = payloads
+        .iter()
+        .map(envelope::event_id)
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+    if event_ids.is_empty() {
+        return false;
+    }
+    let recent_ids: Vec<&str> = recent.into_iter().filter_map(envelope::event_id).collect();
+    event_ids.iter().all(|event_id| recent_ids.contains(event_id))
+}
+
+impl ExpectedVersion {
+    pub fn check(self, stream_id: &str, actual: u64) -> Result<(), StoreError> {
+        let matches = match self {
+            ExpectedVersion::Any => true,
+            ExpectedVersion::NoStream => actual == 0,
+            ExpectedVersion::Exact(version) => actual == version,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(StoreError::WrongExpectedVersion {
+                stream_id: stream_id.to_string(),
+                expected: self,
+                actual,
+            })
+        }
+    }
+}
+
+/// Reads the whole log after a position, one batch at a time.
+pub struct LogReader<'a> {
+    store: &'a dyn EventStore,
+    position: u64,
+    batch_size: usize,
+}
+
+impl<'a> LogReader<'a> {
+    pub fn new(store: &'a dyn EventStore, after_position: u64, batch_size: usize) -> Self {
+        Self {
+            store,
+            position: after_position,
+            batch_size,
+        }
+    }
+
+    /// The next events of the log, `None` once it is read to the end.
+    pub async fn next_batch(&mut self) -> Result<Option<Vec<RecordedEvent>>, StoreError> {
+        let events = self.store.read_all(self.position, self.batch_size).await?;
+        let Some(last) = events.last() else {
+            return Ok(None);
+        };
+        self.position = last.position;
+        Ok(Some(events))
+    }
+}
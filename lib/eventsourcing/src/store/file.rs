@@ -0,0 +1,119 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use ractor::async_trait;
+
+use super::memory::EventLog;
+use super::{EventStore, ExpectedVersion, RecordedEvent, StoreError};
+
+/// Append-only event store writing one JSON document per line.
+///
+/// The whole file is loaded into memory on open, and every append is flushed
+/// to disk before the events become visible to readers.
+///
+/// A final line without its newline is what remains of an append interrupted
+/// by a crash, it is truncated on open. A failed append is rolled back the
+/// same way.
+pub struct JsonLinesEventStore {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    file: File,
+    /// Length of the file up to the last complete line.
+    len: u64,
+    log: EventLog,
+}
+
+impl JsonLinesEventStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+
+        let mut contents = Vec::new();
+        (&file).read_to_end(&mut contents)?;
+        let len = contents
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        if len < contents.len() {
+            tracing::warn!(
+                "truncating {} bytes of an interrupted append",
+                contents.len() - len
+            );
+            file.set_len(len as u64)?;
+        }
+
+        let mut log = EventLog::default();
+        for line in contents[..len].split(|&byte| byte == b'\n') {
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            let event: RecordedEvent = serde_json::from_slice(line)?;
+            log.extend(vec![event]);
+        }
+
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                file,
+                len: len as u64,
+                log,
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl EventStore for JsonLinesEventStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<serde_json::Value>,
+    ) -> Result<u64, StoreError> {
+        let mut inner = self.inner.lock().expect("event log poisoned");
+        let events = inner.log.prepare(stream_id, expected_version, payloads)?;
+
+        let mut buf = Vec::new();
+        for event in &events {
+            serde_json::to_writer(&mut buf, event)?;
+            buf.push(b'\n');
+        }
+        if let Err(err) = inner
+            .file
+            .write_all(&buf)
+            .and_then(|_| inner.file.sync_data())
+        {
+            // Drop whatever part of the events was written.
+            let _ = inner.file.set_len(inner.len);
+            return Err(err.into());
+        }
+        inner.len += buf.len() as u64;
+
+        inner.log.extend(events);
+        Ok(inner.log.stream_version(stream_id))
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let inner = self.inner.lock().expect("event log poisoned");
+        Ok(inner.log.read_stream(stream_id, after_version))
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let inner = self.inner.lock().expect("event log poisoned");
+        Ok(inner.log.read_all(after_position, limit))
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ractor::async_trait;
+
+use super::{
+    is_retried_append, EventStore, ExpectedVersion, RecordedEvent, StoreError, DEDUPE_WINDOW,
+};
+
+/// Event store keeping the whole log in memory, mainly for examples and tests.
+#[derive(Default)]
+pub struct InMemoryEventStore {
+    log: Mutex<EventLog>,
+}
+
+/// The global log plus a per-stream index into it.
+#[derive(Default)]
+pub(crate) struct EventLog {
+    events: Vec<RecordedEvent>,
+    streams: HashMap<String, Vec<usize>>,
+}
+
+impl InMemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EventStore for InMemoryEventStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<serde_json::Value>,
+    ) -> Result<u64, StoreError> {
+        let mut log = self.log.lock().expect("event log poisoned");
+        let events = log.prepare(stream_id, expected_version, payloads)?;
+        log.extend(events);
+        Ok(log.stream_version(stream_id))
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let log = self.log.lock().expect("event log poisoned");
+        Ok(log.read_stream(stream_id, after_version))
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let log = self.log.lock().expect("event log poisoned");
+        Ok(log.read_all(after_position, limit))
+    }
+}
+
+impl EventLog {
+    pub(crate) fn stream_version(&self, stream_id: &str) -> u64 {
+        self.streams
+            .get(stream_id)
+            .map_or(0, |indices| indices.len() as u64)
+    }
+
+    /// Checks the expected version and numbers the new events without
+    /// modifying the log. Returns no events for a retried append.
+    pub(crate) fn prepare(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<serde_json::Value>,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let stream_version = self.stream_version(stream_id);
+        let recent = self.streams.get(stream_id).into_iter().flat_map(|indices| {
+            indices
+                .iter()
+                .rev()
+                .take(DEDUPE_WINDOW)
+                .map(|&index| &self.events[index].payload)
+        });
+        if is_retried_append(&payloads, recent) {
+            return Ok(Vec::new());
+        }
+        expected_version.check(stream_id, stream_version)?;
+
+        let position = self.events.len() as u64;
+        Ok(payloads
+            .into_iter()
+            .zip(1..)
+            .map(|(payload, offset)| RecordedEvent {
+                stream_id: stream_id.to_string(),
+                version: stream_version + offset,
+                position: position + offset,
+                payload,
+            })
+            .collect())
+    }
+
+    /// Adds events returned by [`EventLog::prepare`] to the log.
+    pub(crate) fn extend(&mut self, events: Vec<RecordedEvent>) {
+        for event in events {
+            self.streams
+                .entry(event.stream_id.clone())
+                .or_default()
+                .push(self.events.len());
+            self.events.push(event);
+        }
+    }
+
+    pub(crate) fn read_stream(&self, stream_id: &str, after_version: u64) -> Vec<RecordedEvent> {
+        self.streams
+            .get(stream_id)
+            .map(|indices| {
+                indices
+                    .iter()
+                    .skip(after_version as usize)
+                    .map(|&index| self.events[index].clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn read_all(&self, after_position: u64, limit: usize) -> Vec<RecordedEvent> {
+        self.events
+            .iter()
+            .skip(after_position as usize)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
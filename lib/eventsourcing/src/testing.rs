@@ -0,0 +1,255 @@
+//! Helpers for unit-testing aggregates and projections without an event
+//! store or actors.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use ractor::ActorProcessingErr;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::bus::EventHandler;
+use crate::envelope::{Correlation, EventEnvelope, Upcasters};
+use crate::store::RecordedEvent;
+use crate::{Aggregate, CommandResult};
+
+/// Builds recorded events with consecutive stream versions and global
+/// positions, as a store would assign them. Payloads are wrapped in a version
+/// 1 [`EventEnvelope`].
+///
+/// Timestamps are deterministic: they start at [`START_TIMESTAMP_MS`] and
+/// advance by one second per event, unless set with [`at`](Self::at).
+#[derive(Debug)]
+pub struct EnvelopeBuilder {
+    position: u64,
+    versions: HashMap<String, u64>,
+    timestamp_ms: u64,
+    correlation: Option<Correlation>,
+}
+
+/// Timestamp of the first event built by an [`EnvelopeBuilder`].
+pub const START_TIMESTAMP_MS: u64 = 1_700_000_000_000;
+
+impl Default for EnvelopeBuilder {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            versions: HashMap::new(),
+            timestamp_ms: START_TIMESTAMP_MS,
+            correlation: None,
+        }
+    }
+}
+
+impl EnvelopeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the timestamp of the next event; later ones follow a second
+    /// apart.
+    pub fn at(&mut self, timestamp_ms: u64) -> &mut Self {
+        self.timestamp_ms = timestamp_ms;
+        self
+    }
+
+    /// Correlates the next events, or stops correlating them with `None`.
+    pub fn correlated(&mut self, correlation: Option<Correlation>) -> &mut Self {
+        self.correlation = correlation;
+        self
+    }
+
+    pub fn event(&mut self, stream_id: &str, payload: impl Serialize) -> RecordedEvent {
+        let version = self.versions.entry(stream_id.to_string()).or_default();
+        *version += 1;
+        self.position += 1;
+        let mut envelope =
+            EventEnvelope::new(stream_id, &payload, 1).expect("test payload must serialize");
+        envelope.timestamp_ms = self.timestamp_ms;
+        self.timestamp_ms += 1000;
+        if let Some(correlation) = &self.correlation {
+            envelope = envelope.with_correlation(correlation);
+        }
+        RecordedEvent {
+            stream_id: stream_id.to_string(),
+            version: *version,
+            position: self.position,
+            payload: serde_json::to_value(envelope).expect("envelope must serialize"),
+        }
+    }
+}
+
+/// Feeds events through a projection and exposes it for assertions.
+pub struct ProjectionTester<H> {
+    projection: H,
+    envelopes: EnvelopeBuilder,
+}
+
+impl<H: EventHandler> ProjectionTester<H> {
+    pub fn new(projection: H) -> Self {
+        Self {
+            projection,
+            envelopes: EnvelopeBuilder::new(),
+        }
+    }
+
+    /// Wraps `payload` in the next envelope for `stream_id` and handles it.
+    pub async fn feed(
+        &mut self,
+        stream_id: &str,
+        payload: impl Serialize,
+    ) -> Result<(), ActorProcessingErr> {
+        let event = self.envelopes.event(stream_id, payload);
+        self.projection.handle_event(&event).await
+    }
+
+    /// Handles an already built event, e.g. to test duplicate delivery.
+    pub async fn feed_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.projection.handle_event(event).await
+    }
+
+    pub fn envelopes(&mut self) -> &mut EnvelopeBuilder {
+        &mut self.envelopes
+    }
+
+    pub fn read_model(&self) -> &H {
+        &self.projection
+    }
+}
+
+/// Given-when-then tests of an [`Aggregate`], run synchronously against its
+/// pure logic.
+///
+/// ```ignore
+/// AggregateTestFixture::<Calculator>::new()
+///     .given([CalculatorEvent::DidAdd { value: 8 }])
+///     .when(CalculatorCommand::Div { value: 0 })
+///     .then_error(CalculatorError::DivisionByZero);
+/// ```
+pub struct AggregateTestFixture<A> {
+    aggregate: A,
+}
+
+impl<A: Aggregate> Default for AggregateTestFixture<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Aggregate> AggregateTestFixture<A> {
+    pub fn new() -> Self {
+        Self::with_aggregate(A::default())
+    }
+
+    /// Starts from `aggregate` instead of the default one, e.g. to set
+    /// configuration that is not rebuilt from events.
+    pub fn with_aggregate(aggregate: A) -> Self {
+        Self { aggregate }
+    }
+
+    /// Applies past events.
+    pub fn given(mut self, events: impl IntoIterator<Item = A::Event>) -> Self {
+        for event in events {
+            self.aggregate.apply_event(event);
+        }
+        self
+    }
+
+    /// Handles `command`, then applies the events it produced.
+    pub fn when(self, command: A::Command) -> AggregateTestOutcome<A> {
+        let mut aggregate = self.aggregate;
+        let result = aggregate.handle_command(command);
+        if let Ok(events) = &result {
+            for event in events.iter().cloned() {
+                aggregate.apply_event(event);
+            }
+        }
+        AggregateTestOutcome { aggregate, result }
+    }
+}
+
+/// Result of [`AggregateTestFixture::when`], to assert on.
+pub struct AggregateTestOutcome<A: Aggregate> {
+    aggregate: A,
+    result: CommandResult<A>,
+}
+
+impl<A: Aggregate> AggregateTestOutcome<A> {
+    #[track_caller]
+    pub fn then_events(self, expected: impl IntoIterator<Item = A::Event>) -> Self
+    where
+        A::Event: PartialEq,
+    {
+        let expected: Vec<_> = expected.into_iter().collect();
+        match &self.result {
+            Ok(events) => assert_eq!(events, &expected),
+            Err(err) => panic!("expected events {:?}, got error {:?}", expected, err),
+        }
+        self
+    }
+
+    #[track_caller]
+    pub fn then_error(self, expected: A::Error) -> Self
+    where
+        A::Error: PartialEq,
+    {
+        match &self.result {
+            Ok(events) => panic!("expected error {:?}, got events {:?}", expected, events),
+            Err(err) => assert_eq!(err, &expected),
+        }
+        self
+    }
+
+    /// The aggregate after the events produced by the command were applied.
+    pub fn aggregate(&self) -> &A {
+        &self.aggregate
+    }
+}
+
+/// Reads a golden fixture: recorded events, one JSON object per line, as an
+/// earlier revision of the code persisted them.
+///
+/// Fixtures are checked in under `tests/fixtures/` and never edited. When the
+/// persisted shape of an event changes, bump its version, register an
+/// upcaster and add a fixture of the new shape next to the old ones. Decoding
+/// all of them with [`decode_fixture`] catches changes that would break
+/// existing event logs.
+#[track_caller]
+pub fn read_fixture(path: impl AsRef<Path>) -> Vec<RecordedEvent> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("cannot read fixture {}: {}", path.display(), err));
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).unwrap_or_else(|err| {
+                panic!("{}:{}: not a recorded event: {}", path.display(), index + 1, err)
+            })
+        })
+        .collect()
+}
+
+/// Decodes every event of a golden fixture with the upcasters the code
+/// registers, panicking on the first one that no longer decodes.
+#[track_caller]
+pub fn decode_fixture<E: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    upcasters: &Upcasters,
+) -> Vec<E> {
+    let path = path.as_ref();
+    read_fixture(path)
+        .iter()
+        .map(|event| {
+            upcasters.decode(event).unwrap_or_else(|err| {
+                panic!(
+                    "{}: event {} of {} no longer decodes: {}",
+                    path.display(),
+                    event.version,
+                    event.stream_id,
+                    err
+                )
+            })
+        })
+        .collect()
+}
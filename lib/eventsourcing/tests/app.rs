@@ -0,0 +1,191 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eventsourcing::app::{App, RecurringCommand};
+use eventsourcing::bus::EventHandler;
+use eventsourcing::projection::Projector;
+use eventsourcing::store::{InMemoryEventStore, RecordedEvent};
+use eventsourcing::{Aggregate, AggregateRepository};
+use ractor::{async_trait, ActorProcessingErr};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const PERIOD: Duration = Duration::from_secs(3600);
+const RPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+/// Sum of every increment in the log.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Total {
+    total: i64,
+}
+
+impl Projector for Total {
+    const NAME: &'static str = "total";
+}
+
+#[async_trait]
+impl EventHandler for Total {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.total += event.decode::<Incremented>()?.by;
+        Ok(())
+    }
+}
+
+/// Copies the increments of `counter/A` to `counter/mirror`.
+struct Mirror {
+    counters: AggregateRepository<Counter>,
+}
+
+#[async_trait]
+impl EventHandler for Mirror {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        if event.stream_id == "counter/A" {
+            let Incremented { by } = event.decode()?;
+            self.counters
+                .execute("counter/mirror", Increment { by })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+fn monthly_fee() -> Vec<RecurringCommand> {
+    vec![RecurringCommand {
+        schedule_id: "monthly-fee/A".to_string(),
+        stream_id: "account/A".to_string(),
+        command: json!({ "ApplyFee": { "value": 2 } }),
+        period: PERIOD,
+    }]
+}
+
+#[tokio::test]
+async fn registered_subsystems_are_wired_together() {
+    let builder = App::builder(Arc::new(InMemoryEventStore::new())).with_aggregate::<Counter>();
+    let mirror = Mirror {
+        counters: builder.repository::<Counter>().unwrap(),
+    };
+    let app = builder
+        .with_projection::<Total>()
+        .with_subscriber("mirror", &["counter"], mirror)
+        .build()
+        .await
+        .unwrap();
+    let counters = app.repository::<Counter>().unwrap();
+    for by in [2, 3] {
+        counters
+            .execute_with_reply("counter/A", Increment { by }, RPC_TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    // The mirror follows asynchronously.
+    while counters
+        .get_state("counter/mirror", RPC_TIMEOUT)
+        .await
+        .unwrap()
+        .total
+        != 5
+    {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(app.query::<Total>().await.unwrap().total, 10);
+    app.shutdown().await.unwrap();
+}
+
+#[cfg(feature = "scheduler")]
+mod with_scheduler {
+    use eventsourcing::scheduler::{self, CommandDispatcher, DispatchError, ScheduleEvent};
+    use eventsourcing::store::EventStore;
+    use ractor::async_trait;
+    use serde_json::Value;
+
+    use super::*;
+
+    struct Ignore;
+
+    #[async_trait]
+    impl CommandDispatcher for Ignore {
+        async fn dispatch(
+            &self,
+            _stream_id: &str,
+            _command: Value,
+            _occurrence_id: &str,
+        ) -> Result<(), DispatchError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn recurring_commands_are_scheduled_once() {
+        let store = Arc::new(InMemoryEventStore::new());
+        for _ in 0..2 {
+            let app = App::builder(store.clone())
+                .with_schedule("fees", Arc::new(Ignore), monthly_fee())
+                .build()
+                .await
+                .unwrap();
+            let scheduled = app.scheduled().await.unwrap();
+            assert_eq!(scheduled.len(), 1);
+            assert_eq!(scheduled[0].0, "monthly-fee/A");
+            app.shutdown().await.unwrap();
+        }
+
+        let scheduled = store
+            .read_stream(&scheduler::stream_id("fees"), 0)
+            .await
+            .unwrap()
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.decode::<ScheduleEvent>().unwrap(),
+                    ScheduleEvent::Scheduled(_)
+                )
+            })
+            .count();
+        assert_eq!(scheduled, 1);
+    }
+}
+
+#[cfg(not(feature = "scheduler"))]
+#[tokio::test]
+async fn schedules_are_skipped_without_the_scheduler() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let app = App::builder(store)
+        .with_schedule("fees", Arc::new(()), monthly_fee())
+        .build()
+        .await
+        .unwrap();
+    assert!(app.scheduled().await.unwrap().is_empty());
+    app.shutdown().await.unwrap();
+}
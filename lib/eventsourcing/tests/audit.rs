@@ -0,0 +1,157 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use eventsourcing::audit::{self, AuditLog, CommandOutcome, CommandRecord};
+use eventsourcing::store::{
+    EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent, StoreError,
+};
+use eventsourcing::{Aggregate, AggregateActor, AggregateMessage};
+use ractor::{async_trait, call_t, Actor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const RPC_TIMEOUT_MS: u64 = 1000;
+
+fn record(stream_id: &str, command: &str, recorded_at_ms: u64) -> CommandRecord {
+    CommandRecord {
+        stream_id: stream_id.to_string(),
+        source: Some("teller".to_string()),
+        command: command.to_string(),
+        outcome: CommandOutcome::Rejected {
+            reason: "account is frozen".to_string(),
+        },
+        recorded_at_ms,
+    }
+}
+
+#[tokio::test]
+async fn query_filters_by_stream_and_time_range() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let audit = AuditLog::new(Arc::clone(&store));
+    for record in [
+        record("account/A", "Deposit { amount: 1 }", 100),
+        record("account/B", "Deposit { amount: 2 }", 150),
+        record("account/A", "Deposit { amount: 3 }", 200),
+        record("account/A", "Deposit { amount: 4 }", 300),
+    ] {
+        audit.record(&record).await.unwrap();
+    }
+
+    let commands = |records: Vec<CommandRecord>| {
+        records
+            .into_iter()
+            .map(|record| record.command)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        commands(audit.query("account/A", ..).await.unwrap()),
+        [
+            "Deposit { amount: 1 }",
+            "Deposit { amount: 3 }",
+            "Deposit { amount: 4 }"
+        ]
+    );
+    assert_eq!(
+        commands(audit.query("account/A", 150..300).await.unwrap()),
+        ["Deposit { amount: 3 }"]
+    );
+    assert!(audit.query("account/C", ..).await.unwrap().is_empty());
+
+    // Audit records stay out of the aggregate's own stream.
+    assert!(store.read_stream("account/A", 0).await.unwrap().is_empty());
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+/// Store whose audit streams cannot be appended to.
+#[derive(Default)]
+struct BrokenAuditStore {
+    inner: InMemoryEventStore,
+}
+
+#[async_trait]
+impl EventStore for BrokenAuditStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<Value>,
+    ) -> Result<u64, StoreError> {
+        if stream_id.starts_with(audit::STREAM_TYPE) {
+            return Err(StoreError::Backend("audit disk full".into()));
+        }
+        self.inner
+            .append(stream_id, expected_version, payloads)
+            .await
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.inner.read_stream(stream_id, after_version).await
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.inner.read_all(after_position, limit).await
+    }
+}
+
+#[tokio::test]
+async fn audit_failures_do_not_fail_committed_commands() {
+    let store: Arc<dyn EventStore> = Arc::new(BrokenAuditStore::default());
+    let (actor, _) = Actor::spawn(
+        None,
+        AggregateActor::<Counter>::new(Arc::clone(&store))
+            .with_audit_log(AuditLog::new(Arc::clone(&store))),
+        "counter/A".to_string(),
+    )
+    .await
+    .unwrap();
+
+    for by in [1, 2] {
+        call_t!(
+            actor,
+            AggregateMessage::ExecuteWithReply,
+            RPC_TIMEOUT_MS,
+            Increment { by }
+        )
+        .unwrap()
+        .unwrap();
+    }
+    let counter = call_t!(actor, AggregateMessage::GetState, RPC_TIMEOUT_MS).unwrap();
+    assert_eq!(counter.total, 3);
+    assert_eq!(store.read_stream("counter/A", 0).await.unwrap().len(), 2);
+}
@@ -0,0 +1,108 @@
+use std::convert::Infallible;
+
+use eventsourcing::branch::Branch;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::{Aggregate, AggregateMessage};
+use ractor::call_t;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const STREAM_ID: &str = "counter/A";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+async fn base_store() -> InMemoryEventStore {
+    let store = InMemoryEventStore::new();
+    store
+        .append(
+            STREAM_ID,
+            ExpectedVersion::NoStream,
+            [1, 10, 100]
+                .into_iter()
+                .map(|by| json!({ "by": by }))
+                .collect(),
+        )
+        .await
+        .unwrap();
+    store
+}
+
+#[tokio::test]
+async fn fork_copies_history_up_to_version() {
+    let base = base_store().await;
+    let branch = Branch::new();
+
+    assert_eq!(branch.fork(&base, STREAM_ID, 2).await.unwrap(), 2);
+    let forked = branch.store().read_stream(STREAM_ID, 0).await.unwrap();
+    assert_eq!(
+        forked
+            .iter()
+            .map(|e| e.payload["by"].clone())
+            .collect::<Vec<_>>(),
+        vec![json!(1), json!(10)]
+    );
+}
+
+#[tokio::test]
+async fn fork_where_leaves_out_events() {
+    let base = base_store().await;
+    let branch = Branch::new();
+
+    let version = branch
+        .fork_where(&base, STREAM_ID, |event| event.version != 2)
+        .await
+        .unwrap();
+    assert_eq!(version, 2);
+    let forked = branch.store().read_stream(STREAM_ID, 0).await.unwrap();
+    assert_eq!(forked[1].version, 2);
+    assert_eq!(forked[1].payload["by"], 100);
+}
+
+#[tokio::test]
+async fn hypothetical_commands_do_not_touch_history() {
+    let base = base_store().await;
+    let branch = Branch::new();
+    branch.fork(&base, STREAM_ID, 3).await.unwrap();
+
+    let actor = branch.spawn::<Counter>(STREAM_ID).await.unwrap();
+    call_t!(
+        actor,
+        AggregateMessage::ExecuteWithReply,
+        1000,
+        Increment { by: 1000 }
+    )
+    .unwrap()
+    .unwrap();
+    let counter = call_t!(actor, AggregateMessage::GetState, 1000).unwrap();
+    assert_eq!(counter.total, 1111);
+    actor.stop(None);
+
+    assert_eq!(base.read_stream(STREAM_ID, 0).await.unwrap().len(), 3);
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eventsourcing::bus::{EventBus, EventHandler, EventSubscriber, SubscriberMessage};
+use eventsourcing::snapshot::{InMemorySnapshotStore, SnapshotStore};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent};
+use ractor::concurrency::tokio_primitives::JoinHandle;
+use ractor::{async_trait, Actor, ActorProcessingErr, ActorRef};
+use serde_json::json;
+
+/// Positions of the events handled, shared with the test.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<u64>>>);
+
+#[async_trait]
+impl EventHandler for Collector {
+    async fn handle_event(&mut self, event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.0.lock().unwrap().push(event.position);
+        Ok(())
+    }
+}
+
+impl Collector {
+    async fn wait_for(&self, positions: &[u64]) {
+        for _ in 0..100 {
+            if *self.0.lock().unwrap() == positions {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(*self.0.lock().unwrap(), positions);
+    }
+}
+
+async fn append_and_publish(store: &Arc<dyn EventStore>, stream_id: &str) {
+    let version = store
+        .append(stream_id, ExpectedVersion::Any, vec![json!({ "by": 1 })])
+        .await
+        .unwrap();
+    EventBus::for_store(store).publish(&store.read_stream(stream_id, version - 1).await.unwrap());
+}
+
+async fn subscribe(
+    store: &Arc<dyn EventStore>,
+    checkpoints: Option<Arc<dyn SnapshotStore>>,
+    collector: &Collector,
+) -> (ActorRef<SubscriberMessage>, JoinHandle<()>) {
+    let mut subscriber = EventSubscriber::<Collector>::new(
+        Arc::clone(store),
+        EventBus::for_store(store),
+        &["counter"],
+    );
+    if let Some(checkpoints) = checkpoints {
+        subscriber = subscriber.with_checkpoints(checkpoints, "collector");
+    }
+    Actor::spawn(None, subscriber, collector.clone())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn subscribers_only_receive_events_of_their_store() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let other: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let collector = Collector::default();
+    subscribe(&store, None, &collector).await;
+
+    append_and_publish(&other, "counter/A").await;
+    append_and_publish(&store, "other/A").await;
+    append_and_publish(&store, "counter/A").await;
+    append_and_publish(&store, "counter/B").await;
+    collector.wait_for(&[2, 3]).await;
+}
+
+#[tokio::test]
+async fn subscribers_catch_up_on_missed_events() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    append_and_publish(&store, "counter/A").await;
+    let collector = Collector::default();
+    subscribe(&store, None, &collector).await;
+    collector.wait_for(&[1]).await;
+
+    // Appended without being published, then noticed through the gap.
+    store
+        .append("counter/A", ExpectedVersion::Any, vec![json!({ "by": 1 })])
+        .await
+        .unwrap();
+    append_and_publish(&store, "counter/B").await;
+    collector.wait_for(&[1, 2, 3]).await;
+}
+
+#[tokio::test]
+async fn subscribers_resume_from_their_checkpoint() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let checkpoints: Arc<dyn SnapshotStore> = Arc::new(InMemorySnapshotStore::new());
+    append_and_publish(&store, "counter/A").await;
+    let collector = Collector::default();
+    let (subscriber, handle) = subscribe(&store, Some(Arc::clone(&checkpoints)), &collector).await;
+    append_and_publish(&store, "counter/A").await;
+    collector.wait_for(&[1, 2]).await;
+    subscriber.stop(None);
+    handle.await.unwrap();
+
+    append_and_publish(&store, "counter/B").await;
+    let collector = Collector::default();
+    subscribe(&store, Some(checkpoints), &collector).await;
+    collector.wait_for(&[3]).await;
+}
+
+#[tokio::test]
+async fn subscribers_can_start_after_a_position() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    append_and_publish(&store, "counter/A").await;
+    append_and_publish(&store, "counter/A").await;
+    let collector = Collector::default();
+    Actor::spawn(
+        None,
+        EventSubscriber::<Collector>::new(
+            Arc::clone(&store),
+            EventBus::for_store(&store),
+            &["counter"],
+        )
+        .starting_after(1),
+        collector.clone(),
+    )
+    .await
+    .unwrap();
+
+    append_and_publish(&store, "counter/B").await;
+    collector.wait_for(&[2, 3]).await;
+}
@@ -0,0 +1,61 @@
+use eventsourcing::envelope::{self, Correlation, EventEnvelope};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::testing::{EnvelopeBuilder, START_TIMESTAMP_MS};
+use serde_json::json;
+
+#[tokio::test]
+async fn correlation_follows_the_chain_of_events() {
+    let store = InMemoryEventStore::new();
+    let initial = Correlation::new();
+    assert_eq!(initial.causation_id, None);
+    let opened = EventEnvelope::new("account/A", &json!({ "Opened": {} }), 1)
+        .unwrap()
+        .with_correlation(&initial);
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::NoStream,
+            vec![serde_json::to_value(opened).unwrap()],
+        )
+        .await
+        .unwrap();
+    let recorded = &store.read_stream("account/A", 0).await.unwrap()[0];
+    assert_eq!(
+        envelope::correlation_id(&recorded.payload),
+        Some(initial.correlation_id.as_str())
+    );
+
+    let next = Correlation::caused_by(recorded);
+    assert_eq!(next.correlation_id, initial.correlation_id);
+    assert_eq!(next.causation_id.as_deref(), Some("account/A@1"));
+    assert_ne!(Correlation::new(), Correlation::new());
+}
+
+#[test]
+fn envelope_builder_sets_timestamps_and_correlation() {
+    let mut envelopes = EnvelopeBuilder::new();
+    let first = envelopes.event("account/A", json!({ "Opened": {} }));
+    let second = envelopes.event("account/B", json!({ "Opened": {} }));
+    assert_eq!(
+        EventEnvelope::from_recorded::<()>(&first)
+            .unwrap()
+            .timestamp_ms,
+        START_TIMESTAMP_MS
+    );
+    assert_eq!(
+        EventEnvelope::from_recorded::<()>(&second)
+            .unwrap()
+            .timestamp_ms,
+        START_TIMESTAMP_MS + 1000
+    );
+    assert_eq!(envelope::correlation_id(&first.payload), None);
+
+    let caused = envelopes
+        .at(42)
+        .correlated(Some(Correlation::caused_by(&first)))
+        .event("transfer/T", json!({ "Started": {} }));
+    let envelope = EventEnvelope::from_recorded::<()>(&caused).unwrap();
+    assert_eq!(envelope.timestamp_ms, 42);
+    assert_eq!(envelope.causation_id.as_deref(), Some("account/A@1"));
+    assert!(envelope.correlation_id.is_some());
+}
@@ -0,0 +1,179 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use eventsourcing::envelope::EventEnvelope;
+use eventsourcing::snapshot::InMemorySnapshotStore;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore, StoreError};
+use eventsourcing::{Aggregate, AggregateActor, AggregateMessage, AggregateRef, CommandResult};
+use ractor::rpc::CallResult;
+use ractor::{call_t, Actor};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const RPC_TIMEOUT_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+async fn increment_once(
+    actor: &AggregateRef<Counter>,
+    command_id: &str,
+    by: i64,
+) -> CommandResult<Counter> {
+    let result = actor
+        .call(
+            |reply_port| AggregateMessage::ExecuteFrom {
+                source: None,
+                correlation: None,
+                command_id: Some(command_id.to_string()),
+                command: Increment { by },
+                reply_port: Some(reply_port),
+            },
+            Some(std::time::Duration::from_millis(RPC_TIMEOUT_MS)),
+        )
+        .await
+        .unwrap();
+    match result {
+        CallResult::Success(result) => result,
+        _ => panic!("increment {} failed", command_id),
+    }
+}
+
+fn deposit(event_id: &str) -> Value {
+    let envelope = EventEnvelope::new("account/A", &json!({ "Deposited": { "amount": 10 } }), 1)
+        .unwrap()
+        .with_event_id(event_id);
+    serde_json::to_value(envelope).unwrap()
+}
+
+#[tokio::test]
+async fn retried_append_is_skipped() {
+    let store = InMemoryEventStore::new();
+    let append = |payloads: Vec<Value>, expected_version| {
+        store.append("account/A", expected_version, payloads)
+    };
+
+    assert_eq!(
+        append(vec![deposit("d1")], ExpectedVersion::NoStream)
+            .await
+            .unwrap(),
+        1
+    );
+    // The first attempt was persisted but the caller did not learn about it.
+    assert_eq!(
+        append(vec![deposit("d1")], ExpectedVersion::NoStream)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        append(vec![deposit("d2")], ExpectedVersion::Exact(1))
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        append(
+            vec![deposit("d1"), deposit("d2")],
+            ExpectedVersion::Exact(0)
+        )
+        .await
+        .unwrap(),
+        2
+    );
+    assert_eq!(store.read_all(0, 10).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn partially_new_or_unidentified_appends_are_checked() {
+    let store = InMemoryEventStore::new();
+    store
+        .append("account/A", ExpectedVersion::NoStream, vec![deposit("d1")])
+        .await
+        .unwrap();
+
+    for payloads in [vec![deposit("d1"), deposit("d2")], vec![json!(1)]] {
+        assert!(matches!(
+            store
+                .append("account/A", ExpectedVersion::NoStream, payloads)
+                .await,
+            Err(StoreError::WrongExpectedVersion { actual: 1, .. })
+        ));
+    }
+    // Ids are only deduplicated within a stream.
+    assert_eq!(
+        store
+            .append("account/B", ExpectedVersion::NoStream, vec![deposit("d1")])
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn resent_commands_are_handled_once() {
+    let store = Arc::new(InMemoryEventStore::new());
+    // A snapshot after every event, so the restarted actor replays no ids.
+    let counter = AggregateActor::<Counter>::new(store.clone())
+        .with_snapshots(Arc::new(InMemorySnapshotStore::new()), 1);
+    let (actor, handle) = Actor::spawn(None, counter.clone(), "counter/A".to_string())
+        .await
+        .unwrap();
+
+    let first = increment_once(&actor, "i1", 5).await;
+    assert_eq!(first.unwrap(), [Incremented { by: 5 }]);
+    assert_eq!(
+        increment_once(&actor, "i1", 5).await.unwrap(),
+        [Incremented { by: 5 }]
+    );
+    increment_once(&actor, "i2", 1).await.unwrap();
+
+    // The restarted actor learns about the retry from the store.
+    actor.kill();
+    handle.await.unwrap();
+    let (actor, _) = Actor::spawn(None, counter, "counter/A".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        increment_once(&actor, "i1", 5).await.unwrap(),
+        [Incremented { by: 5 }]
+    );
+    let state = call_t!(actor, AggregateMessage::GetState, RPC_TIMEOUT_MS).unwrap();
+    assert_eq!(state.total, 6);
+
+    let events = store.read_stream("counter/A", 0).await.unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(
+        EventEnvelope::from_recorded::<Incremented>(&events[0])
+            .unwrap()
+            .event_id
+            .as_deref(),
+        Some("i1")
+    );
+}
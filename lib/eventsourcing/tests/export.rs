@@ -0,0 +1,120 @@
+use eventsourcing::envelope::{Correlation, EventEnvelope};
+use eventsourcing::export::{export, Anonymizer};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+enum TransferEvent {
+    Requested {
+        to: String,
+        holder: String,
+        amount: i64,
+    },
+}
+
+async fn append(store: &InMemoryEventStore, stream_id: &str, event: TransferEvent) {
+    let envelope = EventEnvelope::new(stream_id, &event, 1).unwrap();
+    store
+        .append(
+            stream_id,
+            ExpectedVersion::Any,
+            vec![serde_json::to_value(envelope).unwrap()],
+        )
+        .await
+        .unwrap();
+}
+
+fn requested(to: &str, holder: &str) -> TransferEvent {
+    TransferEvent::Requested {
+        to: to.to_string(),
+        holder: holder.to_string(),
+        amount: 10,
+    }
+}
+
+#[tokio::test]
+async fn export_pseudonymizes_consistently_and_redacts() {
+    let store = InMemoryEventStore::new();
+    append(&store, "account/ALICE", requested("BOB", "Alice Smith")).await;
+    store
+        .append("audit/ALICE", ExpectedVersion::Any, vec![json!({})])
+        .await
+        .unwrap();
+    append(&store, "account/BOB", requested("ALICE", "Bob Jones")).await;
+
+    let mut anonymizer = Anonymizer::new()
+        .pseudonymize("/Requested/to")
+        .redact("/Requested/holder");
+    let mut out = Vec::new();
+    let exported = export(
+        &store,
+        |stream_id| stream_id.starts_with("account/"),
+        &mut anonymizer,
+        &mut out,
+    )
+    .await
+    .unwrap();
+    assert_eq!(exported, 2);
+
+    let events: Vec<RecordedEvent> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events[0].stream_id, "account/anon-1");
+    assert_eq!(events[1].stream_id, "account/anon-2");
+    assert_eq!(
+        events.iter().map(|e| e.position).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    let first = EventEnvelope::from_recorded::<()>(&events[0]).unwrap();
+    assert_eq!(first.stream_id, "account/anon-1");
+    assert_eq!(
+        first.payload,
+        json!({ "Requested": { "to": "anon-2", "holder": "[redacted]", "amount": 10 } })
+    );
+    let second = EventEnvelope::from_recorded::<()>(&events[1]).unwrap();
+    assert_eq!(second.payload["Requested"]["to"], "anon-1");
+    assert!(!String::from_utf8_lossy(&serde_json::to_vec(&events).unwrap()).contains("ALICE"));
+}
+
+#[tokio::test]
+async fn export_pseudonymizes_ids_embedding_stream_ids() {
+    let store = InMemoryEventStore::new();
+    let envelope = EventEnvelope::new("account/ACCOUNT1", &requested("ACCOUNT2", "Al"), 1)
+        .unwrap()
+        .with_event_id("monthly-fee/ACCOUNT1@1700000000000")
+        .with_correlation(&Correlation {
+            correlation_id: "ACCOUNT2-payroll".to_string(),
+            causation_id: Some("account/ACCOUNT2@2".to_string()),
+        });
+    store
+        .append(
+            "account/ACCOUNT1",
+            ExpectedVersion::Any,
+            vec![serde_json::to_value(envelope).unwrap()],
+        )
+        .await
+        .unwrap();
+
+    let mut anonymizer = Anonymizer::new().pseudonymize("/Requested/to");
+    let mut out = Vec::new();
+    export(&store, |_| true, &mut anonymizer, &mut out)
+        .await
+        .unwrap();
+
+    let line = String::from_utf8(out).unwrap();
+    assert!(!line.contains("ACCOUNT"), "{}", line);
+    let event: RecordedEvent = serde_json::from_str(line.trim_end()).unwrap();
+    let envelope = EventEnvelope::from_recorded::<()>(&event).unwrap();
+    assert_eq!(envelope.stream_id, "account/anon-1");
+    assert_eq!(
+        envelope.event_id.as_deref(),
+        Some("monthly-fee/anon-1@1700000000000")
+    );
+    assert_eq!(envelope.correlation_id.as_deref(), Some("anon-2"));
+    assert_eq!(envelope.causation_id.as_deref(), Some("account/anon-3@2"));
+    assert_eq!(envelope.payload["Requested"]["to"], "anon-3");
+}
@@ -0,0 +1,88 @@
+use std::path::{Path, PathBuf};
+
+use eventsourcing::audit::{CommandOutcome, CommandRecord};
+use eventsourcing::envelope::Upcasters;
+#[cfg(feature = "scheduler")]
+use eventsourcing::scheduler::{ScheduleEvent, ScheduledCommand};
+use eventsourcing::snapshot::{JsonFileSnapshotStore, Snapshot, SnapshotStore};
+use eventsourcing::testing::decode_fixture;
+use serde_json::json;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+#[cfg(feature = "scheduler")]
+#[test]
+fn schedule_events_still_decode() {
+    assert_eq!(
+        decode_fixture::<ScheduleEvent>(fixture("schedule-events.jsonl"), &Upcasters::new()),
+        vec![
+            ScheduleEvent::Scheduled(ScheduledCommand {
+                schedule_id: "monthly-fee/ACCOUNT1".to_string(),
+                stream_id: "account/ACCOUNT1".to_string(),
+                command: json!({ "ApplyFee": { "value": 2 } }),
+                due_at_ms: 1793692800000,
+                every_ms: Some(2592000000),
+            }),
+            ScheduleEvent::Scheduled(ScheduledCommand {
+                schedule_id: "reminder/ACCOUNT2".to_string(),
+                stream_id: "account/ACCOUNT2".to_string(),
+                command: json!({ "Deposit": { "value": 10 } }),
+                due_at_ms: 1791100860000,
+                every_ms: None,
+            }),
+            ScheduleEvent::Fired {
+                schedule_id: "reminder/ACCOUNT2".to_string(),
+                due_at_ms: 1791100860000,
+            },
+            ScheduleEvent::Cancelled {
+                schedule_id: "monthly-fee/ACCOUNT1".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn audit_records_still_decode() {
+    let records: Vec<CommandRecord> =
+        decode_fixture(fixture("audit-records.jsonl"), &Upcasters::new());
+    assert_eq!(
+        records[1],
+        CommandRecord {
+            stream_id: "calculator/1".to_string(),
+            source: None,
+            command: "Div { value: 0 }".to_string(),
+            outcome: CommandOutcome::Rejected {
+                reason: "Division by zero".to_string()
+            },
+            recorded_at_ms: 1790409600003,
+        }
+    );
+    assert_eq!(records[2].source.as_deref(), Some("saga"));
+    assert_eq!(records[2].outcome, CommandOutcome::Accepted { events: 1 });
+}
+
+#[tokio::test]
+async fn snapshot_files_still_load() {
+    let snapshots = JsonFileSnapshotStore::open(fixture("snapshots.json")).unwrap();
+    assert_eq!(
+        snapshots.load("account/ACCOUNT1").await.unwrap(),
+        Some(Snapshot {
+            stream_id: "account/ACCOUNT1".to_string(),
+            version: 7,
+            state: json!({ "balance": 935, "recent_event_ids": ["fee-2026-09"] }),
+        })
+    );
+    assert_eq!(
+        snapshots
+            .load("calculator/1")
+            .await
+            .unwrap()
+            .unwrap()
+            .version,
+        100
+    );
+}
@@ -0,0 +1,170 @@
+#![cfg(feature = "ops")]
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use eventsourcing::ops::{OpsEvent, OpsHistory, OpsLog, RECENT_LIMIT};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use eventsourcing::testing::ProjectionTester;
+use eventsourcing::{Aggregate, AggregateActor, AggregateMessage};
+use ractor::Actor;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const STREAM_ID: &str = "counter/A";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+#[tokio::test]
+async fn actor_failures_are_recorded_in_the_ops_stream() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    let (actor, handle) = Actor::spawn(
+        None,
+        AggregateActor::<Counter>::new(Arc::clone(&store))
+            .with_ops_log(OpsLog::new(Arc::clone(&store), "counter")),
+        STREAM_ID.to_string(),
+    )
+    .await
+    .unwrap();
+
+    // A concurrent writer makes the actor's next append conflict.
+    store
+        .append(
+            STREAM_ID,
+            ExpectedVersion::NoStream,
+            vec![json!({ "by": 1 })],
+        )
+        .await
+        .unwrap();
+    actor
+        .send_message(AggregateMessage::Execute(Increment { by: 2 }))
+        .unwrap();
+    handle.await.unwrap();
+
+    let mut tester = ProjectionTester::new(OpsHistory::default());
+    for event in store.read_stream("ops/counter", 0).await.unwrap() {
+        tester.feed_event(&event).await.unwrap();
+    }
+    let history = tester.read_model();
+    assert_eq!(history.counts.get("ActorFailed"), Some(&1));
+    assert!(matches!(
+        &history.recent[0].event,
+        OpsEvent::ActorFailed { stream_id, .. } if stream_id == STREAM_ID
+    ));
+}
+
+#[tokio::test]
+async fn rehydration_failures_are_recorded_in_the_ops_stream() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+    store
+        .append(
+            STREAM_ID,
+            ExpectedVersion::NoStream,
+            vec![json!({ "by": "one" })],
+        )
+        .await
+        .unwrap();
+
+    assert!(Actor::spawn(
+        None,
+        AggregateActor::<Counter>::new(Arc::clone(&store))
+            .with_ops_log(OpsLog::new(Arc::clone(&store), "counter")),
+        STREAM_ID.to_string(),
+    )
+    .await
+    .is_err());
+
+    let events = store.read_stream("ops/counter", 0).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        events[0].decode().unwrap(),
+        OpsEvent::ActorFailed { stream_id, .. } if stream_id == STREAM_ID
+    ));
+}
+
+#[tokio::test]
+async fn ops_history_skips_unknown_kinds() {
+    let mut tester = ProjectionTester::new(OpsHistory::default());
+    // Recorded by a newer release.
+    tester
+        .feed(
+            "ops/scheduler",
+            json!({ "ActorRestarted": { "stream_id": STREAM_ID } }),
+        )
+        .await
+        .unwrap();
+    tester
+        .feed(
+            "ops/scheduler",
+            OpsEvent::ActorFailed {
+                stream_id: STREAM_ID.to_string(),
+                reason: "store closed".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+    let history = tester.read_model();
+    assert_eq!(history.counts.len(), 1);
+    assert_eq!(history.counts.get("ActorFailed"), Some(&1));
+    assert_eq!(history.recent.len(), 1);
+}
+
+#[tokio::test]
+async fn ops_history_keeps_the_latest_ops_events() {
+    let mut tester = ProjectionTester::new(OpsHistory::default());
+    tester.feed(STREAM_ID, Incremented { by: 1 }).await.unwrap();
+    for i in 0..=RECENT_LIMIT {
+        tester
+            .feed(
+                "ops/scheduler",
+                OpsEvent::DispatchFailed {
+                    schedule_id: format!("fee/{}", i),
+                    stream_id: "account/A".to_string(),
+                    reason: "mailbox closed".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    let history = tester.read_model();
+    assert_eq!(history.counts.len(), 1);
+    assert_eq!(
+        history.counts.get("DispatchFailed"),
+        Some(&(RECENT_LIMIT as u64 + 1))
+    );
+    assert_eq!(history.recent.len(), RECENT_LIMIT);
+    assert_eq!(history.recent[0].stream_id, "ops/scheduler");
+    assert!(matches!(
+        &history.recent[0].event,
+        OpsEvent::DispatchFailed { schedule_id, .. } if schedule_id == "fee/1"
+    ));
+}
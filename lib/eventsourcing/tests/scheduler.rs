@@ -0,0 +1,285 @@
+#![cfg(feature = "scheduler")]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eventsourcing::ops::{OpsEvent, OpsLog};
+use eventsourcing::scheduler::{
+    self, CommandDispatcher, DispatchError, ScheduleEvent, ScheduledCommand, Scheduler,
+    SchedulerMessage,
+};
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+use ractor::{async_trait, call_t, Actor, ActorRef};
+use serde_json::{json, Value};
+
+const RESOLUTION: Duration = Duration::from_millis(10);
+const TIMEOUT_MS: u64 = 1000;
+
+/// Records the dispatched commands, after failing the first `failures`
+/// attempts, with invalid command errors if `invalid`.
+#[derive(Default)]
+struct Recorder {
+    dispatched: Mutex<Vec<(String, Value)>>,
+    attempts: Mutex<Vec<String>>,
+    failures: Mutex<usize>,
+    invalid: bool,
+}
+
+impl Recorder {
+    fn failing(failures: usize, invalid: bool) -> Self {
+        Self {
+            failures: Mutex::new(failures),
+            invalid,
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+impl CommandDispatcher for Recorder {
+    async fn dispatch(
+        &self,
+        stream_id: &str,
+        command: Value,
+        occurrence_id: &str,
+    ) -> Result<(), DispatchError> {
+        self.attempts
+            .lock()
+            .unwrap()
+            .push(occurrence_id.to_string());
+        let mut failures = self.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            if self.invalid {
+                return Err(serde_json::from_str::<u64>("\"fee\"").unwrap_err().into());
+            }
+            return Err(DispatchError::Failed("mailbox closed".into()));
+        }
+        self.dispatched
+            .lock()
+            .unwrap()
+            .push((stream_id.to_string(), command));
+        Ok(())
+    }
+}
+
+async fn schedule_events(store: &InMemoryEventStore) -> Vec<ScheduleEvent> {
+    store
+        .read_stream(&scheduler::stream_id("fees"), 0)
+        .await
+        .unwrap()
+        .iter()
+        .map(|event| event.decode().unwrap())
+        .collect()
+}
+
+fn fee(schedule_id: &str, due_at_ms: u64, every_ms: Option<u64>) -> ScheduledCommand {
+    ScheduledCommand {
+        schedule_id: schedule_id.to_string(),
+        stream_id: "account/A".to_string(),
+        command: json!({ "ApplyFee": { "value": 2 } }),
+        due_at_ms,
+        every_ms,
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+async fn spawn(
+    store: &Arc<InMemoryEventStore>,
+    recorder: &Arc<Recorder>,
+) -> ActorRef<SchedulerMessage> {
+    let (scheduler, _) = Actor::spawn(
+        None,
+        Scheduler::new(store.clone(), recorder.clone()).with_resolution(RESOLUTION),
+        "fees".to_string(),
+    )
+    .await
+    .unwrap();
+    scheduler
+}
+
+#[tokio::test]
+async fn due_commands_fire_and_pending_ones_survive_restarts() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let recorder = Arc::new(Recorder::default());
+    let scheduler = spawn(&store, &recorder).await;
+
+    let later = now_ms() + 60_000;
+    for command in [
+        fee("now", 0, None),
+        fee("later", later, None),
+        fee("cancelled", later, None),
+    ] {
+        call_t!(scheduler, SchedulerMessage::Schedule, TIMEOUT_MS, command).unwrap();
+    }
+    assert!(call_t!(
+        scheduler,
+        SchedulerMessage::Cancel,
+        TIMEOUT_MS,
+        "cancelled".to_string()
+    )
+    .unwrap());
+    tokio::time::sleep(RESOLUTION * 20).await;
+    assert_eq!(
+        recorder.dispatched.lock().unwrap().as_slice(),
+        [(
+            "account/A".to_string(),
+            json!({ "ApplyFee": { "value": 2 } })
+        )]
+    );
+    scheduler.stop(None);
+
+    // The restarted scheduler neither fires the done command again nor
+    // forgets the pending one.
+    let scheduler = spawn(&store, &recorder).await;
+    let pending = call_t!(scheduler, SchedulerMessage::List, TIMEOUT_MS).unwrap();
+    assert_eq!(pending, [fee("later", later, None)]);
+    tokio::time::sleep(RESOLUTION * 20).await;
+    assert_eq!(recorder.dispatched.lock().unwrap().len(), 1);
+    scheduler.stop(None);
+
+    let events = store
+        .read_stream(&scheduler::stream_id("fees"), 0)
+        .await
+        .unwrap();
+    assert!(events
+        .iter()
+        .any(|event| event.decode::<ScheduleEvent>().unwrap()
+            == ScheduleEvent::Cancelled {
+                schedule_id: "cancelled".to_string()
+            }));
+}
+
+#[tokio::test]
+async fn recurring_commands_are_rescheduled_after_firing() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let recorder = Arc::new(Recorder::default());
+    let scheduler = spawn(&store, &recorder).await;
+
+    let every_ms = 60_000;
+    call_t!(
+        scheduler,
+        SchedulerMessage::Schedule,
+        TIMEOUT_MS,
+        fee("monthly", now_ms() - 2 * every_ms - 1, Some(every_ms))
+    )
+    .unwrap();
+    tokio::time::sleep(RESOLUTION * 20).await;
+
+    // Missed occurrences are caught up one per tick, then it waits a period.
+    let pending = call_t!(scheduler, SchedulerMessage::List, TIMEOUT_MS).unwrap();
+    assert!(pending[0].due_at_ms > now_ms());
+    assert!(pending[0].due_at_ms <= now_ms() + every_ms);
+    assert_eq!(recorder.dispatched.lock().unwrap().len(), 3);
+    scheduler.stop(None);
+}
+
+#[tokio::test]
+async fn commands_fire_once_handled_under_one_occurrence_id() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let recorder = Arc::new(Recorder::failing(2, false));
+    let scheduler = spawn(&store, &recorder).await;
+
+    call_t!(
+        scheduler,
+        SchedulerMessage::Schedule,
+        TIMEOUT_MS,
+        fee("once", 1000, None)
+    )
+    .unwrap();
+    tokio::time::sleep(RESOLUTION * 20).await;
+    scheduler.stop(None);
+
+    // Every attempt is at the same occurrence, and only the last one fired.
+    assert_eq!(
+        recorder.attempts.lock().unwrap().as_slice(),
+        vec![scheduler::occurrence_id("once", 1000); 3]
+    );
+    assert_eq!(recorder.dispatched.lock().unwrap().len(), 1);
+    assert_eq!(
+        schedule_events(&store).await,
+        [
+            ScheduleEvent::Scheduled(fee("once", 1000, None)),
+            ScheduleEvent::Fired {
+                schedule_id: "once".to_string(),
+                due_at_ms: 1000
+            }
+        ]
+    );
+}
+
+#[tokio::test]
+async fn dispatch_failures_are_recorded_once_per_occurrence() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let recorder = Arc::new(Recorder::failing(5, false));
+    let (scheduler, _) = Actor::spawn(
+        None,
+        Scheduler::new(store.clone(), recorder.clone())
+            .with_resolution(RESOLUTION)
+            .with_ops_log(OpsLog::new(store.clone(), "scheduler")),
+        "fees".to_string(),
+    )
+    .await
+    .unwrap();
+
+    call_t!(
+        scheduler,
+        SchedulerMessage::Schedule,
+        TIMEOUT_MS,
+        fee("once", 1000, None)
+    )
+    .unwrap();
+    tokio::time::sleep(RESOLUTION * 20).await;
+    scheduler.stop(None);
+
+    assert_eq!(recorder.attempts.lock().unwrap().len(), 6);
+    let failures = store.read_stream("ops/scheduler", 0).await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert!(matches!(
+        failures[0].decode().unwrap(),
+        OpsEvent::DispatchFailed { schedule_id, .. } if schedule_id == "once"
+    ));
+}
+
+#[tokio::test]
+async fn commands_that_never_deserialize_are_parked() {
+    let store = Arc::new(InMemoryEventStore::new());
+    let recorder = Arc::new(Recorder::failing(usize::MAX, true));
+    let scheduler = spawn(&store, &recorder).await;
+
+    call_t!(
+        scheduler,
+        SchedulerMessage::Schedule,
+        TIMEOUT_MS,
+        fee("monthly", 1000, Some(60_000))
+    )
+    .unwrap();
+    tokio::time::sleep(RESOLUTION * 20).await;
+
+    assert_eq!(recorder.attempts.lock().unwrap().len(), 3);
+    assert!(call_t!(scheduler, SchedulerMessage::List, TIMEOUT_MS)
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        schedule_events(&store).await.last(),
+        Some(ScheduleEvent::Parked { schedule_id, due_at_ms: 1000, .. }) if schedule_id == "monthly"
+    ));
+
+    // Scheduling it again resumes it.
+    call_t!(
+        scheduler,
+        SchedulerMessage::Schedule,
+        TIMEOUT_MS,
+        fee("monthly", 1000, Some(60_000))
+    )
+    .unwrap();
+    tokio::time::sleep(RESOLUTION * 20).await;
+    assert_eq!(recorder.attempts.lock().unwrap().len(), 6);
+    scheduler.stop(None);
+}
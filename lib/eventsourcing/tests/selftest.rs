@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use eventsourcing::selftest;
+use eventsourcing::store::{EventStore, InMemoryEventStore};
+
+#[tokio::test]
+async fn self_test_passes_on_a_working_store() {
+    let store: Arc<dyn EventStore> = Arc::new(InMemoryEventStore::new());
+
+    for _ in 0..2 {
+        let report = selftest::run(&store).await;
+        assert!(report.passed(), "{}", report);
+        let timer_checks = usize::from(cfg!(feature = "scheduler"));
+        assert_eq!(report.checks.len(), 3 + timer_checks);
+    }
+    // Repeated runs do not pile up probe events.
+    let probes = store.read_stream("selftest/probe", 0).await.unwrap();
+    assert_eq!(probes.len(), 1);
+}
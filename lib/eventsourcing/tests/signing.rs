@@ -0,0 +1,199 @@
+#![cfg(feature = "signing")]
+
+use std::sync::Arc;
+
+use eventsourcing::audit::{AuditLog, CommandOutcome, CommandRecord};
+use eventsourcing::envelope::EventEnvelope;
+use eventsourcing::signing::{self, SigningEventStore, SigningKey};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore};
+use serde_json::{json, Value};
+
+const KEY: [u8; 32] = [7; 32];
+
+fn deposit(stream_id: &str, amount: u64) -> Value {
+    let envelope =
+        EventEnvelope::new(stream_id, &json!({ "Deposited": { "amount": amount } }), 1).unwrap();
+    serde_json::to_value(envelope).unwrap()
+}
+
+#[tokio::test]
+async fn appended_events_are_signed_and_verified() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::NoStream,
+            vec![deposit("account/A", 10)],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(store.read_stream("account/A", 0).await.unwrap().len(), 1);
+    assert_eq!(store.read_all(0, 10).await.unwrap().len(), 1);
+    // Consumers only need the verifying key.
+    let recorded = &inner.read_stream("account/A", 0).await.unwrap()[0];
+    signing::verify_recorded(recorded, &store.verifying_key()).unwrap();
+}
+
+#[tokio::test]
+async fn fields_from_newer_releases_are_signed() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+    let mut newer = deposit("account/A", 10);
+    newer["tenant"] = json!("acme");
+
+    store
+        .append("account/A", ExpectedVersion::NoStream, vec![newer])
+        .await
+        .unwrap();
+
+    let mut recorded = store.read_stream("account/A", 0).await.unwrap()[0].clone();
+    assert_eq!(recorded.payload["tenant"], "acme");
+    recorded.payload["tenant"] = json!("other");
+    assert!(signing::verify_recorded(&recorded, &store.verifying_key()).is_err());
+}
+
+#[tokio::test]
+async fn tampered_unsigned_and_foreign_events_are_rejected() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::NoStream,
+            vec![deposit("account/A", 10)],
+        )
+        .await
+        .unwrap();
+    let mut tampered = inner.read_stream("account/A", 0).await.unwrap()[0]
+        .payload
+        .clone();
+    tampered["payload"]["Deposited"]["amount"] = json!(1000);
+    inner
+        .append("account/B", ExpectedVersion::NoStream, vec![tampered])
+        .await
+        .unwrap();
+    inner
+        .append(
+            "account/C",
+            ExpectedVersion::NoStream,
+            vec![deposit("account/C", 10)],
+        )
+        .await
+        .unwrap();
+    let mut foreign =
+        EventEnvelope::new("account/D", &json!({ "Deposited": { "amount": 10 } }), 1).unwrap();
+    signing::sign(&mut foreign, 1, &SigningKey::from_bytes(&[8; 32])).unwrap();
+    inner
+        .append(
+            "account/D",
+            ExpectedVersion::NoStream,
+            vec![serde_json::to_value(foreign).unwrap()],
+        )
+        .await
+        .unwrap();
+
+    for stream_id in ["account/B", "account/C", "account/D"] {
+        assert!(
+            store.read_stream(stream_id, 0).await.is_err(),
+            "{}",
+            stream_id
+        );
+    }
+    assert!(store.read_all(0, 10).await.is_err());
+}
+
+#[tokio::test]
+async fn payloads_without_envelope_are_not_appended() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::Any,
+            vec![deposit("account/A", 10)],
+        )
+        .await
+        .unwrap();
+
+    let err = store
+        .append(
+            "account/A",
+            ExpectedVersion::Any,
+            vec![deposit("account/A", 20), json!({ "amount": 1 })],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "event 3 of account/A is not an envelope");
+    assert_eq!(inner.read_stream("account/A", 0).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn replayed_events_are_rejected() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+    store
+        .append(
+            "account/A",
+            ExpectedVersion::NoStream,
+            vec![deposit("account/A", 10)],
+        )
+        .await
+        .unwrap();
+
+    let mut replayed = inner.read_stream("account/A", 0).await.unwrap()[0].clone();
+    signing::verify_recorded(&replayed, &store.verifying_key()).unwrap();
+    replayed.version = 2;
+    assert!(signing::verify_recorded(&replayed, &store.verifying_key()).is_err());
+}
+
+#[tokio::test]
+async fn audit_records_are_signed_at_the_version_they_land_at() {
+    let inner = Arc::new(InMemoryEventStore::new());
+    let store: Arc<dyn EventStore> = Arc::new(SigningEventStore::new(
+        inner.clone(),
+        SigningKey::from_bytes(&KEY),
+    ));
+    // Another process writing to the same log with the same key.
+    let other = SigningEventStore::new(inner.clone(), SigningKey::from_bytes(&KEY));
+    let audit = AuditLog::new(Arc::clone(&store));
+    let record = |command: &str| CommandRecord {
+        stream_id: "account/A".to_string(),
+        source: None,
+        command: command.to_string(),
+        outcome: CommandOutcome::Accepted { events: 1 },
+        recorded_at_ms: 100,
+    };
+
+    audit
+        .record(&record("Deposit { amount: 1 }"))
+        .await
+        .unwrap();
+    AuditLog::new(Arc::new(other))
+        .record(&record("Deposit { amount: 2 }"))
+        .await
+        .unwrap();
+    audit
+        .record(&record("Deposit { amount: 3 }"))
+        .await
+        .unwrap();
+
+    let commands: Vec<String> = audit
+        .query("account/A", ..)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.command)
+        .collect();
+    assert_eq!(
+        commands,
+        [
+            "Deposit { amount: 1 }",
+            "Deposit { amount: 2 }",
+            "Deposit { amount: 3 }"
+        ]
+    );
+}
@@ -0,0 +1,148 @@
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use eventsourcing::snapshot::{
+    InMemorySnapshotStore, JsonFileSnapshotStore, Snapshot, SnapshotStore,
+};
+use eventsourcing::store::{
+    EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent, StoreError,
+};
+use eventsourcing::{Aggregate, AggregateActor, AggregateMessage};
+use ractor::{async_trait, call_t, Actor};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const STREAM_ID: &str = "counter/A";
+const RPC_TIMEOUT_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    total: i64,
+}
+
+#[derive(Debug)]
+struct Increment {
+    by: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Incremented {
+    by: i64,
+}
+
+impl Aggregate for Counter {
+    type Command = Increment;
+    type Event = Incremented;
+    type Error = Infallible;
+
+    fn handle_command(&self, command: Increment) -> Result<Vec<Incremented>, Infallible> {
+        Ok(vec![Incremented { by: command.by }])
+    }
+
+    fn apply_event(&mut self, event: Incremented) {
+        self.total += event.by;
+    }
+}
+
+/// Store recording the `after_version` of every stream read.
+#[derive(Default)]
+struct RecordingStore {
+    inner: InMemoryEventStore,
+    reads: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl EventStore for RecordingStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<Value>,
+    ) -> Result<u64, StoreError> {
+        self.inner
+            .append(stream_id, expected_version, payloads)
+            .await
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.reads.lock().unwrap().push(after_version);
+        self.inner.read_stream(stream_id, after_version).await
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        self.inner.read_all(after_position, limit).await
+    }
+}
+
+#[tokio::test]
+async fn actor_rehydrates_from_the_snapshot_and_the_tail() {
+    let store = Arc::new(RecordingStore::default());
+    store
+        .append(
+            STREAM_ID,
+            ExpectedVersion::NoStream,
+            vec![json!({ "by": 1 }), json!({ "by": 1 }), json!({ "by": 1 })],
+        )
+        .await
+        .unwrap();
+    // Differs from the replayed state, to tell which one the actor used.
+    let snapshots = Arc::new(InMemorySnapshotStore::new());
+    snapshots
+        .save(Snapshot {
+            stream_id: STREAM_ID.to_string(),
+            version: 2,
+            state: json!({ "total": 100 }),
+        })
+        .await
+        .unwrap();
+
+    let (actor, _) = Actor::spawn(
+        None,
+        AggregateActor::<Counter>::new(store.clone()).with_snapshots(snapshots.clone(), 2),
+        STREAM_ID.to_string(),
+    )
+    .await
+    .unwrap();
+    let counter = call_t!(actor, AggregateMessage::GetState, RPC_TIMEOUT_MS).unwrap();
+    assert_eq!(counter.total, 101);
+    assert_eq!(*store.reads.lock().unwrap(), vec![2]);
+
+    // Two events after the snapshot, so the next one is saved.
+    call_t!(
+        actor,
+        AggregateMessage::ExecuteWithReply,
+        RPC_TIMEOUT_MS,
+        Increment { by: 1 }
+    )
+    .unwrap()
+    .unwrap();
+    let snapshot = snapshots.load(STREAM_ID).await.unwrap().unwrap();
+    assert_eq!(snapshot.version, 4);
+    assert_eq!(snapshot.state, json!({ "total": 102 }));
+}
+
+#[tokio::test]
+async fn failed_file_saves_are_not_loaded() {
+    let path = std::env::temp_dir()
+        .join(format!("eventsourcing-{}-missing", std::process::id()))
+        .join("snapshots.json");
+    let snapshots = JsonFileSnapshotStore::open(&path).unwrap();
+    let result = snapshots
+        .save(Snapshot {
+            stream_id: STREAM_ID.to_string(),
+            version: 1,
+            state: json!({ "total": 1 }),
+        })
+        .await;
+
+    assert!(matches!(result, Err(StoreError::Io(_))));
+    assert!(snapshots.load(STREAM_ID).await.unwrap().is_none());
+}
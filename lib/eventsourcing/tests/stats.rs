@@ -0,0 +1,12 @@
+use eventsourcing::stats::percentile;
+
+#[test]
+fn percentiles_use_the_nearest_rank() {
+    let values: Vec<u64> = (1..=10).collect();
+    assert_eq!(percentile(&values, 0), 1);
+    assert_eq!(percentile(&values, 50), 5);
+    assert_eq!(percentile(&values, 90), 9);
+    assert_eq!(percentile(&values, 99), 10);
+    assert_eq!(percentile(&values, 100), 10);
+    assert_eq!(percentile(&[7], 50), 7);
+}
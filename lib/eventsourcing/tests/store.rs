@@ -0,0 +1,197 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use eventsourcing::store::{
+    EventStore, ExpectedVersion, InMemoryEventStore, JsonLinesEventStore, LogReader, StoreError,
+};
+use serde_json::json;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "eventsourcing-{}-{}.jsonl",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+async fn check_expected_versions(store: &dyn EventStore) {
+    assert_eq!(
+        store
+            .append("s/A", ExpectedVersion::NoStream, vec![json!(1), json!(2)])
+            .await
+            .unwrap(),
+        2
+    );
+    assert!(matches!(
+        store
+            .append("s/A", ExpectedVersion::NoStream, vec![json!(3)])
+            .await,
+        Err(StoreError::WrongExpectedVersion { actual: 2, .. })
+    ));
+    assert!(matches!(
+        store
+            .append("s/A", ExpectedVersion::Exact(1), vec![json!(3)])
+            .await,
+        Err(StoreError::WrongExpectedVersion { actual: 2, .. })
+    ));
+    assert_eq!(
+        store
+            .append("s/A", ExpectedVersion::Exact(2), vec![json!(3)])
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        store
+            .append("s/B", ExpectedVersion::Any, vec![json!(4)])
+            .await
+            .unwrap(),
+        1
+    );
+
+    let stream = store.read_stream("s/A", 1).await.unwrap();
+    assert_eq!(
+        stream
+            .iter()
+            .map(|event| (event.version, event.payload.clone()))
+            .collect::<Vec<_>>(),
+        vec![(2, json!(2)), (3, json!(3))]
+    );
+}
+
+async fn check_read_all_paging(store: &dyn EventStore) {
+    for i in 0..5 {
+        let stream_id = if i % 2 == 0 { "p/A" } else { "p/B" };
+        store
+            .append(stream_id, ExpectedVersion::Any, vec![json!(i)])
+            .await
+            .unwrap();
+    }
+
+    let mut pages = Vec::new();
+    let mut position = 0;
+    loop {
+        let page = store.read_all(position, 2).await.unwrap();
+        let Some(last) = page.last() else {
+            break;
+        };
+        position = last.position;
+        pages.push(
+            page.iter()
+                .map(|event| event.payload.clone())
+                .collect::<Vec<_>>(),
+        );
+    }
+    assert_eq!(
+        pages,
+        vec![
+            vec![json!(0), json!(1)],
+            vec![json!(2), json!(3)],
+            vec![json!(4)]
+        ]
+    );
+
+    let mut log = LogReader::new(store, 2, 2);
+    let mut batches = Vec::new();
+    while let Some(batch) = log.next_batch().await.unwrap() {
+        batches.push(batch.len());
+    }
+    assert_eq!(batches, [2, 1]);
+}
+
+#[tokio::test]
+async fn in_memory_store_checks_expected_versions() {
+    check_expected_versions(&InMemoryEventStore::new()).await;
+}
+
+#[tokio::test]
+async fn in_memory_store_pages_the_global_log() {
+    check_read_all_paging(&InMemoryEventStore::new()).await;
+}
+
+#[tokio::test]
+async fn json_lines_store_checks_expected_versions() {
+    let path = temp_path("versions");
+    check_expected_versions(&JsonLinesEventStore::open(&path).unwrap()).await;
+}
+
+#[tokio::test]
+async fn json_lines_store_pages_the_global_log() {
+    let path = temp_path("paging");
+    check_read_all_paging(&JsonLinesEventStore::open(&path).unwrap()).await;
+}
+
+#[tokio::test]
+async fn json_lines_store_keeps_events_across_reopens() {
+    let path = temp_path("reopen");
+    {
+        let store = JsonLinesEventStore::open(&path).unwrap();
+        store
+            .append("s/A", ExpectedVersion::NoStream, vec![json!(1), json!(2)])
+            .await
+            .unwrap();
+        store
+            .append("s/B", ExpectedVersion::NoStream, vec![json!(3)])
+            .await
+            .unwrap();
+    }
+
+    let store = JsonLinesEventStore::open(&path).unwrap();
+    assert_eq!(store.read_stream("s/A", 0).await.unwrap().len(), 2);
+    let all = store.read_all(0, 10).await.unwrap();
+    assert_eq!(
+        all.iter()
+            .map(|event| (event.stream_id.as_str(), event.position))
+            .collect::<Vec<_>>(),
+        vec![("s/A", 1), ("s/A", 2), ("s/B", 3)]
+    );
+    // Versions continue where the previous run stopped.
+    assert_eq!(
+        store
+            .append("s/A", ExpectedVersion::Exact(2), vec![json!(4)])
+            .await
+            .unwrap(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn json_lines_store_drops_a_torn_final_line() {
+    let path = temp_path("torn");
+    {
+        let store = JsonLinesEventStore::open(&path).unwrap();
+        store
+            .append("s/A", ExpectedVersion::NoStream, vec![json!(1)])
+            .await
+            .unwrap();
+    }
+    // What a crash in the middle of an append leaves behind.
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(br#"{"stream_id":"s/A","version":2,"posi"#)
+        .unwrap();
+
+    let store = JsonLinesEventStore::open(&path).unwrap();
+    assert_eq!(store.read_stream("s/A", 0).await.unwrap().len(), 1);
+    store
+        .append("s/A", ExpectedVersion::Exact(1), vec![json!(2)])
+        .await
+        .unwrap();
+
+    let store = JsonLinesEventStore::open(&path).unwrap();
+    assert_eq!(
+        store
+            .read_stream("s/A", 0)
+            .await
+            .unwrap()
+            .iter()
+            .map(|event| event.payload.clone())
+            .collect::<Vec<_>>(),
+        vec![json!(1), json!(2)]
+    );
+}
@@ -0,0 +1,214 @@
+use eventsourcing::envelope::{EventEnvelope, Upcasters};
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+mod v1 {
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize)]
+    pub enum AccountEvent {
+        AmountDeposited { value: i64 },
+        Closed,
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum AccountEvent {
+    Deposited { amount: i64 },
+    Closed,
+}
+
+/// v1 -> v2: `AmountDeposited { value }` became `Deposited { amount }`.
+fn rename_deposited(mut payload: Value) -> Value {
+    if let Some(deposited) = payload.get_mut("AmountDeposited").map(Value::take) {
+        payload = json!({ "Deposited": { "amount": deposited["value"] } });
+    }
+    payload
+}
+
+fn upcasters() -> Upcasters {
+    Upcasters::new().register("AccountEvent", 1, rename_deposited)
+}
+
+async fn append(store: &InMemoryEventStore, payload: Value) -> RecordedEvent {
+    let version = store
+        .append("account/A", ExpectedVersion::Any, vec![payload])
+        .await
+        .unwrap();
+    store
+        .read_stream("account/A", version - 1)
+        .await
+        .unwrap()
+        .remove(0)
+}
+
+#[tokio::test]
+async fn v1_events_are_upcast_when_read() {
+    let store = InMemoryEventStore::new();
+    let deposited = EventEnvelope::new(
+        "account/A",
+        &v1::AccountEvent::AmountDeposited { value: 5 },
+        1,
+    )
+    .unwrap();
+    assert_eq!(deposited.event_type, "AccountEvent");
+    let closed = EventEnvelope::new("account/A", &v1::AccountEvent::Closed, 1).unwrap();
+
+    let deposited = append(&store, serde_json::to_value(deposited).unwrap()).await;
+    let closed = append(&store, serde_json::to_value(closed).unwrap()).await;
+
+    assert_eq!(
+        upcasters().decode::<AccountEvent>(&deposited).unwrap(),
+        AccountEvent::Deposited { amount: 5 }
+    );
+    assert_eq!(
+        upcasters().decode::<AccountEvent>(&closed).unwrap(),
+        AccountEvent::Closed
+    );
+    // Without the upcaster, the old shape no longer decodes.
+    assert!(deposited.decode::<AccountEvent>().is_err());
+}
+
+#[tokio::test]
+async fn current_events_are_left_untouched() {
+    let store = InMemoryEventStore::new();
+    let envelope =
+        EventEnvelope::new("account/A", &AccountEvent::Deposited { amount: 7 }, 2).unwrap();
+    let recorded = append(&store, serde_json::to_value(&envelope).unwrap()).await;
+
+    let upcast =
+        upcasters().upcast(EventEnvelope::from_recorded::<AccountEvent>(&recorded).unwrap());
+    assert_eq!(upcast, envelope);
+    assert_eq!(
+        recorded.decode::<AccountEvent>().unwrap(),
+        AccountEvent::Deposited { amount: 7 }
+    );
+}
+
+#[tokio::test]
+async fn payloads_without_envelope_are_read_as_v1() {
+    let store = InMemoryEventStore::new();
+    let recorded = append(&store, json!({ "AmountDeposited": { "value": 3 } })).await;
+
+    let envelope = EventEnvelope::from_recorded::<AccountEvent>(&recorded).unwrap();
+    assert_eq!(envelope.event_type, "AccountEvent");
+    assert_eq!(envelope.version, 1);
+    assert_eq!(
+        upcasters().decode::<AccountEvent>(&recorded).unwrap(),
+        AccountEvent::Deposited { amount: 3 }
+    );
+}
+
+#[tokio::test]
+async fn envelopes_from_a_newer_release_are_still_envelopes() {
+    let store = InMemoryEventStore::new();
+    let mut envelope = serde_json::to_value(
+        EventEnvelope::new("account/A", &AccountEvent::Deposited { amount: 7 }, 2).unwrap(),
+    )
+    .unwrap();
+    envelope["trace_parent"] = json!("00-4bf92f3577b34da6-00f067aa0ba902b7-01");
+    let recorded = append(&store, envelope).await;
+
+    let envelope = EventEnvelope::from_recorded::<AccountEvent>(&recorded).unwrap();
+    assert_eq!(envelope.version, 2);
+    assert_eq!(
+        recorded.decode::<AccountEvent>().unwrap(),
+        AccountEvent::Deposited { amount: 7 }
+    );
+}
+
+#[tokio::test]
+async fn versions_are_tracked_per_event_type() {
+    let store = InMemoryEventStore::new();
+    let upcasters = upcasters()
+        .register("TransferEvent", 1, |payload| payload)
+        .register("TransferEvent", 2, |payload| payload);
+    let envelope =
+        EventEnvelope::new("account/A", &AccountEvent::Deposited { amount: 7 }, 2).unwrap();
+    let recorded = append(&store, serde_json::to_value(&envelope).unwrap()).await;
+
+    let upcast = upcasters.upcast(EventEnvelope::from_recorded::<AccountEvent>(&recorded).unwrap());
+    assert_eq!(upcast.version, 2);
+}
+
+mod customer_v1 {
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize)]
+    pub enum CustomerEvent {
+        Registered { name: String },
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum CustomerEvent {
+    Registered { full_name: String },
+}
+
+#[tokio::test]
+async fn installed_upcasters_apply_to_every_decode() {
+    let store = InMemoryEventStore::new();
+    let envelope = EventEnvelope::new(
+        "customer/A",
+        &customer_v1::CustomerEvent::Registered {
+            name: "Ada".to_string(),
+        },
+        1,
+    )
+    .unwrap();
+    let recorded = append(&store, serde_json::to_value(envelope).unwrap()).await;
+
+    Upcasters::new()
+        .register("CustomerEvent", 1, |mut payload: Value| {
+            let name = payload["Registered"]["name"].take();
+            json!({ "Registered": { "full_name": name } })
+        })
+        .install();
+    // As decoded by projections and event handlers.
+    assert_eq!(
+        recorded.decode::<CustomerEvent>().unwrap(),
+        CustomerEvent::Registered {
+            full_name: "Ada".to_string()
+        }
+    );
+}
+
+#[tokio::test]
+async fn unknown_variants_are_skipped_by_decode_known() {
+    let store = InMemoryEventStore::new();
+    let frozen = EventEnvelope::new("account/A", &json!({ "Frozen": null }), 2).unwrap();
+    let frozen = append(&store, serde_json::to_value(frozen).unwrap()).await;
+    let closed = append(&store, json!("Closed")).await;
+    let malformed = append(&store, json!({ "Deposited": { "amount": "ten" } })).await;
+
+    assert_eq!(frozen.decode_known::<AccountEvent>().unwrap(), None);
+    assert!(frozen.decode::<AccountEvent>().is_err());
+    assert_eq!(
+        closed.decode_known::<AccountEvent>().unwrap(),
+        Some(AccountEvent::Closed)
+    );
+    // Only unknown variants are skipped.
+    assert!(malformed.decode_known::<AccountEvent>().is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Currency {
+    Eur,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum PaymentEvent {
+    Paid { currency: Currency },
+}
+
+#[tokio::test]
+async fn known_variants_failing_to_decode_are_not_skipped() {
+    let store = InMemoryEventStore::new();
+    let paid = append(&store, json!({ "Paid": { "currency": "Usd" } })).await;
+    let refunded = append(&store, json!({ "Refunded": { "currency": "Eur" } })).await;
+
+    // Fails on the unknown `Currency`, although `Paid` is known.
+    assert!(paid.decode_known::<PaymentEvent>().is_err());
+    assert_eq!(refunded.decode_known::<PaymentEvent>().unwrap(), None);
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use eventsourcing::budget::YieldBudget;
+use eventsourcing::bus::EventHandler;
+use eventsourcing::projection::{ProjectionActor, Projector};
+use eventsourcing::snapshot::InMemorySnapshotStore;
+use eventsourcing::store::{EventStore, ExpectedVersion, InMemoryEventStore, RecordedEvent};
+use ractor::{async_trait, Actor, ActorProcessingErr};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const EVENTS: usize = 10_000;
+
+/// Counts how often it gets to run while the current task is busy.
+fn spawn_ticker() -> Arc<AtomicUsize> {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&ticks);
+    tokio::spawn(async move {
+        loop {
+            counter.fetch_add(1, Ordering::Relaxed);
+            tokio::task::yield_now().await;
+        }
+    });
+    ticks
+}
+
+#[tokio::test]
+async fn budget_yields_every_n_events() {
+    let ticks = spawn_ticker();
+    let mut budget = YieldBudget::new(10, Duration::from_secs(60));
+    for _ in 0..1000 {
+        budget.tick().await;
+    }
+    assert!(ticks.load(Ordering::Relaxed) >= 100);
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EventCount(usize);
+
+#[async_trait]
+impl EventHandler for EventCount {
+    async fn handle_event(&mut self, _event: &RecordedEvent) -> Result<(), ActorProcessingErr> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+impl Projector for EventCount {
+    const NAME: &'static str = "event-count";
+}
+
+// The default test runtime has a single thread, so the ticker only runs when
+// the rebuild yields.
+#[tokio::test]
+async fn other_tasks_run_during_projection_rebuild() {
+    let store = Arc::new(InMemoryEventStore::new());
+    for index in 0..EVENTS {
+        store
+            .append("counter/1", ExpectedVersion::Any, vec![json!(index)])
+            .await
+            .unwrap();
+    }
+
+    let ticks = spawn_ticker();
+    let (actor, handle) = Actor::spawn(
+        None,
+        ProjectionActor::<EventCount>::new(store, Arc::new(InMemorySnapshotStore::new())),
+        (),
+    )
+    .await
+    .unwrap();
+    assert!(ticks.load(Ordering::Relaxed) >= EVENTS / eventsourcing::budget::DEFAULT_YIELD_EVERY);
+
+    actor.stop(None);
+    handle.await.unwrap();
+}
@@ -0,0 +1,212 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use eventsourcing::envelope;
+use eventsourcing::store::{
+    is_retried_append, EventStore, ExpectedVersion, RecordedEvent, StoreError, DEDUPE_WINDOW,
+};
+use ractor::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many
+/// of them have run.
+const MIGRATIONS: &[&str] = &[r#"
+CREATE TABLE streams (
+    stream_id TEXT PRIMARY KEY,
+    version INTEGER NOT NULL
+);
+
+CREATE TABLE events (
+    position INTEGER PRIMARY KEY AUTOINCREMENT,
+    stream_id TEXT NOT NULL REFERENCES streams (stream_id),
+    version INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    UNIQUE (stream_id, version)
+);
+"#];
+
+/// Event store backed by a SQLite database.
+///
+/// The `events` table holds the global log, where the autoincrement `position`
+/// is the global sequence number. The unique `(stream_id, version)` constraint
+/// backs the optimistic-concurrency check even when several processes share
+/// the database file.
+pub struct SqliteEventStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteEventStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open(path).map_err(backend)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory().map_err(backend)?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, StoreError> {
+        migrate(&mut conn).map_err(backend)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn backend(err: rusqlite::Error) -> StoreError {
+    StoreError::Backend(Box::new(err))
+}
+
+fn is_unique_violation(err: &rusqlite::Error) -> bool {
+    matches!(
+        err,
+        rusqlite::Error::SqliteFailure(e, _)
+            if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
+
+fn recorded_event(row: &rusqlite::Row<'_>) -> rusqlite::Result<(String, u64, u64, String)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn decode_rows(rows: Vec<(String, u64, u64, String)>) -> Result<Vec<RecordedEvent>, StoreError> {
+    rows.into_iter()
+        .map(|(stream_id, version, position, payload)| {
+            Ok(RecordedEvent {
+                stream_id,
+                version,
+                position,
+                payload: serde_json::from_str(&payload)?,
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl EventStore for SqliteEventStore {
+    async fn append(
+        &self,
+        stream_id: &str,
+        expected_version: ExpectedVersion,
+        payloads: Vec<serde_json::Value>,
+    ) -> Result<u64, StoreError> {
+        let mut conn = self.conn.lock().expect("sqlite connection poisoned");
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(backend)?;
+
+        let current: u64 = tx
+            .query_row(
+                "SELECT version FROM streams WHERE stream_id = ?1",
+                [stream_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(backend)?
+            .unwrap_or(0);
+        if payloads
+            .iter()
+            .any(|payload| envelope::event_id(payload).is_some())
+        {
+            let recent = tx
+                .prepare(
+                    "SELECT payload FROM events WHERE stream_id = ?1
+                     ORDER BY version DESC LIMIT ?2",
+                )
+                .and_then(|mut stmt| {
+                    stmt.query_map(params![stream_id, DEDUPE_WINDOW as i64], |row| {
+                        row.get::<_, String>(0)
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                })
+                .map_err(backend)?
+                .iter()
+                .map(|payload| serde_json::from_str(payload))
+                .collect::<Result<Vec<serde_json::Value>, _>>()?;
+            if is_retried_append(&payloads, &recent) {
+                return Ok(current);
+            }
+        }
+        expected_version.check(stream_id, current)?;
+
+        let version = current + payloads.len() as u64;
+        tx.execute(
+            "INSERT INTO streams (stream_id, version) VALUES (?1, ?2)
+             ON CONFLICT (stream_id) DO UPDATE SET version = excluded.version",
+            params![stream_id, version],
+        )
+        .map_err(backend)?;
+        {
+            let mut insert = tx
+                .prepare("INSERT INTO events (stream_id, version, payload) VALUES (?1, ?2, ?3)")
+                .map_err(backend)?;
+            for (payload, offset) in payloads.iter().zip(1u64..) {
+                insert
+                    .execute(params![stream_id, current + offset, payload.to_string()])
+                    .map_err(|err| {
+                        if is_unique_violation(&err) {
+                            StoreError::WrongExpectedVersion {
+                                stream_id: stream_id.to_string(),
+                                expected: expected_version,
+                                actual: current,
+                            }
+                        } else {
+                            backend(err)
+                        }
+                    })?;
+            }
+        }
+        tx.commit().map_err(backend)?;
+
+        Ok(version)
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        after_version: u64,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let conn = self.conn.lock().expect("sqlite connection poisoned");
+        let rows = conn
+            .prepare(
+                "SELECT stream_id, version, position, payload FROM events
+                 WHERE stream_id = ?1 AND version > ?2 ORDER BY version",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![stream_id, after_version], recorded_event)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(backend)?;
+        decode_rows(rows)
+    }
+
+    async fn read_all(
+        &self,
+        after_position: u64,
+        limit: usize,
+    ) -> Result<Vec<RecordedEvent>, StoreError> {
+        let conn = self.conn.lock().expect("sqlite connection poisoned");
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = conn
+            .prepare(
+                "SELECT stream_id, version, position, payload FROM events
+                 WHERE position > ?1 ORDER BY position LIMIT ?2",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![after_position, limit], recorded_event)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(backend)?;
+        decode_rows(rows)
+    }
+}
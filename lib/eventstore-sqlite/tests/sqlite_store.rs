@@ -0,0 +1,84 @@
+use eventsourcing::store::{EventStore, ExpectedVersion, StoreError};
+use eventstore_sqlite::SqliteEventStore;
+use serde_json::json;
+
+#[tokio::test]
+async fn append_and_read_back() {
+    let store = SqliteEventStore::open_in_memory().unwrap();
+
+    let version = store
+        .append("account/1", ExpectedVersion::NoStream, vec![json!(1), json!(2)])
+        .await
+        .unwrap();
+    assert_eq!(version, 2);
+    store
+        .append("account/2", ExpectedVersion::Any, vec![json!(3)])
+        .await
+        .unwrap();
+    store
+        .append("account/1", ExpectedVersion::Exact(2), vec![json!(4)])
+        .await
+        .unwrap();
+
+    let stream = store.read_stream("account/1", 1).await.unwrap();
+    assert_eq!(
+        stream
+            .iter()
+            .map(|e| (e.version, e.position, e.payload.clone()))
+            .collect::<Vec<_>>(),
+        vec![(2, 2, json!(2)), (3, 4, json!(4))]
+    );
+
+    let all = store.read_all(1, 2).await.unwrap();
+    assert_eq!(
+        all.iter()
+            .map(|e| (e.stream_id.as_str(), e.position))
+            .collect::<Vec<_>>(),
+        vec![("account/1", 2), ("account/2", 3)]
+    );
+}
+
+#[tokio::test]
+async fn rejects_wrong_expected_version() {
+    let store = SqliteEventStore::open_in_memory().unwrap();
+    store
+        .append("account/1", ExpectedVersion::Any, vec![json!(1)])
+        .await
+        .unwrap();
+
+    let err = store
+        .append("account/1", ExpectedVersion::NoStream, vec![json!(2)])
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        StoreError::WrongExpectedVersion { actual: 1, .. }
+    ));
+    assert_eq!(store.read_stream("account/1", 0).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn retried_append_is_skipped() {
+    let store = SqliteEventStore::open_in_memory().unwrap();
+    let payload = json!({
+        "event_type": "AccountEvent",
+        "version": 1,
+        "timestamp_ms": 0,
+        "stream_id": "account/1",
+        "event_id": "deposit-1",
+        "payload": { "Deposited": { "amount": 10 } },
+    });
+
+    for _ in 0..2 {
+        let version = store
+            .append(
+                "account/1",
+                ExpectedVersion::NoStream,
+                vec![payload.clone()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(version, 1);
+    }
+    assert_eq!(store.read_all(0, 10).await.unwrap().len(), 1);
+}
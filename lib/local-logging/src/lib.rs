@@ -8,7 +8,22 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::Registry;
 
-pub fn init() -> Result<()> {
+#[cfg(feature = "otlp")]
+mod otlp;
+
+/// Flushes exported spans when dropped, keep it until the end of `main`.
+#[must_use]
+pub struct LoggingGuard {
+    #[cfg(feature = "otlp")]
+    otlp: Option<otlp::Exporter>,
+}
+
+/// Logs to stderr in the Glog format, filtered by `RUST_LOG`.
+///
+/// With the `otlp` feature, spans are also exported over OTLP/HTTP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set, with the spans sharing a
+/// correlation id in one trace.
+pub fn init() -> Result<LoggingGuard> {
     let fmt = tracing_subscriber::fmt::Layer::default()
         .with_ansi(stderr().is_terminal())
         .with_writer(std::io::stderr)
@@ -18,7 +33,17 @@ pub fn init() -> Result<()> {
     let subscriber = Registry::default()
         .with(fmt)
         .with(EnvFilter::from_default_env());
-    tracing::subscriber::set_global_default(subscriber)?;
 
-    Ok(())
+    #[cfg(feature = "otlp")]
+    {
+        let otlp = otlp::Exporter::from_env()?;
+        let subscriber = subscriber.with(otlp.as_ref().map(otlp::Exporter::layer));
+        tracing::subscriber::set_global_default(subscriber)?;
+        Ok(LoggingGuard { otlp })
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        tracing::subscriber::set_global_default(subscriber)?;
+        Ok(LoggingGuard {})
+    }
 }
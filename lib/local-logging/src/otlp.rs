@@ -0,0 +1,115 @@
+//! Span export to an OpenTelemetry collector.
+
+use std::fmt;
+
+use anyhow::Result;
+use opentelemetry::trace::{TraceContextExt as _, TraceId, TracerProvider as _};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::LoggingGuard;
+
+const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+/// Span field holding the correlation id of the command or event handled in
+/// the span, as set by `eventsourcing`.
+const CORRELATION_FIELD: &str = "correlation_id";
+
+pub(crate) struct Exporter {
+    provider: SdkTracerProvider,
+}
+
+impl Exporter {
+    /// Exporter configured by the standard `OTEL_*` variables, `None` unless
+    /// an endpoint is set. The service name comes from `OTEL_SERVICE_NAME`.
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        if std::env::var_os(ENDPOINT_VAR).is_none() {
+            return Ok(None);
+        }
+        let exporter = SpanExporter::builder().with_http().build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    pub(crate) fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer(env!("CARGO_PKG_NAME")))
+            .and_then(CorrelatedTraces)
+    }
+}
+
+/// Puts the spans of one correlation in one trace.
+///
+/// Commands and events reach actors and subscribers through mailboxes and
+/// the event log, even in other processes, so their spans have no parent to
+/// inherit a trace from. The correlation id travels with them instead, and
+/// the trace id of a root span carrying one is derived from it.
+struct CorrelatedTraces;
+
+impl<S> Layer<S> for CorrelatedTraces
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = CorrelationVisitor(None);
+        attrs.record(&mut visitor);
+        let (Some(correlation_id), Some(span)) = (visitor.0, ctx.span(id)) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+        if !data.parent_cx.has_active_span() {
+            data.builder.trace_id = Some(trace_id(&correlation_id));
+        }
+    }
+}
+
+struct CorrelationVisitor(Option<String>);
+
+impl Visit for CorrelationVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == CORRELATION_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == CORRELATION_FIELD {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// 128-bit FNV-1a hash of the correlation id, the same in every process.
+fn trace_id(correlation_id: &str) -> TraceId {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let hash = correlation_id.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ u128::from(byte)).wrapping_mul(PRIME)
+    });
+    // The all-zero id is invalid.
+    TraceId::from(hash.max(1))
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        if let Some(exporter) = &self.otlp {
+            if let Err(err) = exporter.provider.shutdown() {
+                eprintln!("failed to flush spans: {}", err);
+            }
+        }
+    }
+}